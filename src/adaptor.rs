@@ -0,0 +1,462 @@
+//! Adaptor signatures for BIP-340 Schnorr and ECDSA.
+//!
+//! An adaptor signature, or pre-signature, is a signature that is encrypted
+//! with an adaptor point T = t*G. Anyone knowing the secret t can complete it
+//! into a valid signature and anyone seeing both the pre-signature and the
+//! completed signature can extract t from them.
+//!
+//! For Schnorr, the pre-signature commits to the nonce R = k*G + T. For
+//! ECDSA, we use the one-time verifiably encrypted signature scheme, where a
+//! DLEQ proof shows that the nonce R = k*T and R_a = k*G share the same
+//! discrete logarithm.
+//!
+//! WARNING: the serialization format is specific to hal. The nonce derivation
+//! and the DLEQ proofs use hal's own tagged hashes and are NOT compatible with
+//! the `ecdsa_adaptor` module of secp256k1-zkp or any other implementation.
+//! To prevent mixing them up, serialized adaptor signatures start with the
+//! bytes "hal" followed by a byte for the kind of signature, so they have
+//! different lengths than the 162-byte secp256k1-zkp encoding.
+
+use bitcoin::hashes::{sha256t_hash_newtype, Hash, HashEngine};
+use secp256k1::{ecdsa, schnorr, Keypair, Parity, PublicKey, Scalar, SecretKey, XOnlyPublicKey};
+
+use crate::frost::Bip340ChallengeHash;
+use crate::{scalar, SECP};
+
+sha256t_hash_newtype! {
+	pub struct AdaptorNonceTag = hash_str("hal/adaptor/nonce");
+
+	/// Tagged hash used to derive the nonces of adaptor signatures.
+	#[hash_newtype(forward)]
+	pub struct AdaptorNonceHash(_);
+
+	pub struct DleqChallengeTag = hash_str("hal/adaptor/dleq");
+
+	/// Tagged hash for the challenge of the DLEQ proof.
+	#[hash_newtype(forward)]
+	pub struct DleqChallengeHash(_);
+}
+
+/// Derive a nonce from the secret key, the public inputs and auxiliary randomness.
+fn derive_nonce(purpose: u8, secret: &[u8; 32], parts: &[&[u8]], aux: &[u8; 32]) -> Result<Scalar, &'static str> {
+	let mut engine = AdaptorNonceHash::engine();
+	engine.input(&[purpose]);
+	engine.input(secret);
+	for part in parts {
+		engine.input(part);
+	}
+	engine.input(aux);
+	let ret = scalar::reduce(AdaptorNonceHash::from_engine(engine).to_byte_array());
+	if scalar::is_zero(ret) {
+		return Err("derived a zero nonce, try other auxiliary randomness");
+	}
+	Ok(ret)
+}
+
+/// Add up the points, skipping any point at infinity.
+fn sum(points: &[Option<PublicKey>]) -> Option<PublicKey> {
+	scalar::point_sum(points.iter().flatten())
+}
+
+fn is_odd(point: &PublicKey) -> bool {
+	point.x_only_public_key().1 == Parity::Odd
+}
+
+fn parse_scalar(bytes: &[u8], what: &'static str) -> Result<Scalar, &'static str> {
+	let mut buf = [0u8; 32];
+	buf.copy_from_slice(bytes);
+	Scalar::from_be_bytes(buf).map_err(|_| what)
+}
+
+/// The BIP-340 challenge for the given nonce, public key and message.
+fn bip340_challenge(nonce: &PublicKey, pubkey: &XOnlyPublicKey, msg: &[u8; 32]) -> Scalar {
+	let mut engine = Bip340ChallengeHash::engine();
+	engine.input(&nonce.x_only_public_key().0.serialize());
+	engine.input(&pubkey.serialize());
+	engine.input(msg);
+	scalar::reduce(Bip340ChallengeHash::from_engine(engine).to_byte_array())
+}
+
+/// A Schnorr adaptor signature.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SchnorrAdaptorSignature {
+	/// The nonce of the final signature, R = k*G + T, with its parity.
+	pub nonce: PublicKey,
+	pub s: Scalar,
+}
+
+impl SchnorrAdaptorSignature {
+	pub fn from_slice(bytes: &[u8]) -> Result<SchnorrAdaptorSignature, &'static str> {
+		if bytes.len() != 65 {
+			return Err("Schnorr adaptor signature should be 65 bytes");
+		}
+		Ok(SchnorrAdaptorSignature {
+			nonce: PublicKey::from_slice(&bytes[0..33]).map_err(|_| "invalid nonce")?,
+			s: parse_scalar(&bytes[33..65], "invalid s value")?,
+		})
+	}
+
+	pub fn serialize(&self) -> [u8; 65] {
+		let mut ret = [0u8; 65];
+		ret[0..33].copy_from_slice(&self.nonce.serialize());
+		ret[33..65].copy_from_slice(&self.s.to_be_bytes());
+		ret
+	}
+
+	/// Create an adaptor signature for the message, encrypted with the adaptor point.
+	pub fn sign(
+		secret_key: &SecretKey,
+		adaptor: &PublicKey,
+		msg: &[u8; 32],
+		aux: &[u8; 32],
+	) -> Result<SchnorrAdaptorSignature, &'static str> {
+		let keypair = Keypair::from_secret_key(&SECP, secret_key);
+		let (pubkey, parity) = keypair.x_only_public_key();
+		let x = match parity {
+			Parity::Even => Scalar::from(*secret_key),
+			Parity::Odd => Scalar::from(secret_key.negate()),
+		};
+
+		let mut k = derive_nonce(0, &secret_key.secret_bytes(), &[
+			&pubkey.serialize(), &adaptor.serialize(), msg,
+		], aux)?;
+		let nonce = sum(&[scalar::base_mul(k), Some(*adaptor)])
+			.ok_or("nonce is the point at infinity, try other auxiliary randomness")?;
+		// The final signature uses the even version of R, so if R is odd,
+		// both k and t will be negated in it.
+		if is_odd(&nonce) {
+			k = scalar::neg(k);
+		}
+
+		let e = bip340_challenge(&nonce, &pubkey, msg);
+		Ok(SchnorrAdaptorSignature {
+			nonce,
+			s: scalar::add(k, scalar::mul(e, x)),
+		})
+	}
+
+	/// Verify the adaptor signature for the public key, adaptor point and message.
+	pub fn verify(&self, pubkey: &XOnlyPublicKey, adaptor: &PublicKey, msg: &[u8; 32]) -> bool {
+		let e = bip340_challenge(&self.nonce, pubkey, msg);
+		let p = pubkey.public_key(Parity::Even);
+
+		// s'*G = R - T + e*P, or -R + T + e*P if R is odd.
+		let (r, t) = if is_odd(&self.nonce) {
+			(self.nonce.negate(&SECP), *adaptor)
+		} else {
+			(self.nonce, adaptor.negate(&SECP))
+		};
+		scalar::base_mul(self.s) == sum(&[Some(r), Some(t), scalar::point_mul(&p, e)])
+	}
+
+	/// Complete the adaptor signature using the adaptor secret.
+	pub fn complete(&self, secret: &SecretKey) -> schnorr::Signature {
+		let s = if is_odd(&self.nonce) {
+			scalar::sub(self.s, Scalar::from(*secret))
+		} else {
+			scalar::add(self.s, Scalar::from(*secret))
+		};
+		let mut ret = [0u8; 64];
+		ret[0..32].copy_from_slice(&self.nonce.x_only_public_key().0.serialize());
+		ret[32..64].copy_from_slice(&s.to_be_bytes());
+		schnorr::Signature::from_slice(&ret).expect("64 bytes")
+	}
+
+	/// Extract the adaptor secret from the completed signature.
+	pub fn extract(
+		&self,
+		signature: &schnorr::Signature,
+		adaptor: &PublicKey,
+	) -> Result<SecretKey, &'static str> {
+		let sig = signature.serialize();
+		if sig[0..32] != self.nonce.x_only_public_key().0.serialize() {
+			return Err("signature doesn't use the nonce of the adaptor signature");
+		}
+		let s = parse_scalar(&sig[32..64], "invalid signature s value")?;
+		let t = if is_odd(&self.nonce) {
+			scalar::sub(self.s, s)
+		} else {
+			scalar::sub(s, self.s)
+		};
+		let secret = scalar::to_secret(t).ok_or("extracted a zero secret")?;
+		if secret.public_key(&SECP) != *adaptor {
+			return Err("extracted secret doesn't match the adaptor point");
+		}
+		Ok(secret)
+	}
+}
+
+/// An ECDSA adaptor signature.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EcdsaAdaptorSignature {
+	/// The nonce of the final signature, R = k*T.
+	pub nonce: PublicKey,
+	/// The nonce committed to by the adaptor signature, R_a = k*G.
+	pub adaptor_nonce: PublicKey,
+	pub s: Scalar,
+	/// The DLEQ proof that R and R_a have the same discrete logarithm,
+	/// as the challenge e and the response z.
+	pub proof: (Scalar, Scalar),
+}
+
+impl EcdsaAdaptorSignature {
+	pub fn from_slice(bytes: &[u8]) -> Result<EcdsaAdaptorSignature, &'static str> {
+		if bytes.len() != 162 {
+			return Err("ECDSA adaptor signature should be 162 bytes");
+		}
+		Ok(EcdsaAdaptorSignature {
+			nonce: PublicKey::from_slice(&bytes[0..33]).map_err(|_| "invalid nonce")?,
+			adaptor_nonce: PublicKey::from_slice(&bytes[33..66])
+				.map_err(|_| "invalid adaptor nonce")?,
+			s: parse_scalar(&bytes[66..98], "invalid s value")?,
+			proof: (
+				parse_scalar(&bytes[98..130], "invalid DLEQ proof")?,
+				parse_scalar(&bytes[130..162], "invalid DLEQ proof")?,
+			),
+		})
+	}
+
+	pub fn serialize(&self) -> [u8; 162] {
+		let mut ret = [0u8; 162];
+		ret[0..33].copy_from_slice(&self.nonce.serialize());
+		ret[33..66].copy_from_slice(&self.adaptor_nonce.serialize());
+		ret[66..98].copy_from_slice(&self.s.to_be_bytes());
+		ret[98..130].copy_from_slice(&self.proof.0.to_be_bytes());
+		ret[130..162].copy_from_slice(&self.proof.1.to_be_bytes());
+		ret
+	}
+
+	/// The r value of the final signature.
+	fn r(&self) -> Scalar {
+		let mut x = [0u8; 32];
+		x.copy_from_slice(&self.nonce.serialize()[1..33]);
+		scalar::reduce(x)
+	}
+
+	/// Create an adaptor signature for the message, encrypted with the adaptor point.
+	pub fn sign(
+		secret_key: &SecretKey,
+		adaptor: &PublicKey,
+		msg: &[u8; 32],
+		aux: &[u8; 32],
+	) -> Result<EcdsaAdaptorSignature, &'static str> {
+		let sk = secret_key.secret_bytes();
+		let pubkey = secret_key.public_key(&SECP);
+		let k = derive_nonce(1, &sk, &[&pubkey.serialize(), &adaptor.serialize(), msg], aux)?;
+		let nonce = scalar::point_mul(adaptor, k).expect("non-zero");
+		let adaptor_nonce = scalar::base_mul(k).expect("non-zero");
+
+		let mut ret = EcdsaAdaptorSignature {
+			nonce,
+			adaptor_nonce,
+			s: Scalar::ZERO,
+			proof: (Scalar::ZERO, Scalar::ZERO),
+		};
+
+		// s' = k^-1 * (m + r*x)
+		let m = scalar::reduce(*msg);
+		let rx = scalar::mul(ret.r(), Scalar::from(*secret_key));
+		ret.s = scalar::mul(scalar::inv(k).expect("non-zero"), scalar::add(m, rx));
+		if scalar::is_zero(ret.s) {
+			return Err("signature has a zero s value, try other auxiliary randomness");
+		}
+
+		let a = derive_nonce(2, &k.to_be_bytes(), &[
+			&adaptor.serialize(), &adaptor_nonce.serialize(), &nonce.serialize(),
+		], aux)?;
+		let e = dleq_challenge(
+			adaptor, &adaptor_nonce, &nonce,
+			&scalar::base_mul(a).expect("non-zero"),
+			&scalar::point_mul(adaptor, a).expect("non-zero"),
+		);
+		ret.proof = (e, scalar::add(a, scalar::mul(e, k)));
+		Ok(ret)
+	}
+
+	/// Verify the DLEQ proof that R_a = k*G and R = k*T.
+	fn verify_proof(&self, adaptor: &PublicKey) -> bool {
+		let (e, z) = self.proof;
+		let neg_e = scalar::neg(e);
+		// A1 = z*G - e*R_a and A2 = z*T - e*R
+		let a1 = sum(&[scalar::base_mul(z), scalar::point_mul(&self.adaptor_nonce, neg_e)]);
+		let a2 = sum(&[scalar::point_mul(adaptor, z), scalar::point_mul(&self.nonce, neg_e)]);
+		match (a1, a2) {
+			(Some(a1), Some(a2)) => {
+				dleq_challenge(adaptor, &self.adaptor_nonce, &self.nonce, &a1, &a2) == e
+			}
+			_ => false,
+		}
+	}
+
+	/// Verify the adaptor signature for the public key, adaptor point and message.
+	pub fn verify(&self, pubkey: &PublicKey, adaptor: &PublicKey, msg: &[u8; 32]) -> bool {
+		if !self.verify_proof(adaptor) {
+			return false;
+		}
+		let s_inv = match scalar::inv(self.s) {
+			Some(s) => s,
+			None => return false,
+		};
+		// R_a = s'^-1 * (m*G + r*X)
+		let u1 = scalar::mul(scalar::reduce(*msg), s_inv);
+		let u2 = scalar::mul(self.r(), s_inv);
+		sum(&[scalar::base_mul(u1), scalar::point_mul(pubkey, u2)]) == Some(self.adaptor_nonce)
+	}
+
+	/// Complete the adaptor signature using the adaptor secret.
+	pub fn complete(&self, secret: &SecretKey) -> ecdsa::Signature {
+		let y_inv = scalar::inv(Scalar::from(*secret)).expect("non-zero");
+		let s = scalar::mul(self.s, y_inv);
+		let mut ret = [0u8; 64];
+		ret[0..32].copy_from_slice(&self.r().to_be_bytes());
+		ret[32..64].copy_from_slice(&s.to_be_bytes());
+		let mut sig = ecdsa::Signature::from_compact(&ret).expect("valid signature");
+		sig.normalize_s();
+		sig
+	}
+
+	/// Extract the adaptor secret from the completed signature.
+	pub fn extract(
+		&self,
+		signature: &ecdsa::Signature,
+		adaptor: &PublicKey,
+	) -> Result<SecretKey, &'static str> {
+		let sig = signature.serialize_compact();
+		if sig[0..32] != self.r().to_be_bytes() {
+			return Err("signature doesn't use the nonce of the adaptor signature");
+		}
+		let s = parse_scalar(&sig[32..64], "invalid signature s value")?;
+		let s_inv = scalar::inv(s).ok_or("signature has a zero s value")?;
+		let y = scalar::to_secret(scalar::mul(self.s, s_inv)).ok_or("extracted a zero secret")?;
+		// The signature might have been normalized to a low s value.
+		let pk = y.public_key(&SECP);
+		if pk == *adaptor {
+			Ok(y)
+		} else if pk == adaptor.negate(&SECP) {
+			Ok(y.negate())
+		} else {
+			Err("extracted secret doesn't match the adaptor point")
+		}
+	}
+}
+
+fn dleq_challenge(
+	adaptor: &PublicKey,
+	adaptor_nonce: &PublicKey,
+	nonce: &PublicKey,
+	a1: &PublicKey,
+	a2: &PublicKey,
+) -> Scalar {
+	let mut engine = DleqChallengeHash::engine();
+	for p in &[adaptor, adaptor_nonce, nonce, a1, a2] {
+		engine.input(&p.serialize());
+	}
+	scalar::reduce(DleqChallengeHash::from_engine(engine).to_byte_array())
+}
+
+/// The prefix of serialized adaptor signatures, marking the hal-only format.
+pub const FORMAT_PREFIX: &[u8; 3] = b"hal";
+const KIND_SCHNORR: u8 = 0x01;
+const KIND_ECDSA: u8 = 0x02;
+
+/// An adaptor signature of either kind.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AdaptorSignature {
+	Schnorr(SchnorrAdaptorSignature),
+	Ecdsa(EcdsaAdaptorSignature),
+}
+
+impl AdaptorSignature {
+	/// Parse an adaptor signature in hal's format, prefixed with "hal" and its kind.
+	pub fn from_slice(bytes: &[u8]) -> Result<AdaptorSignature, &'static str> {
+		if bytes.len() < 4 || &bytes[0..3] != FORMAT_PREFIX {
+			if bytes.len() == 162 {
+				return Err("secp256k1-zkp ECDSA adaptor signatures are not supported, \
+					only hal's own format");
+			}
+			return Err("adaptor signature is not in hal's format");
+		}
+		match bytes[3] {
+			KIND_SCHNORR => Ok(AdaptorSignature::Schnorr(
+				SchnorrAdaptorSignature::from_slice(&bytes[4..])?,
+			)),
+			KIND_ECDSA => Ok(AdaptorSignature::Ecdsa(
+				EcdsaAdaptorSignature::from_slice(&bytes[4..])?,
+			)),
+			_ => Err("unknown kind of adaptor signature"),
+		}
+	}
+
+	/// Serialize the adaptor signature in hal's format.
+	pub fn serialize(&self) -> Vec<u8> {
+		let mut ret = FORMAT_PREFIX.to_vec();
+		match self {
+			AdaptorSignature::Schnorr(s) => {
+				ret.push(KIND_SCHNORR);
+				ret.extend_from_slice(&s.serialize());
+			}
+			AdaptorSignature::Ecdsa(s) => {
+				ret.push(KIND_ECDSA);
+				ret.extend_from_slice(&s.serialize());
+			}
+		}
+		ret
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn key(b: u8) -> SecretKey {
+		SecretKey::from_slice(&[b; 32]).unwrap()
+	}
+
+	#[test]
+	fn test_schnorr_adaptor() {
+		let msg = [0x42; 32];
+		for (sk, t) in &[(key(1), key(2)), (key(3), key(4)), (key(5), key(6)), (key(7), key(8))] {
+			let keypair = Keypair::from_secret_key(&SECP, sk);
+			let pubkey = keypair.x_only_public_key().0;
+			let adaptor = t.public_key(&SECP);
+
+			let pre = SchnorrAdaptorSignature::sign(sk, &adaptor, &msg, &[0; 32]).unwrap();
+			assert_eq!(SchnorrAdaptorSignature::from_slice(&pre.serialize()).unwrap(), pre);
+			let bytes = AdaptorSignature::Schnorr(pre).serialize();
+			assert_eq!(&bytes[0..4], b"hal\x01");
+			assert_eq!(AdaptorSignature::from_slice(&bytes).unwrap(), AdaptorSignature::Schnorr(pre));
+			assert!(pre.verify(&pubkey, &adaptor, &msg));
+			assert!(!pre.verify(&pubkey, &adaptor, &[0x43; 32]));
+			assert!(!pre.verify(&pubkey, &key(9).public_key(&SECP), &msg));
+
+			let sig = pre.complete(t);
+			let m = secp256k1::Message::from_digest(msg);
+			assert!(SECP.verify_schnorr(&sig, &m, &pubkey).is_ok());
+			assert_eq!(pre.extract(&sig, &adaptor).unwrap(), *t);
+		}
+	}
+
+	#[test]
+	fn test_ecdsa_adaptor() {
+		let msg = [0x42; 32];
+		for (sk, t) in &[(key(1), key(2)), (key(3), key(4)), (key(5), key(6)), (key(7), key(8))] {
+			let pubkey = sk.public_key(&SECP);
+			let adaptor = t.public_key(&SECP);
+
+			let pre = EcdsaAdaptorSignature::sign(sk, &adaptor, &msg, &[0; 32]).unwrap();
+			assert_eq!(EcdsaAdaptorSignature::from_slice(&pre.serialize()).unwrap(), pre);
+			let bytes = AdaptorSignature::Ecdsa(pre).serialize();
+			assert_eq!(&bytes[0..4], b"hal\x02");
+			assert_eq!(AdaptorSignature::from_slice(&bytes).unwrap(), AdaptorSignature::Ecdsa(pre));
+			assert!(AdaptorSignature::from_slice(&bytes[4..]).is_err());
+			assert!(pre.verify(&pubkey, &adaptor, &msg));
+			assert!(!pre.verify(&pubkey, &adaptor, &[0x43; 32]));
+			assert!(!pre.verify(&pubkey, &key(9).public_key(&SECP), &msg));
+
+			let sig = pre.complete(t);
+			let m = secp256k1::Message::from_digest(msg);
+			assert!(SECP.verify_ecdsa(&m, &sig, &pubkey).is_ok());
+			assert_eq!(pre.extract(&sig, &adaptor).unwrap(), *t);
+		}
+	}
+}
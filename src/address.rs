@@ -1,11 +1,14 @@
+use std::convert::TryFrom;
 
+use bitcoin::bech32::primitives::decode::CheckedHrpstring;
+use bitcoin::bech32::{Bech32, Bech32m, Fe32};
 use bitcoin::{
 	address, Address, Network, Script, PubkeyHash, ScriptHash, WPubkeyHash, WScriptHash,
 };
 use secp256k1::XOnlyPublicKey;
 use serde::{Deserialize, Serialize};
 
-use crate::SECP;
+use crate::{HexBytes, SECP};
 use crate::tx;
 
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
@@ -13,9 +16,15 @@ pub struct AddressInfo {
 	#[serde(rename = "type")]
 	pub type_: Option<String>,
 	pub script_pub_key: tx::OutputScriptInfo,
+	/// The encoding of the address: base58, bech32 or bech32m.
+	pub encoding: String,
+	/// The networks this address is valid for.
+	pub networks: Vec<Network>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub witness_program_version: Option<usize>,
 	#[serde(skip_serializing_if = "Option::is_none")]
+	pub witness_program: Option<HexBytes>,
+	#[serde(skip_serializing_if = "Option::is_none")]
 	pub pubkey_hash: Option<PubkeyHash>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub script_hash: Option<ScriptHash>,
@@ -23,6 +32,56 @@ pub struct AddressInfo {
 	pub witness_pubkey_hash: Option<WPubkeyHash>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub witness_script_hash: Option<WScriptHash>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub taproot_output_key: Option<XOnlyPublicKey>,
+}
+
+/// The result of validating a single address.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct AddressValidationInfo {
+	pub address: String,
+	pub valid: bool,
+	#[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+	pub type_: Option<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub networks: Vec<Network>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub script_pub_key: Option<HexBytes>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+/// The networks that addresses can be valid for.
+pub const NETWORKS: [Network; 5] = [
+	Network::Bitcoin, Network::Testnet, Network::Testnet4, Network::Signet, Network::Regtest,
+];
+
+/// Try to locate a single-character error in a bech32 or bech32m string.
+///
+/// Returns the position of the erroneous character and the character that
+/// makes the checksum valid, if a single substitution can fix it.
+pub fn locate_bech32_error(s: &str) -> Option<(usize, char)> {
+	let lower = s.to_lowercase();
+	let sep = lower.rfind('1')?;
+	let mut chars = lower.chars().collect::<Vec<_>>();
+	for pos in sep + 1..chars.len() {
+		let orig = chars[pos];
+		for fe in 0..32u8 {
+			let c = Fe32::try_from(fe).expect("< 32").to_char();
+			if c == orig {
+				continue;
+			}
+			chars[pos] = c;
+			let candidate = chars.iter().collect::<String>();
+			if CheckedHrpstring::new::<Bech32>(&candidate).is_ok()
+				|| CheckedHrpstring::new::<Bech32m>(&candidate).is_ok()
+			{
+				return Some((pos, c));
+			}
+		}
+		chars[pos] = orig;
+	}
+	None
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
@@ -89,3 +148,35 @@ impl Addresses {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::str::FromStr;
+
+	#[test]
+	fn test_locate_bech32_error() {
+		// A valid BIP-350 address with a single substituted character.
+		let valid = "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0";
+		let typo = valid.replacen("9h8z", "9h9z", 1);
+		assert_eq!(locate_bech32_error(&typo), Some((valid.find("9h8z").unwrap() + 2, '8')));
+		// Uppercase addresses are located too, the fix is given in lowercase.
+		let typo = "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T5";
+		assert_eq!(locate_bech32_error(typo), Some((41, '4')));
+		assert_eq!(locate_bech32_error(valid), None);
+
+		// BIP-350 invalid addresses that are not a single substitution away from a valid one.
+		for invalid in &[
+			// Bech32 instead of Bech32m and vice versa.
+			"bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+			"tb1z0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqglt7rf",
+			"bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+			// Invalid character in the data part.
+			"bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4",
+		] {
+			assert!(Address::from_str(invalid).is_err(), "{}", invalid);
+			assert_eq!(locate_bech32_error(invalid), None, "{}", invalid);
+		}
+	}
+}
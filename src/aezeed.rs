@@ -0,0 +1,335 @@
+//! LND's aezeed cipher seeds.
+//!
+//! An aezeed is a 24-word mnemonic from the BIP-39 English wordlist that
+//! encodes 33 bytes: an external version, the 16-byte entropy with an internal
+//! version and a wallet birthday, enciphered with AEZ under a scrypt key of the
+//! passphrase, followed by the salt and a CRC-32C checksum.
+
+use bip39lib::Language;
+use bitcoin::Network;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::bip39::SeedInfo;
+use crate::crypto::{aes_round, blake2b, crc32c, scrypt};
+use crate::HexBytes;
+
+/// The passphrase that is used when no passphrase is set.
+pub const DEFAULT_PASSPHRASE: &str = "aezeed";
+
+/// The only external and internal version that exists.
+const VERSION: u8 = 0;
+const ENCIPHERED_SIZE: usize = 33;
+const SALT_OFFSET: usize = 24;
+const CHECKSUM_OFFSET: usize = 29;
+/// The AEZ authenticator size in bytes.
+const TAU: usize = 4;
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: usize = 8;
+const SCRYPT_P: usize = 1;
+
+/// The birthday is the number of days since the genesis block was mined.
+fn genesis_date() -> NaiveDate {
+	NaiveDate::from_ymd_opt(2009, 1, 3).unwrap()
+}
+
+type Block = [u8; 16];
+
+fn xor(a: &Block, b: &Block) -> Block {
+	let mut ret = *a;
+	ret.iter_mut().zip(b.iter()).for_each(|(r, b)| *r ^= b);
+	ret
+}
+
+/// Doubling in GF(2^128).
+fn double(b: &Block) -> Block {
+	let n = u128::from_be_bytes(*b);
+	((n << 1) ^ if n >> 127 == 1 { 0x87 } else { 0 }).to_be_bytes()
+}
+
+/// Multiplication of a block with a small integer in GF(2^128).
+fn mult(i: u32, b: &Block) -> Block {
+	let mut ret = [0u8; 16];
+	let mut x = *b;
+	for bit in 0..32 {
+		if (i >> bit) & 1 == 1 {
+			ret = xor(&ret, &x);
+		}
+		x = double(&x);
+	}
+	ret
+}
+
+/// The AEZ v5 tweakable blockcipher, limited to the parts used by aezeed.
+struct Aez {
+	i: Block,
+	j: Block,
+	l: Block,
+}
+
+impl Aez {
+	fn new(key: &[u8]) -> Aez {
+		let key = if key.len() == 48 { key.to_vec() } else { blake2b(48, key) };
+		let mut ret = Aez { i: [0; 16], j: [0; 16], l: [0; 16] };
+		ret.i.copy_from_slice(&key[0..16]);
+		ret.j.copy_from_slice(&key[16..32]);
+		ret.l.copy_from_slice(&key[32..48]);
+		ret
+	}
+
+	/// E_K^{j,i} for j >= 0.
+	fn e(&self, j: u32, i: u32, x: &Block) -> Block {
+		let mut i_pow = self.i;
+		for _ in 0..i.div_ceil(8) {
+			i_pow = double(&i_pow);
+		}
+		let offset = xor(&xor(&mult(j, &self.j), &i_pow), &mult(i % 8, &self.l));
+		let mut state = xor(x, &offset);
+		for key in [self.j, self.i, self.l, [0; 16]].iter() {
+			aes_round(&mut state, key);
+		}
+		state
+	}
+
+	/// AEZ-hash of the tweak vector.
+	fn hash(&self, tweak: &[&[u8]]) -> Block {
+		let mut delta = [0u8; 16];
+		for (idx, t) in tweak.iter().enumerate() {
+			let j = 3 + idx as u32;
+			let mut blocks = t.chunks(16).peekable();
+			let mut i = 1;
+			while let Some(block) = blocks.next() {
+				if block.len() == 16 {
+					let mut x = [0u8; 16];
+					x.copy_from_slice(block);
+					delta = xor(&delta, &self.e(j, i, &x));
+				}
+				if blocks.peek().is_none() && block.len() < 16 {
+					delta = xor(&delta, &self.e(j, 0, &pad(block)));
+				}
+				i += 1;
+			}
+			if t.is_empty() {
+				delta = xor(&delta, &self.e(j, 0, &pad(&[])));
+			}
+		}
+		delta
+	}
+
+	/// The AEZ-tiny Feistel network for messages of 16 to 31 bytes.
+	fn tiny(&self, delta: &Block, input: &[u8], decipher: bool) -> Vec<u8> {
+		assert!(input.len() >= 16 && input.len() < 32);
+		let m = input.len() * 8;
+		let n = m / 2;
+		let mut l = bits(input, 0, n);
+		let mut r = bits(input, n, n);
+
+		let rounds = 8u32;
+		for k in 0..rounds {
+			let i = if decipher { rounds - 1 - k } else { k };
+			let x = ((r << (128 - n)) | (1 << (127 - n)) | i as u128).to_be_bytes();
+			let f = u128::from_be_bytes(self.e(0, 6, &xor(delta, &x))) >> (128 - n);
+			let new_r = l ^ f;
+			l = r;
+			r = new_r;
+		}
+
+		let mut ret = vec![0u8; input.len()];
+		set_bits(&mut ret, 0, n, r);
+		set_bits(&mut ret, n, n, l);
+		ret
+	}
+
+	fn tweak_delta(&self, ad: &[u8]) -> Block {
+		let tau = ((TAU * 8) as u128).to_be_bytes();
+		self.hash(&[&tau, &[], ad])
+	}
+
+	/// Encrypt with an empty nonce and a single associated data element.
+	fn encrypt(&self, ad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+		let mut x = plaintext.to_vec();
+		x.extend(&[0; TAU]);
+		self.tiny(&self.tweak_delta(ad), &x, false)
+	}
+
+	/// Decrypt with an empty nonce and a single associated data element.
+	fn decrypt(&self, ad: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+		let mut x = self.tiny(&self.tweak_delta(ad), ciphertext, true);
+		let tag = x.split_off(x.len() - TAU);
+		if tag.iter().all(|b| *b == 0) {
+			Some(x)
+		} else {
+			None
+		}
+	}
+}
+
+/// Pad a partial block with a one bit followed by zeros.
+fn pad(data: &[u8]) -> Block {
+	let mut ret = [0u8; 16];
+	ret[..data.len()].copy_from_slice(data);
+	ret[data.len()] = 0x80;
+	ret
+}
+
+/// Read up to 128 bits starting at the given bit offset.
+fn bits(data: &[u8], offset: usize, len: usize) -> u128 {
+	(offset..offset + len).fold(0, |acc, b| (acc << 1) | ((data[b / 8] >> (7 - b % 8)) & 1) as u128)
+}
+
+/// Write up to 128 bits starting at the given bit offset.
+fn set_bits(data: &mut [u8], offset: usize, len: usize, value: u128) {
+	for b in 0..len {
+		if (value >> (len - 1 - b)) & 1 == 1 {
+			data[(offset + b) / 8] |= 0x80 >> ((offset + b) % 8);
+		}
+	}
+}
+
+fn cipher(passphrase: &str, salt: &[u8], scrypt_log_n: u8) -> Aez {
+	let passphrase = if passphrase.is_empty() { DEFAULT_PASSPHRASE } else { passphrase };
+	let key = scrypt(passphrase.as_bytes(), salt, scrypt_log_n, SCRYPT_R, SCRYPT_P, 32);
+	Aez::new(&key)
+}
+
+/// The associated data is the external version and the salt.
+fn associated_data(salt: &[u8]) -> Vec<u8> {
+	let mut ad = vec![VERSION];
+	ad.extend(salt);
+	ad
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct AezeedInfo {
+	pub mnemonic: String,
+	pub version: u8,
+	pub internal_version: u8,
+	/// The number of days since the genesis block.
+	pub birthday: u16,
+	pub birthday_date: NaiveDate,
+	pub salt: HexBytes,
+	pub passphrase: String,
+	/// The seed is the entropy of the aezeed.
+	pub seed: SeedInfo,
+}
+
+/// Encode entropy into an aezeed mnemonic.
+pub fn encode(entropy: &[u8; 16], birthday: u16, salt: &[u8; 5], passphrase: &str) -> String {
+	encode_with_cost(entropy, birthday, salt, passphrase, SCRYPT_LOG_N)
+}
+
+/// Encode with a custom scrypt cost, LND's test vectors use a lower one.
+fn encode_with_cost(
+	entropy: &[u8; 16],
+	birthday: u16,
+	salt: &[u8; 5],
+	passphrase: &str,
+	scrypt_log_n: u8,
+) -> String {
+	let mut plaintext = vec![VERSION];
+	plaintext.extend(&birthday.to_be_bytes());
+	plaintext.extend(entropy);
+	let ciphertext = cipher(passphrase, salt, scrypt_log_n)
+		.encrypt(&associated_data(salt), &plaintext);
+
+	let mut data = vec![VERSION];
+	data.extend(ciphertext);
+	data.extend(salt);
+	let checksum = crc32c(&data);
+	data.extend(&checksum.to_be_bytes());
+
+	let words = Language::English.word_list();
+	(0..24).map(|i| words[bits(&data, i * 11, 11) as usize]).collect::<Vec<_>>().join(" ")
+}
+
+/// Decode and decipher an aezeed mnemonic.
+pub fn decode(mnemonic: &str, passphrase: &str, network: Network) -> Result<AezeedInfo, &'static str> {
+	decode_with_cost(mnemonic, passphrase, network, SCRYPT_LOG_N)
+}
+
+fn decode_with_cost(
+	mnemonic: &str,
+	passphrase: &str,
+	network: Network,
+	scrypt_log_n: u8,
+) -> Result<AezeedInfo, &'static str> {
+	let words = mnemonic.split_whitespace().collect::<Vec<_>>();
+	if words.len() != 24 {
+		return Err("an aezeed has 24 words");
+	}
+	let mut data = [0u8; ENCIPHERED_SIZE];
+	for (i, word) in words.iter().enumerate() {
+		let idx = Language::English.find_word(&word.to_lowercase()).ok_or("unknown word")?;
+		set_bits(&mut data, i * 11, 11, idx as u128);
+	}
+
+	let mut checksum = [0u8; 4];
+	checksum.copy_from_slice(&data[CHECKSUM_OFFSET..]);
+	if crc32c(&data[0..CHECKSUM_OFFSET]) != u32::from_be_bytes(checksum) {
+		return Err("invalid aezeed checksum");
+	}
+	if data[0] != VERSION {
+		return Err("unknown aezeed version");
+	}
+	let salt = &data[SALT_OFFSET..CHECKSUM_OFFSET];
+	let plaintext = cipher(passphrase, salt, scrypt_log_n)
+		.decrypt(&associated_data(salt), &data[1..SALT_OFFSET])
+		.ok_or("invalid passphrase")?;
+	if plaintext[0] != VERSION {
+		return Err("unknown aezeed internal version");
+	}
+	let birthday = u16::from_be_bytes([plaintext[1], plaintext[2]]);
+
+	Ok(AezeedInfo {
+		mnemonic: words.join(" "),
+		version: data[0],
+		internal_version: plaintext[0],
+		birthday,
+		birthday_date: genesis_date() + chrono::Duration::days(birthday as i64),
+		salt: salt.into(),
+		passphrase: passphrase.to_owned(),
+		seed: SeedInfo::from_seed(&plaintext[3..], network),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// LND's version0TestVectors, which are created with an scrypt N of 16.
+	#[test]
+	fn test_aezeed() {
+		let entropy = [
+			0x81, 0xb6, 0x37, 0xd8, 0x63, 0x59, 0xe6, 0x96,
+			0x0d, 0xe7, 0x95, 0xe4, 0x1e, 0x0b, 0x4c, 0xfd,
+		];
+		let vectors = [
+			(0, "", "ability liquid travel stem barely drastic pact cupboard apple thrive \
+				morning oak feature tissue couch old math inform success suggest drink motion \
+				know royal"),
+			(3365, "!very_safe_55345_password*", "able tree stool crush transfer cloud cross \
+				three profit outside hen citizen plate ride require leg siren drum success \
+				suggest drink require fiscal upgrade"),
+		];
+		for (birthday, passphrase, mnemonic) in vectors.iter() {
+			assert_eq!(&encode_with_cost(&entropy, *birthday, b"salt1", passphrase, 4), mnemonic);
+			let info = decode_with_cost(mnemonic, passphrase, Network::Bitcoin, 4).unwrap();
+			assert_eq!(info.birthday, *birthday);
+			assert_eq!(info.salt.0, b"salt1".to_vec());
+			assert_eq!(info.seed.seed.0, entropy.to_vec());
+			let wrong = decode_with_cost(mnemonic, "wrong", Network::Bitcoin, 4);
+			assert_eq!(wrong, Err("invalid passphrase"));
+		}
+		let info = decode_with_cost(vectors[1].2, vectors[1].1, Network::Bitcoin, 4).unwrap();
+		assert_eq!(info.birthday_date, NaiveDate::from_ymd_opt(2018, 3, 22).unwrap());
+		let info = decode_with_cost(vectors[0].2, "aezeed", Network::Bitcoin, 4).unwrap();
+		assert_eq!(info.seed.seed.0, entropy.to_vec());
+
+		let mut words = vectors[0].2.split(' ').collect::<Vec<_>>();
+		words.swap(0, 1);
+		assert_eq!(decode(&words.join(" "), "", Network::Bitcoin), Err("invalid aezeed checksum"));
+
+		let mnemonic = encode(&entropy, 3365, b"salt1", "");
+		assert_eq!(decode(&mnemonic, "", Network::Bitcoin).unwrap().seed.seed.0, entropy.to_vec());
+	}
+}
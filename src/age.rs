@@ -0,0 +1,181 @@
+//! Passphrase encryption in the age format.
+//!
+//! Only the scrypt recipient is supported and files are always ASCII-armored, which is
+//! what `age --passphrase --armor` produces. See https://age-encryption.org/v1.
+
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use bitcoin::secp256k1::rand::{self, RngCore};
+
+use crate::crypto::{
+	base64_decode, base64_encode, chacha20poly1305_open, chacha20poly1305_seal, hkdf_sha256,
+	scrypt,
+};
+
+/// The scrypt work factor that age uses by default, as the base-2 logarithm of N.
+pub const DEFAULT_WORK_FACTOR: u8 = 18;
+/// The highest work factor we accept when decrypting.
+const MAX_WORK_FACTOR: u8 = 22;
+
+const INTRO: &str = "age-encryption.org/v1";
+const SCRYPT_LABEL: &str = "age-encryption.org/v1/scrypt";
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+const CHUNK_SIZE: usize = 64 * 1024;
+const LINE_LENGTH: usize = 64;
+
+fn scrypt_key(passphrase: &str, salt: &[u8], log_n: u8) -> [u8; 32] {
+	let mut scrypt_salt = SCRYPT_LABEL.as_bytes().to_vec();
+	scrypt_salt.extend(salt);
+	let mut ret = [0u8; 32];
+	ret.copy_from_slice(&scrypt(passphrase.as_bytes(), &scrypt_salt, log_n, 8, 1, 32));
+	ret
+}
+
+fn hkdf_key(salt: &[u8], file_key: &[u8], label: &[u8]) -> [u8; 32] {
+	let mut ret = [0u8; 32];
+	ret.copy_from_slice(&hkdf_sha256(salt, file_key, label, 32));
+	ret
+}
+
+/// The header MAC, over the header up to and including the "---".
+fn header_mac(file_key: &[u8], header: &str) -> Vec<u8> {
+	let mut engine = hmac::HmacEngine::<sha256::Hash>::new(&hkdf_key(&[], file_key, b"header"));
+	engine.input(header.as_bytes());
+	hmac::Hmac::<sha256::Hash>::from_engine(engine)[..].to_vec()
+}
+
+/// The nonce of a payload chunk is the chunk counter and a flag for the last chunk.
+fn chunk_nonce(counter: usize, last: bool) -> [u8; 12] {
+	let mut nonce = [0u8; 12];
+	nonce[3..11].copy_from_slice(&(counter as u64).to_be_bytes());
+	nonce[11] = last as u8;
+	nonce
+}
+
+/// Encrypt the plaintext with the passphrase, the scrypt work factor is the base-2 logarithm of N.
+pub fn encrypt(plaintext: &[u8], passphrase: &str, work_factor: u8) -> String {
+	let mut file_key = [0u8; 16];
+	let mut salt = [0u8; 16];
+	let mut nonce = [0u8; 16];
+	rand::thread_rng().fill_bytes(&mut file_key);
+	rand::thread_rng().fill_bytes(&mut salt);
+	rand::thread_rng().fill_bytes(&mut nonce);
+
+	let wrap_key = scrypt_key(passphrase, &salt, work_factor);
+	let body = chacha20poly1305_seal(&wrap_key, &[0; 12], &file_key);
+	let mut header = format!(
+		"{}\n-> scrypt {} {}\n{}\n---",
+		INTRO, base64_encode(&salt, false), work_factor, base64_encode(&body, false),
+	);
+	let mac = header_mac(&file_key, &header);
+	header.push_str(&format!(" {}\n", base64_encode(&mac, false)));
+
+	let mut data = header.into_bytes();
+	data.extend(&nonce);
+	let payload_key = hkdf_key(&nonce, &file_key, b"payload");
+	let nb_chunks = plaintext.len().div_ceil(CHUNK_SIZE).max(1);
+	for i in 0..nb_chunks {
+		let chunk = &plaintext[i * CHUNK_SIZE..plaintext.len().min((i + 1) * CHUNK_SIZE)];
+		data.extend(chacha20poly1305_seal(&payload_key, &chunk_nonce(i, i == nb_chunks - 1), chunk));
+	}
+
+	let encoded = base64_encode(&data, true);
+	let mut ret = format!("{}\n", ARMOR_BEGIN);
+	for line in encoded.as_bytes().chunks(LINE_LENGTH) {
+		ret.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+		ret.push('\n');
+	}
+	ret.push_str(ARMOR_END);
+	ret.push('\n');
+	ret
+}
+
+/// Whether the text looks like an ASCII-armored age file.
+pub fn is_armored(s: &str) -> bool {
+	s.trim_start().starts_with(ARMOR_BEGIN)
+}
+
+/// Decrypt an ASCII-armored age file that is encrypted with a passphrase.
+pub fn decrypt(armored: &str, passphrase: &str) -> Result<Vec<u8>, &'static str> {
+	let armored = armored.trim();
+	if !armored.starts_with(ARMOR_BEGIN) || !armored.ends_with(ARMOR_END) {
+		return Err("not an ASCII-armored age file");
+	}
+	let encoded = armored[ARMOR_BEGIN.len()..armored.len() - ARMOR_END.len()]
+		.split_whitespace().collect::<String>();
+	let data = base64_decode(&encoded).ok_or("invalid base64 in age file")?;
+
+	// The header consists of four lines for a single scrypt stanza with a short body.
+	let mut lines = Vec::new();
+	let mut pos = 0;
+	let mut mac_line_start = 0;
+	while lines.len() < 4 {
+		let end = data[pos..].iter().position(|b| *b == b'\n').ok_or("invalid age header")?;
+		lines.push(std::str::from_utf8(&data[pos..pos + end]).map_err(|_| "invalid age header")?);
+		mac_line_start = pos;
+		pos += end + 1;
+	}
+	if lines[0] != INTRO {
+		return Err("unsupported age version");
+	}
+	let stanza = lines[1].split(' ').collect::<Vec<_>>();
+	if stanza.len() != 4 || stanza[0] != "->" || stanza[1] != "scrypt" {
+		return Err("the age file is not encrypted with a passphrase");
+	}
+	let salt = base64_decode(stanza[2]).filter(|s| s.len() == 16).ok_or("invalid scrypt salt")?;
+	let work_factor = stanza[3].parse::<u8>().map_err(|_| "invalid scrypt work factor")?;
+	if work_factor > MAX_WORK_FACTOR {
+		return Err("the scrypt work factor is too high");
+	}
+	let body = base64_decode(lines[2]).filter(|b| b.len() == 32).ok_or("invalid scrypt stanza")?;
+	let mac = lines[3].strip_prefix("--- ").and_then(base64_decode).ok_or("invalid age header")?;
+
+	let wrap_key = scrypt_key(passphrase, &salt, work_factor);
+	let file_key = chacha20poly1305_open(&wrap_key, &[0; 12], &body).ok_or("invalid passphrase")?;
+	let header = std::str::from_utf8(&data[..mac_line_start + 3]).expect("checked above");
+	if header_mac(&file_key, header) != mac {
+		return Err("invalid age header MAC");
+	}
+
+	if data.len() < pos + 16 {
+		return Err("missing age payload");
+	}
+	let payload_key = hkdf_key(&data[pos..pos + 16], &file_key, b"payload");
+	let chunks = data[pos + 16..].chunks(CHUNK_SIZE + 16).collect::<Vec<_>>();
+	if chunks.is_empty() {
+		return Err("missing age payload");
+	}
+	let mut ret = Vec::new();
+	for (i, chunk) in chunks.iter().enumerate() {
+		let nonce = chunk_nonce(i, i == chunks.len() - 1);
+		ret.extend(chacha20poly1305_open(&payload_key, &nonce, chunk).ok_or("invalid age payload")?);
+	}
+	Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_age() {
+		let armored = encrypt(b"hal", "passphrase", 10);
+		assert!(is_armored(&armored));
+		assert_eq!(decrypt(&armored, "passphrase").unwrap(), b"hal".to_vec());
+		assert_eq!(decrypt(&armored, "wrong"), Err("invalid passphrase"));
+
+		// Created with `rage --passphrase --armor` at work factor 10.
+		let armored = "\
+			-----BEGIN AGE ENCRYPTED FILE-----\n\
+			YWdlLWVuY3J5cHRpb24ub3JnL3YxCi0+IHNjcnlwdCA3TW45Y2ZoSnlwODUwczRy\n\
+			eTgyRlNBIDEwCjRLRU1zTWxUaVhPcm1XZFBIY21HVmU0UUF6b2gwN0RudGllRHY5\n\
+			bWJWTkUKLS0tIE9nbk15elZIc0lSd2lKQkhTNzdaY1lOcXppcnVUZXFXTWg5L2Q5\n\
+			YUNndTQKzMN2q9eGl1HZH6vcG0F7F/ePhwVLo5gPvaoch/SD1mhruks=\n\
+			-----END AGE ENCRYPTED FILE-----\n";
+		assert_eq!(decrypt(armored, "correct horse battery staple").unwrap(), b"hal".to_vec());
+
+		let plaintext = vec![42u8; CHUNK_SIZE + 1];
+		let armored = encrypt(&plaintext, "passphrase", 10);
+		assert_eq!(decrypt(&armored, "passphrase").unwrap(), plaintext);
+	}
+}
@@ -1,12 +1,13 @@
 
 use std::borrow::Borrow;
+use std::io::Write;
 use std::str::FromStr;
 
 use bitcoin::consensus::encode;
 use bitcoin::{Network, NetworkKind};
 use secp256k1::{self, XOnlyPublicKey};
 
-use crate::exit;
+use crate::{exit, util};
 
 /// Construct a new boolean command flag.
 pub fn flag<'a>(name: &'a str, help: &'a str) -> clap::Arg<'a, 'a> {
@@ -34,6 +35,9 @@ pub fn opts_networks() -> Vec<clap::Arg<'static, 'static>> {
 			.short("t")
 			.required(false)
 			.global(true),
+		flag("testnet4", "run in testnet4 mode")
+			.required(false)
+			.global(true),
 		flag("signet", "run in signet mode")
 			.required(false)
 			.global(true),
@@ -51,6 +55,43 @@ pub fn opt_yaml() -> clap::Arg<'static, 'static> {
 		.global(true)
 }
 
+/// Options for entropy from dice rolls, coin flips or playing cards.
+pub fn opts_physical_entropy() -> Vec<clap::Arg<'static, 'static>> {
+	vec![
+		opt("dice", "use the SHA-256 hash of dice rolls as entropy, like 16253..., \
+			for six-sided dice this matches the dice roll entropy of Coldcard"),
+		opt("dice-sides", "the number of sides of the dice").default_value("6"),
+		opt("coins", "use the SHA-256 hash of coin flips as entropy, as h/t or 1/0 \
+			(specific to hal)"),
+		opt("cards", "use the SHA-256 hash of playing cards as entropy, like \"AS 10H QD\" \
+			(specific to hal), drawn from a single shuffled deck so all cards must be different"),
+		flag("cards-replace", "the cards were drawn with replacement, reshuffling the deck \
+			after each draw, so cards can repeat").requires("cards"),
+	]
+}
+
+/// Options to encrypt secret output with a passphrase.
+pub fn opts_encrypt() -> Vec<clap::Arg<'static, 'static>> {
+	vec![
+		flag("encrypt", "encrypt the output with a passphrase in the ASCII-armored age format, \
+			the passphrase is prompted for or read from $HAL_PASSPHRASE"),
+		opt("encrypt-passphrase-file", "read the passphrase to encrypt with from a file")
+			.requires("encrypt"),
+		opt("insecure-encrypt-passphrase", "the passphrase to encrypt with (INSECURE: \
+			visible to other users and stored in the shell history)")
+			.requires("encrypt"),
+	]
+}
+
+/// Options to provide a passphrase other than the environment or a prompt.
+pub fn opts_passphrase() -> Vec<clap::Arg<'static, 'static>> {
+	vec![
+		opt("passphrase-file", "read the passphrase from a file"),
+		opt("insecure-passphrase", "the passphrase (INSECURE: visible to other users and \
+			stored in the shell history)"),
+	]
+}
+
 /// A flexible pubkey return type that accepts both xonly and regular pubkeys.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FlexiblePubkey {
@@ -68,6 +109,8 @@ pub trait ArgMatchesExt<'a>: Borrow<clap::ArgMatches<'a>> {
 			Some(Network::Bitcoin)
 		} else if self.borrow().is_present("testnet") {
 			Some(Network::Testnet)
+		} else if self.borrow().is_present("testnet4") {
+			Some(Network::Testnet4)
 		} else if self.borrow().is_present("signet") {
 			Some(Network::Signet)
 		} else if self.borrow().is_present("regtest") {
@@ -170,6 +213,28 @@ pub trait ArgMatchesExt<'a>: Borrow<clap::ArgMatches<'a>> {
 			serde_json::to_writer_pretty(::std::io::stdout(), &out).unwrap();
 		}
 	}
+
+	/// Write secret output, encrypted with a passphrase if --encrypt was given.
+	fn write_secret(&self, data: &[u8]) {
+		if self.borrow().is_present("encrypt") {
+			let passphrase = util::passphrase(
+				self.borrow(), "encrypt-passphrase-file", "insecure-encrypt-passphrase", true,
+			);
+			print!("{}", hal::age::encrypt(data, &passphrase, hal::age::DEFAULT_WORK_FACTOR));
+		} else {
+			::std::io::stdout().write_all(data).unwrap();
+		}
+	}
+
+	/// Print secret output, encrypted with a passphrase if --encrypt was given.
+	fn print_secret_output<T: serde::Serialize>(&self, out: &T) {
+		let data = if self.out_yaml() {
+			serde_yaml::to_vec(&out).unwrap()
+		} else {
+			serde_json::to_vec_pretty(&out).unwrap()
+		};
+		self.write_secret(&data)
+	}
 }
 
 impl<'a> ArgMatchesExt<'a> for clap::ArgMatches<'a> {}
@@ -1,13 +1,21 @@
 
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::process;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::{thread, time};
 
 use bitcoin::address::AddressData;
+use bitcoin::bech32;
 use bitcoin::hashes::Hash;
 use bitcoin::hashes::hex::FromHex;
 use bitcoin::{Address, ScriptBuf, WPubkeyHash, WScriptHash};
+use secp256k1::XOnlyPublicKey;
 use clap;
 
-use hal;
+use hal::{self, GetInfo};
 
 use crate::prelude::*;
 
@@ -31,12 +39,16 @@ pub fn subcommand<'a>() -> clap::App<'a, 'a> {
 	cmd::subcommand_group("address", "work with addresses")
 		.subcommand(cmd_create())
 		.subcommand(cmd_inspect())
+		.subcommand(cmd_validate())
+		.subcommand(cmd_vanity())
 }
 
 pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
 	match args.subcommand() {
 		("create", Some(ref m)) => exec_create(&m),
 		("inspect", Some(ref m)) => exec_inspect(&m),
+		("validate", Some(m)) => exec_validate(m),
+		("vanity", Some(m)) => exec_vanity(m),
 		(_, _) => unreachable!("clap prints help"),
 	};
 }
@@ -134,9 +146,42 @@ fn cmd_inspect<'a>() -> clap::App<'a, 'a> {
 
 fn exec_inspect<'a>(args: &clap::ArgMatches<'a>) {
 	let address_str = args.value_of("address").need("no address provided");
-	let address = Address::from_str(address_str)
-		.need("invalid address format")
-		.assume_checked();
+	let info = address_info(address_str).unwrap_or_else(|e| exit!("{}", e));
+	args.print_output(&info)
+}
+
+/// Describe the error when parsing an address as precisely as we can.
+fn address_error(address_str: &str, err: bitcoin::address::ParseError) -> String {
+	if let Some((pos, c)) = hal::address::locate_bech32_error(address_str) {
+		return format!(
+			"invalid address checksum: likely error at position {} ('{}'), \
+			the checksum would be valid with '{}'",
+			pos, address_str.chars().nth(pos).unwrap_or('?'), c,
+		);
+	}
+	// For segwit addresses, the bech32 error is more useful than the base58 one.
+	let lower = address_str.to_lowercase();
+	if ["bc1", "tb1", "bcrt1"].iter().any(|p| lower.starts_with(p)) {
+		if let Err(e) = bech32::segwit::decode(address_str) {
+			let mut msg = e.to_string();
+			let mut source = e.source();
+			while let Some(s) = source {
+				msg.push_str(&format!(": {}", s));
+				source = s.source();
+			}
+			return format!("invalid segwit address: {}", msg);
+		}
+	}
+	format!("invalid address format: {}", err)
+}
+
+fn address_info(address_str: &str) -> Result<hal::address::AddressInfo, String> {
+	let unchecked = Address::from_str(address_str).map_err(|e| address_error(address_str, e))?;
+	let networks = hal::address::NETWORKS.iter()
+		.filter(|n| unchecked.is_valid_for_network(**n))
+		.copied()
+		.collect();
+	let address = unchecked.assume_checked();
 	let script_pk = address.script_pubkey();
 
 	let mut info = hal::address::AddressInfo {
@@ -147,11 +192,15 @@ fn exec_inspect<'a>(args: &clap::ArgMatches<'a>) {
 			type_: None,
 		},
 		type_: None,
+		encoding: "base58".to_owned(),
+		networks,
 		pubkey_hash: None,
 		script_hash: None,
 		witness_pubkey_hash: None,
 		witness_script_hash: None,
 		witness_program_version: None,
+		witness_program: None,
+		taproot_output_key: None,
 	};
 
 	match address.to_address_data() {
@@ -167,6 +216,8 @@ fn exec_inspect<'a>(args: &clap::ArgMatches<'a>) {
 			let version = witness_program.version().to_num() as usize;
 			info.witness_program_version = Some(version);
 			let program = witness_program.program();
+			info.witness_program = Some(program.as_bytes().into());
+			info.encoding = if version == 0 { "bech32" } else { "bech32m" }.to_owned();
 
 			if version == 0 {
 				if program.len() == 20 {
@@ -180,12 +231,343 @@ fn exec_inspect<'a>(args: &clap::ArgMatches<'a>) {
 				} else {
 					info.type_ = Some("invalid-witness-program".to_owned());
 				}
+			} else if version == 1 && program.len() == 32 {
+				info.type_ = Some("p2tr".to_owned());
+				info.taproot_output_key = Some(XOnlyPublicKey::from_slice(program.as_bytes())
+					.map_err(|_| "invalid taproot output key")?);
+			} else if witness_program.is_p2a() {
+				info.type_ = Some("p2a".to_owned());
 			} else {
 				info.type_ = Some("unknown-witness-program-version".to_owned());
 			}
 		},
-		_ => exit!("unknown address type"),
+		_ => return Err("unknown address type".to_owned()),
 	}
 
-	args.print_output(&info)
+	Ok(info)
+}
+
+fn cmd_validate<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("validate", "validate a batch of addresses read from stdin\n\n\
+		The input can be either one address per line or a JSON array of addresses. \
+		For each address, a line of JSON is printed. \
+		Exits with a non-zero status if any address is invalid. \
+		When a network is selected, addresses must be valid for that network.")
+		.unset_setting(clap::AppSettings::ArgRequiredElseHelp)
+}
+
+/// Validate the address, for the given network if any.
+fn validate_address(
+	address: String,
+	network: Option<bitcoin::Network>,
+) -> hal::address::AddressValidationInfo {
+	let mut res = hal::address::AddressValidationInfo {
+		valid: false,
+		type_: None,
+		networks: Vec::new(),
+		script_pub_key: None,
+		error: None,
+		address,
+	};
+	match address_info(&res.address) {
+		Ok(info) => {
+			res.valid = network.map(|n| info.networks.contains(&n)).unwrap_or(true);
+			if !res.valid {
+				res.error = Some(format!("address is not valid for {}", network.unwrap()));
+			}
+			res.type_ = info.type_;
+			res.networks = info.networks;
+			res.script_pub_key = info.script_pub_key.hex;
+		}
+		Err(e) => res.error = Some(e),
+	}
+	res
+}
+
+fn exec_validate<'a>(args: &clap::ArgMatches<'a>) {
+	let network = args.explicit_network();
+
+	let mut input = String::new();
+	io::stdin().read_to_string(&mut input).need("error reading stdin");
+	let addresses = if input.trim_start().starts_with('[') {
+		serde_json::from_str::<Vec<String>>(&input).need("invalid JSON array of addresses")
+	} else {
+		input.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).map(|l| l.to_owned()).collect()
+	};
+
+	let stdout = io::stdout();
+	let mut stdout = stdout.lock();
+	let mut all_valid = true;
+	for address in addresses {
+		let res = validate_address(address, network);
+		all_valid &= res.valid;
+		serde_json::to_writer(&mut stdout, &res).need("error writing output");
+		writeln!(stdout).need("error writing output");
+	}
+
+	if !all_valid {
+		process::exit(1);
+	}
+}
+
+/// The address types supported by the vanity search.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VanityType {
+	P2pkh,
+	P2wpkh,
+	P2tr,
+}
+
+impl VanityType {
+	fn address(self, pubkey: &bitcoin::PublicKey, network: bitcoin::Network) -> Address {
+		match self {
+			VanityType::P2pkh => Address::p2pkh(pubkey, network),
+			VanityType::P2wpkh => {
+				Address::p2wpkh(&bitcoin::CompressedPublicKey(pubkey.inner), network)
+			}
+			VanityType::P2tr => Address::p2tr(&SECP, pubkey.inner.into(), None, network),
+		}
+	}
+}
+
+/// Matches addresses against the vanity pattern.
+enum VanityMatcher {
+	Prefix {
+		prefix: String,
+		case_insensitive: bool,
+	},
+	Regex(regex::Regex),
+}
+
+impl VanityMatcher {
+	fn is_match(&self, address: &str) -> bool {
+		match self {
+			VanityMatcher::Prefix { prefix, case_insensitive: false } => address.starts_with(prefix),
+			VanityMatcher::Prefix { prefix, case_insensitive: true } => {
+				address.len() >= prefix.len() && address[..prefix.len()].eq_ignore_ascii_case(prefix)
+			}
+			VanityMatcher::Regex(re) => re.is_match(address),
+		}
+	}
+}
+
+const BASE58_CHARS: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BECH32_CHARS: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Check that the prefix can match the address type and estimate the number
+/// of attempts expected to find a match.
+fn vanity_difficulty(
+	type_: VanityType,
+	network: bitcoin::Network,
+	prefix: &str,
+	case_insensitive: bool,
+) -> Result<f64, String> {
+	// Bech32 addresses are matched case-insensitively.
+	let prefix = if type_ == VanityType::P2pkh {
+		prefix.to_owned()
+	} else {
+		prefix.to_ascii_lowercase()
+	};
+	let example = type_.address(&bitcoin::PublicKey::new(*NUMS_H), network).to_string();
+	let (fixed, charset) = match type_ {
+		// The first character is determined by the version byte, but testnet
+		// p2pkh addresses can start with either m or n.
+		VanityType::P2pkh => (&example[..1], BASE58_CHARS),
+		_ => (&example[..example.rfind('1').expect("bech32") + 2], BECH32_CHARS),
+	};
+
+	let mut difficulty = 1.0;
+	for (i, c) in prefix.chars().enumerate() {
+		if i < fixed.len() {
+			let testnet_p2pkh = type_ == VanityType::P2pkh && fixed != "1" && (c == 'm' || c == 'n');
+			if testnet_p2pkh {
+				difficulty *= 2.0;
+			} else if !fixed[i..].starts_with(c) {
+				return Err(format!("these addresses always start with '{}'", fixed));
+			}
+			continue;
+		}
+		if type_ == VanityType::P2pkh {
+			let other_case = if c.is_ascii_uppercase() {
+				c.to_ascii_lowercase()
+			} else {
+				c.to_ascii_uppercase()
+			};
+			let has = |c: char| BASE58_CHARS.contains(c);
+			match (has(c), case_insensitive && c != other_case && has(other_case)) {
+				(_, true) => difficulty *= 29.0,
+				(true, false) => difficulty *= 58.0,
+				(false, false) => return Err(format!("invalid base58 character: '{}'", c)),
+			}
+		} else if charset.contains(c) {
+			difficulty *= 32.0;
+		} else {
+			return Err(format!("invalid bech32 character: '{}'", c));
+		}
+	}
+	Ok(difficulty)
+}
+
+fn cmd_vanity<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("vanity", "search for a key with an address matching a prefix or regex\n\n\
+		The search is random, so it can take anywhere from an instant to forever.")
+		.arg(args::arg("pattern", "the prefix the address should start with, \
+			or a regular expression with --regex").required(true))
+		.arg(args::opt("type", "the address type: p2pkh, p2wpkh or p2tr").default_value("p2wpkh"))
+		.arg(args::flag("regex", "interpret the pattern as a regular expression"))
+		.arg(args::flag("case-insensitive", "match case-insensitively; \
+			bech32 addresses are always matched case-insensitively").short("i"))
+		.arg(args::opt("threads", "the number of threads to use, defaults to the number of CPUs"))
+}
+
+fn exec_vanity<'a>(args: &clap::ArgMatches<'a>) {
+	let network = args.network();
+	let pattern = args.value_of("pattern").need("no pattern provided");
+	let type_ = match args.value_of("type").need("no address type provided") {
+		"p2pkh" => VanityType::P2pkh,
+		"p2wpkh" => VanityType::P2wpkh,
+		"p2tr" => VanityType::P2tr,
+		t => exit!("unsupported address type: {}", t),
+	};
+	// Bech32 addresses are always lowercase.
+	let case_insensitive = args.is_present("case-insensitive") || type_ != VanityType::P2pkh;
+
+	let matcher = if args.is_present("regex") {
+		let re = regex::RegexBuilder::new(pattern)
+			.case_insensitive(case_insensitive)
+			.build()
+			.need("invalid regular expression");
+		VanityMatcher::Regex(re)
+	} else {
+		let difficulty = vanity_difficulty(type_, network, pattern, case_insensitive)
+			.unwrap_or_else(|e| exit!("prefix can never match: {}", e));
+		eprintln!("Expected number of attempts: {:.0}", difficulty);
+		VanityMatcher::Prefix {
+			prefix: pattern.to_owned(),
+			case_insensitive,
+		}
+	};
+
+	let threads = match args.value_of("threads") {
+		Some(t) => t.parse::<usize>().need("invalid number of threads"),
+		None => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+	};
+	if threads == 0 {
+		exit!("need at least one thread");
+	}
+
+	let matcher = Arc::new(matcher);
+	let found = Arc::new(AtomicBool::new(false));
+	let attempts = Arc::new(AtomicU64::new(0));
+	let (tx, rx) = mpsc::channel();
+	let start = time::Instant::now();
+	for _ in 0..threads {
+		let (matcher, found, attempts, tx) = (matcher.clone(), found.clone(), attempts.clone(), tx.clone());
+		thread::spawn(move || {
+			let mut rng = secp256k1::rand::thread_rng();
+			while !found.load(Ordering::Relaxed) {
+				for _ in 0..256 {
+					let (secret_key, public_key) = SECP.generate_keypair(&mut rng);
+					let pubkey = bitcoin::PublicKey::new(public_key);
+					if matcher.is_match(&type_.address(&pubkey, network).to_string()) {
+						found.store(true, Ordering::Relaxed);
+						let _ = tx.send(secret_key);
+						return;
+					}
+				}
+				attempts.fetch_add(256, Ordering::Relaxed);
+			}
+		});
+	}
+
+	let secret_key = rx.recv().need("no key found");
+	eprintln!(
+		"Found after about {} attempts in {:.1}s",
+		attempts.load(Ordering::Relaxed), start.elapsed().as_secs_f64(),
+	);
+	let privkey = bitcoin::PrivateKey {
+		compressed: true,
+		network: network.into(),
+		inner: secret_key,
+	};
+	args.print_output(&privkey.get_info(network))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bitcoin::Network;
+
+	#[test]
+	fn test_address_info() {
+		let info = address_info("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0").unwrap();
+		assert_eq!(info.type_.as_deref(), Some("p2tr"));
+		assert_eq!(info.encoding, "bech32m");
+		assert_eq!(info.networks, vec![Network::Bitcoin]);
+		assert_eq!(info.witness_program_version, Some(1));
+		assert_eq!(info.taproot_output_key.unwrap().to_string(),
+			"79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+
+		// Future segwit versions from the BIP-350 test vectors.
+		let info = address_info("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs").unwrap();
+		assert_eq!(info.type_.as_deref(), Some("unknown-witness-program-version"));
+		assert_eq!(info.witness_program_version, Some(2));
+		assert_eq!(info.witness_program.unwrap().hex(), "751e76e8199196d454941c45d1b3a323");
+
+		let info = address_info("tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c").unwrap();
+		assert_eq!(info.networks, vec![Network::Testnet, Network::Testnet4, Network::Signet]);
+
+		let info = address_info("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4").unwrap();
+		assert_eq!((info.type_.as_deref(), &info.encoding[..]), (Some("p2wpkh"), "bech32"));
+
+		let err = address_info("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd").unwrap_err();
+		assert!(err.starts_with("invalid segwit address"), "{}", err);
+		let err = address_info("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T5").unwrap_err();
+		assert!(err.contains("position 41"), "{}", err);
+	}
+
+	#[test]
+	fn test_validate_address() {
+		let address = "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c";
+		let res = validate_address(address.to_owned(), None);
+		assert!(res.valid);
+		assert_eq!(res.type_.as_deref(), Some("p2tr"));
+		assert_eq!(res.script_pub_key.unwrap().hex(),
+			"5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433");
+		assert!(validate_address(address.to_owned(), Some(Network::Signet)).valid);
+
+		let res = validate_address(address.to_owned(), Some(Network::Bitcoin));
+		assert!(!res.valid);
+		assert_eq!(res.error.as_deref(), Some("address is not valid for bitcoin"));
+		assert_eq!(res.networks.len(), 3);
+
+		let res = validate_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh".to_owned(), None);
+		assert!(!res.valid);
+		assert!(res.error.is_some());
+		assert!(res.networks.is_empty());
+	}
+
+	#[test]
+	fn test_vanity_prefix() {
+		let address = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+		let matcher = VanityMatcher::Prefix { prefix: "BC1QW508".to_owned(), case_insensitive: true };
+		assert!(matcher.is_match(address));
+		assert!(!matcher.is_match("bc1qw509d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"));
+		assert!(!matcher.is_match("bc1"));
+
+		let matcher = VanityMatcher::Prefix { prefix: "1Hal".to_owned(), case_insensitive: false };
+		assert!(matcher.is_match("1HalfBZuSPJqUCiYy9HWZhtp5ZvEngDAd8"));
+		assert!(!matcher.is_match("1haLfBZuSPJqUCiYy9HWZhtp5ZvEngDAd8"));
+
+		// Uppercase bech32 prefixes are the same as lowercase ones.
+		let network = bitcoin::Network::Bitcoin;
+		assert_eq!(vanity_difficulty(VanityType::P2wpkh, network, "BC1QW5", true), Ok(1024.0));
+		assert_eq!(vanity_difficulty(VanityType::P2wpkh, network, "bc1qw5", true), Ok(1024.0));
+		assert!(vanity_difficulty(VanityType::P2wpkh, network, "bc1qb", true).is_err());
+		assert!(vanity_difficulty(VanityType::P2tr, network, "bc1q", true).is_err());
+		assert_eq!(vanity_difficulty(VanityType::P2pkh, network, "1Ha", false), Ok(58.0 * 58.0));
+		assert_eq!(vanity_difficulty(VanityType::P2pkh, network, "1Ha", true), Ok(29.0 * 29.0));
+		assert!(vanity_difficulty(VanityType::P2pkh, network, "1H0", false).is_err());
+	}
 }
@@ -1,5 +1,4 @@
 
-use std::str::FromStr;
 
 use bitcoin::bip32;
 use clap;
@@ -10,79 +9,107 @@ pub fn subcommand<'a>() -> clap::App<'a, 'a> {
 	cmd::subcommand_group("bip32", "BIP-32 key derivation")
 		.subcommand(cmd_derive())
 		.subcommand(cmd_inspect())
+		.subcommand(cmd_descriptors())
+		.subcommand(cmd_convert())
 }
 
 pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
 	match args.subcommand() {
 		("derive", Some(ref m)) => exec_derive(&m),
 		("inspect", Some(ref m)) => exec_inspect(&m),
+		("descriptors", Some(m)) => exec_descriptors(m),
+		("convert", Some(m)) => exec_convert(m),
 		(_, _) => unreachable!("clap prints help"),
 	};
 }
 
+/// Parse an extended key in any of the SLIP-132 versions.
+fn ext_key(
+	s: &str,
+) -> (Option<bip32::Xpriv>, bip32::Xpub, &'static hal::bip32::Slip132Version) {
+	hal::bip32::decode_slip132(s).need("invalid extended key")
+}
+
 fn cmd_derive<'a>() -> clap::App<'a, 'a> {
-	cmd::subcommand("derive", "derive keys from an extended key")
-		.arg(args::arg("ext-key", "extended public or private key").required(true))
-		.arg(args::arg("derivation-path", "the derivation path").required(true))
+	cmd::subcommand("derive", "derive keys from an extended key\n\n\
+		The key can be given as a key expression with origin and derivation path, \
+		like [d34db33f/84h/0h/0h]xpub.../0/*. Derivation paths can contain ranges like \
+		0-99 and wildcards, which result in a list of derived keys, of at most 100000 keys.")
+		.arg(args::arg("ext-key", "extended public or private key, \
+			or a key expression").required(true))
+		.arg(args::arg("derivation-path", "the derivation path, \
+			appended to the path of the key expression"))
+		.arg(args::opt("range", "the range of indices to derive for wildcards \
+			as <start>..<end> (exclusive), <start>..=<end> (inclusive) or a single index; \
+			defaults to index 0"))
 }
 
 fn exec_derive<'a>(args: &clap::ArgMatches<'a>) {
-	let path_str = args.value_of("derivation-path").unwrap();
-	let path: bip32::DerivationPath = path_str.parse().need("error parsing derivation path");
-	let key_str = args.value_of("ext-key").unwrap();
-
-	let master_fingerprint;
-	let mut derived_xpriv = None;
-	let derived_xpub = match bip32::Xpriv::from_str(&key_str) {
-		Ok(ext_priv) => {
-			derived_xpriv = Some(ext_priv.derive_priv(&SECP, &path).need("derivation error"));
-			master_fingerprint = ext_priv.fingerprint(&SECP);
-			bip32::Xpub::from_priv(&SECP, derived_xpriv.as_ref().unwrap())
-		}
-		Err(_) => {
-			let ext_pub = bip32::Xpub::from_str(key_str).need("invalid extended key");
-			master_fingerprint = ext_pub.fingerprint();
-			ext_pub.derive_pub(&SECP, &path).need("derivation error")
-		}
+	let key_expr = args.value_of("ext-key").unwrap();
+	let (origin, key_str, key_path) = hal::bip32::split_key_expression(key_expr)
+		.need("invalid key expression");
+	let (ext_priv, ext_pub, version) = ext_key(key_str);
+
+	let path_str = match args.value_of("derivation-path") {
+		Some(p) if !key_path.is_empty() => format!("{}/{}", key_path, p.trim_start_matches("m/")),
+		Some(p) => p.to_owned(),
+		None => key_path.to_owned(),
 	};
-
-	let info = hal::bip32::DerivationInfo {
-		network: derived_xpub.network,
-		master_fingerprint: Some(master_fingerprint),
-		path: Some(path),
-		xpriv: derived_xpriv,
-		xpub: derived_xpub,
-		chain_code: derived_xpub.chain_code,
-		identifier: derived_xpub.identifier(),
-		fingerprint: derived_xpub.fingerprint(),
-		public_key: derived_xpub.public_key,
-		private_key: derived_xpriv.map(|x| x.private_key),
-		addresses: hal::address::Addresses::from_pubkey(
-			&bitcoin::PublicKey::new(derived_xpub.public_key),
-			args.network_from_kind(derived_xpub.network),
-		),
+	let expanded = hal::bip32::expand_derivation_path(&path_str, util::derivation_range(args))
+		.need("error parsing derivation path");
+
+	// Without origin, the master is only known if the given key is a master key.
+	let origin = match origin {
+		Some(origin) => Some(origin),
+		None if ext_pub.depth == 0 => Some((ext_pub.fingerprint(), bip32::DerivationPath::master())),
+		None => None,
 	};
 
-	args.print_output(&info)
+	let infos = expanded.paths.into_iter().map(|path| {
+		let derived_xpriv = ext_priv.map(|k| k.derive_priv(&SECP, &path).need("derivation error"));
+		let derived_xpub = match derived_xpriv {
+			Some(ref xpriv) => bip32::Xpub::from_priv(&SECP, xpriv),
+			None => ext_pub.derive_pub(&SECP, &path).need("derivation error"),
+		};
+
+		hal::bip32::DerivationInfo {
+			network: derived_xpub.network,
+			master_fingerprint: origin.as_ref().map(|o| o.0),
+			path: origin.as_ref().map(|o| o.1.extend(&path)),
+			xpriv: derived_xpriv,
+			xpub: derived_xpub,
+			chain_code: derived_xpub.chain_code,
+			identifier: derived_xpub.identifier(),
+			fingerprint: derived_xpub.fingerprint(),
+			public_key: derived_xpub.public_key,
+			private_key: derived_xpriv.map(|x| x.private_key),
+			addresses: hal::address::Addresses::from_pubkey(
+				&bitcoin::PublicKey::new(derived_xpub.public_key),
+				args.network_from_kind(derived_xpub.network),
+			),
+			slip132: Some(hal::bip32::Slip132Info::new(
+				derived_xpriv.as_ref(), &derived_xpub, version,
+			)),
+		}
+	}).collect::<Vec<_>>();
+
+	if expanded.ranged {
+		args.print_output(&infos)
+	} else {
+		args.print_output(&infos[0])
+	}
 }
 
 fn cmd_inspect<'a>() -> clap::App<'a, 'a> {
-	cmd::subcommand("inspect", "inspect a BIP-32 xpub or xpriv")
+	cmd::subcommand("inspect", "inspect a BIP-32 xpub or xpriv\n\n\
+		All SLIP-132 versions like ypub and zpub are supported.")
 		.arg(args::arg("ext-key", "extended public or private key").required(true))
 }
 
 fn exec_inspect<'a>(args: &clap::ArgMatches<'a>) {
 	let key_str = args.value_of("ext-key").unwrap();
 
-	let mut xpriv = None;
-
-	let xpub = match bip32::Xpriv::from_str(&key_str) {
-		Ok(ext_priv) => {
-			xpriv = Some(ext_priv);
-			bip32::Xpub::from_priv(&SECP, xpriv.as_ref().unwrap())
-		}
-		Err(_) => key_str.parse().need("invalid extended key"),
-	};
+	let (xpriv, xpub, version) = ext_key(key_str);
 
 	let info = hal::bip32::DerivationInfo {
 		network: xpub.network,
@@ -99,7 +126,94 @@ fn exec_inspect<'a>(args: &clap::ArgMatches<'a>) {
 			&bitcoin::PublicKey::new(xpub.public_key),
 			args.network_from_kind(xpub.network),
 		),
+		slip132: Some(hal::bip32::Slip132Info::new(xpriv.as_ref(), &xpub, version)),
 	};
 
 	args.print_output(&info)
 }
+
+fn cmd_descriptors<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("descriptors", "get the standard wallet descriptors for a key\n\n\
+		For a master xpriv or a BIP-39 mnemonic, the BIP-44, BIP-49, BIP-84 and BIP-86 \
+		single-sig descriptors and the BIP-48 and BIP-87 multisig key expressions are given. \
+		Other extended keys are used as account keys, in which case the key origin can be \
+		given with --origin.")
+		.arg(args::arg("key", "a master xpriv, an account xpub or xpriv, \
+			or a BIP-39 mnemonic").required(true))
+		.arg(args::opt("passphrase", "the BIP-39 passphrase for a mnemonic"))
+		.arg(args::opt("account", "the account number").default_value("0"))
+		.arg(args::opt("origin", "the key origin of an account key as [fingerprint/path]"))
+		.arg(args::flag("private", "use the private keys in the descriptors"))
+}
+
+fn exec_descriptors<'a>(args: &clap::ArgMatches<'a>) {
+	let key_str = args.value_of("key").need("no key provided");
+	let account = args.value_of("account").unwrap_or("0").parse::<u32>()
+		.need("invalid account number");
+
+	let master = if key_str.trim().contains(' ') {
+		let mnemonic = bip39::Mnemonic::parse(key_str).need("invalid mnemonic phrase");
+		let seed = mnemonic.to_seed(args.value_of("passphrase").unwrap_or(""));
+		Some(bip32::Xpriv::new_master(args.network(), &seed).need("invalid seed"))
+	} else {
+		match ext_key(key_str) {
+			(Some(xpriv), _, version) if xpriv.depth == 0 && version.is_standard()
+				&& !args.is_present("origin") => Some(xpriv),
+			_ => None,
+		}
+	};
+
+	let info = if let Some(master) = master {
+		hal::bip32::wallet_descriptors(&master, account, args.is_present("private"))
+			.need("failed to create descriptors")
+	} else {
+		let origin = args.value_of("origin")
+			.map(|s| hal::bip32::parse_key_origin(s).need("invalid key origin"));
+		let (xpriv, xpub, version) = ext_key(key_str);
+		// Non-standard SLIP-132 versions imply the script type.
+		if !version.is_standard() && version.purpose.is_none() {
+			exit!("{} keys are used for multisig", version.public_prefix);
+		}
+		let purpose = if version.is_standard() { None } else { version.purpose };
+		match xpriv {
+			Some(xpriv) if args.is_present("private") => {
+				hal::bip32::account_descriptors(&xpriv, origin, purpose)
+			}
+			_ => {
+				if args.is_present("private") {
+					exit!("can't use --private with an xpub");
+				}
+				hal::bip32::account_descriptors(&xpub, origin, purpose)
+			}
+		}
+	};
+	args.print_output(&info)
+}
+
+fn cmd_convert<'a>() -> clap::App<'a, 'a> {
+	lazy_static! {
+		static ref VERSION_HELP: String = format!(
+			"the SLIP-132 version to convert to, one of: {}",
+			hal::bip32::SLIP132_VERSIONS.iter().map(|v| v.public_prefix).collect::<Vec<_>>()
+				.join(", "),
+		);
+	}
+
+	cmd::subcommand("convert", "convert an extended key to another SLIP-132 version")
+		.arg(args::arg("ext-key", "extended public or private key").required(true))
+		.arg(args::arg("version", "the version").required(true).help(&VERSION_HELP))
+}
+
+fn exec_convert<'a>(args: &clap::ArgMatches<'a>) {
+	let (xpriv, xpub, _) = ext_key(args.value_of("ext-key").need("no extended key provided"));
+	let prefix = args.value_of("version").need("no version provided");
+	let version = hal::bip32::Slip132Version::from_prefix(prefix).need("unknown version");
+
+	let converted = match xpriv {
+		// Convert private keys only when a private version is asked.
+		Some(ref xpriv) if prefix == version.private_prefix => version.encode_xpriv(xpriv),
+		None if prefix == version.private_prefix => exit!("can't convert a public key to {}", prefix),
+		_ => version.encode_xpub(&xpub),
+	}.need("can't convert key");
+	print!("{}", converted);
+}
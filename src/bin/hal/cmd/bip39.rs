@@ -1,6 +1,11 @@
-use std::io;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::{io, process, thread};
 
-use bip39::Mnemonic;
+use bip39::{Language, Mnemonic};
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::{bip32, Address, AddressType, Network, ScriptBuf};
 use bitcoin::hashes::{sha256, Hash};
 use bitcoin::secp256k1::rand::{self, RngCore};
 use clap;
@@ -26,12 +31,20 @@ pub fn subcommand<'a>() -> clap::App<'a, 'a> {
 	cmd::subcommand_group("bip39", "BIP-39 mnemonics")
 		.subcommand(cmd_generate())
 		.subcommand(cmd_get_seed())
+		.subcommand(cmd_derive())
+		.subcommand(cmd_check())
+		.subcommand(cmd_last_word())
+		.subcommand(cmd_recover_missing())
 }
 
 pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
 	match args.subcommand() {
 		("generate", Some(ref m)) => exec_generate(&m),
 		("get-seed", Some(ref m)) => exec_get_seed(&m),
+		("derive", Some(m)) => exec_derive(m),
+		("check", Some(m)) => exec_check(m),
+		("last-word", Some(m)) => exec_last_word(m),
+		("recover-missing", Some(m)) => exec_recover_missing(m),
 		(_, _) => unreachable!("clap prints help"),
 	};
 }
@@ -55,6 +68,8 @@ fn cmd_generate<'a>() -> clap::App<'a, 'a> {
 			.help(&LANGUAGE_HELP))
 		.arg(args::arg("entropy", "hex-encoded entropy data").long("entropy"))
 		.arg(args::flag("stdin", "read entropy from stdin"))
+		.args(&args::opts_physical_entropy())
+		.args(&args::opts_encrypt())
 }
 
 fn exec_generate<'a>(args: &clap::ArgMatches<'a>) {
@@ -73,9 +88,14 @@ fn exec_generate<'a>(args: &clap::ArgMatches<'a>) {
 	let nb_entropy_bytes = (word_count / 3) * 4;
 
 	let mut entropy;
-	match (args.is_present("entropy"), args.is_present("stdin")) {
-		(true, true) => exit!("can't provide --entropy and --stdin"),
-		(true, false) => {
+	let physical = util::physical_entropy(args, nb_entropy_bytes);
+	match (args.is_present("entropy"), args.is_present("stdin"), physical) {
+		(true, true, _) => exit!("can't provide --entropy and --stdin"),
+		(true, _, Some(_)) | (_, true, Some(_)) => {
+			exit!("can't combine dice, coins or cards with --entropy or --stdin")
+		}
+		(false, false, Some(physical)) => entropy = physical,
+		(true, false, None) => {
 			let entropy_hex = args.value_of("entropy").unwrap();
 			if entropy_hex.len() != nb_entropy_bytes * 2 {
 				exit!(
@@ -85,7 +105,7 @@ fn exec_generate<'a>(args: &clap::ArgMatches<'a>) {
 			}
 			entropy = hex::decode(&entropy_hex).need("invalid entropy hex");
 		}
-		(false, true) => {
+		(false, true, None) => {
 			let mut hasher = sha256::Hash::engine();
 			let stdin = io::stdin();
 			let read = io::copy(&mut stdin.lock(), &mut hasher).need("error reading stdin");
@@ -94,7 +114,7 @@ fn exec_generate<'a>(args: &clap::ArgMatches<'a>) {
 			}
 			entropy = sha256::Hash::from_engine(hasher)[0..nb_entropy_bytes].to_vec();
 		}
-		(false, false) => {
+		(false, false, None) => {
 			entropy = vec![0; nb_entropy_bytes];
 			rand::thread_rng().fill_bytes(&mut entropy);
 		}
@@ -102,7 +122,7 @@ fn exec_generate<'a>(args: &clap::ArgMatches<'a>) {
 
 	assert!(entropy.len() == nb_entropy_bytes);
 	let mnemonic = Mnemonic::from_entropy_in(language, &entropy).unwrap();
-	args.print_output(&hal::GetInfo::get_info(&mnemonic, network))
+	args.print_secret_output(&hal::GetInfo::get_info(&mnemonic, network))
 }
 
 fn cmd_get_seed<'a>() -> clap::App<'a, 'a> {
@@ -129,6 +149,331 @@ fn exec_get_seed<'a>(args: &clap::ArgMatches<'a>) {
 	args.print_output(&info)
 }
 
+fn cmd_derive<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand(
+		"derive",
+		"derive the account keys and first addresses for a given BIP-39 mnemonic\n\n\
+		By default, the BIP-44, BIP-49, BIP-84 and BIP-86 accounts are derived.",
+	)
+	.arg(args::arg("mnemonic", "the mnemonic phrase").required(true))
+	.arg(args::arg("passphrase", "the BIP-39 passphrase").long("passphrase"))
+	.arg(args::opt("purpose", "only derive the account for this purpose: 44, 49, 84 or 86"))
+	.arg(args::opt("account", "the account number").default_value("0"))
+	.arg(args::opt("coin-type", "the coin type, defaults to 0 for mainnet and 1 otherwise"))
+	.arg(args::opt("path", "a custom account derivation path instead of the standard ones"))
+	.arg(args::opt("type", "the script type for a custom path if not implied by its purpose: \
+		p2pkh, p2sh-p2wpkh, p2wpkh or p2tr"))
+	.arg(args::opt("addresses", "the number of receive and change addresses to derive")
+		.default_value("5"))
+}
+
+fn exec_derive<'a>(args: &clap::ArgMatches<'a>) {
+	let network = args.network();
+
+	let mnemonic = args.value_of("mnemonic").need("no mnemonic provided");
+	let mnemonic = Mnemonic::parse(mnemonic).need("invalid mnemonic phrase");
+	let seed = mnemonic.to_seed(args.value_of("passphrase").unwrap_or(""));
+	let master = bip32::Xpriv::new_master(network, &seed).need("invalid seed");
+
+	let nb_addresses = args.value_of("addresses").unwrap_or("5").parse::<u32>()
+		.need("invalid number of addresses");
+	let account = args.value_of("account").unwrap_or("0").parse::<u32>()
+		.need("invalid account number");
+	let coin_type = args.value_of("coin-type")
+		.map(|c| c.parse::<u32>().need("invalid coin type"))
+		.unwrap_or_else(|| hal::bip39::coin_type(network));
+
+	let accounts = if let Some(path) = args.value_of("path") {
+		let path = path.parse::<bip32::DerivationPath>().need("invalid derivation path");
+		let purpose = match args.value_of("type") {
+			Some(t) => hal::bip32::single_sig_purpose(t).need("unknown script type"),
+			None => match path.as_ref().first() {
+				Some(bip32::ChildNumber::Hardened { index })
+					if [44, 49, 84, 86].contains(index) => *index,
+				_ => exit!("the script type can't be inferred from the path, use --type"),
+			},
+		};
+		vec![(path, purpose)]
+	} else {
+		let purposes = match args.value_of("purpose") {
+			Some(p) => vec![p.parse::<u32>().need("invalid purpose")],
+			None => vec![44, 49, 84, 86],
+		};
+		purposes.into_iter().map(|purpose| {
+			let path = [purpose, coin_type, account].iter()
+				.map(|i| bip32::ChildNumber::from_hardened_idx(*i))
+				.collect::<Result<Vec<_>, _>>()
+				.need("invalid account path");
+			(bip32::DerivationPath::from(path), purpose)
+		}).collect()
+	};
+
+	let info = hal::bip39::MnemonicDerivationInfo {
+		master_fingerprint: master.fingerprint(&SECP),
+		accounts: accounts.into_iter().map(|(path, purpose)| {
+			hal::bip39::derive_account(&master, &path, purpose, network, nb_addresses)
+				.need("failed to derive account")
+		}).collect(),
+	};
+	args.print_output(&info)
+}
+
+/// Get the language from the language argument or the one that knows most of the words.
+fn mnemonic_language<'a>(args: &clap::ArgMatches<'a>, words: &[&str]) -> Language {
+	if let Some(s) = args.value_of("language") {
+		return hal::bip39::parse_language(s).need("invalid language string");
+	}
+	Language::ALL.iter().cloned().max_by_key(|l| {
+		// Prefer the first language on ties.
+		let known = words.iter().filter(|w| l.find_word(w).is_some()).count();
+		(known, usize::MAX - Language::ALL.iter().position(|x| x == l).unwrap())
+	}).unwrap()
+}
+
+/// Split a mnemonic argument into lowercase words.
+fn mnemonic_words(mnemonic: &str) -> Vec<String> {
+	mnemonic.split_whitespace().map(|w| w.to_lowercase()).collect()
+}
+
+fn cmd_check<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("check", "check a BIP-39 mnemonic for errors\n\n\
+		Reports the words that are not in the wordlist, with suggestions, \
+		and whether the checksum is valid. Exits with an error if the mnemonic is invalid.")
+		.arg(args::arg("mnemonic", "the mnemonic phrase").required(true))
+		.arg(args::opt("language", "the language of the mnemonic, detected if omitted"))
+}
+
+fn exec_check<'a>(args: &clap::ArgMatches<'a>) {
+	let words = mnemonic_words(args.value_of("mnemonic").need("no mnemonic provided"));
+	let word_refs = words.iter().map(|w| w.as_str()).collect::<Vec<_>>();
+	let language = mnemonic_language(args, &word_refs);
+
+	let checks = words.iter().enumerate().map(|(index, word)| {
+		let in_wordlist = language.find_word(word).is_some();
+		let suggestions = if in_wordlist {
+			Vec::new()
+		} else {
+			// Words with the same first four letters or a small edit distance.
+			let mut candidates = language.word_list().iter().filter_map(|w| {
+				let distance = util::lev_distance(word, w);
+				let prefix = word.chars().count() >= 4 && w.chars().take(4).eq(word.chars().take(4));
+				if distance <= 2 || prefix {
+					Some((if prefix { 0 } else { distance }, *w))
+				} else {
+					None
+				}
+			}).collect::<Vec<_>>();
+			candidates.sort();
+			candidates.into_iter().take(5).map(|(_, w)| w.to_owned()).collect()
+		};
+		hal::bip39::WordCheckInfo {
+			index,
+			word: word.clone(),
+			in_wordlist,
+			suggestions,
+		}
+	}).collect::<Vec<_>>();
+
+	let indices = words.iter().map(|w| language.find_word(w)).collect::<Option<Vec<_>>>();
+	let valid_word_count = hal::bip39::valid_word_count(words.len());
+	let checksum_valid = match indices {
+		Some(ref i) if valid_word_count => Some(hal::bip39::checksum_valid(i)),
+		_ => None,
+	};
+	let info = hal::bip39::MnemonicCheckInfo {
+		language: if checks.iter().any(|c| c.in_wordlist) {
+			Some(hal::bip39::language_name(language))
+		} else {
+			None
+		},
+		word_count: words.len(),
+		valid_word_count,
+		words: checks,
+		checksum_valid,
+		valid: checksum_valid == Some(true),
+	};
+	args.print_output(&info);
+	if !info.valid {
+		process::exit(1);
+	}
+}
+
+fn cmd_last_word<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("last-word", "list all valid last words for the first words of a mnemonic")
+		.arg(args::arg("words", "the first 11, 14, 17, 20 or 23 words").required(true))
+		.arg(args::opt("language", "the language of the mnemonic, detected if omitted"))
+}
+
+fn exec_last_word<'a>(args: &clap::ArgMatches<'a>) {
+	let words = mnemonic_words(args.value_of("words").need("no words provided"));
+	let word_refs = words.iter().map(|w| w.as_str()).collect::<Vec<_>>();
+	let language = mnemonic_language(args, &word_refs);
+
+	let indices = words.iter().map(|w| {
+		language.find_word(w).need(&format!("word not in the wordlist: {}", w))
+	}).collect::<Vec<_>>();
+	let last_words = hal::bip39::last_words(language, &indices).need("invalid mnemonic prefix");
+	args.print_output(&last_words)
+}
+
+/// What a recovered mnemonic should match.
+/// The maximum number of receive and change addresses to check for each candidate.
+const MAX_GAP: u32 = 10_000;
+
+enum RecoveryTarget {
+	Fingerprint(bip32::Fingerprint),
+	Address {
+		purpose: u32,
+		script_pubkey: ScriptBuf,
+	},
+}
+
+impl RecoveryTarget {
+	fn is_match(&self, seed: &[u8], network: Network, gap: u32) -> bool {
+		let master = bip32::Xpriv::new_master(network, seed).expect("valid seed");
+		match *self {
+			RecoveryTarget::Fingerprint(fp) => master.fingerprint(&SECP) == fp,
+			RecoveryTarget::Address { purpose, ref script_pubkey } => {
+				let path = [purpose, hal::bip39::coin_type(network), 0].iter()
+					.map(|i| bip32::ChildNumber::Hardened { index: *i })
+					.collect::<Vec<_>>();
+				let xpriv = master.derive_priv(&SECP, &path).expect("valid path");
+				let xpub = bip32::Xpub::from_priv(&SECP, &xpriv);
+				// The gap is at most MAX_GAP, so the indices are valid.
+				(0..2).any(|branch| (0..gap).any(|i| {
+					let child = [
+						bip32::ChildNumber::Normal { index: branch },
+						bip32::ChildNumber::Normal { index: i },
+					];
+					let pk = xpub.derive_pub(&SECP, &child).expect("valid path").public_key;
+					hal::bip32::single_sig_address(purpose, &pk, network)
+						.map(|a| a.script_pubkey() == *script_pubkey)
+						.unwrap_or(false)
+				}))
+			}
+		}
+	}
+}
+
+fn cmd_recover_missing<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("recover-missing", "recover a mnemonic with missing or swapped words\n\n\
+		Unknown words are given as ?, at most two are supported. The candidates with a valid \
+		checksum are checked against the target address or master key fingerprint. \
+		For address targets, the first receive and change addresses of the first BIP-44, \
+		BIP-49, BIP-84 or BIP-86 account, depending on the address type, are checked.")
+		.arg(args::arg("mnemonic", "the mnemonic phrase with ? for unknown words").required(true))
+		.arg(args::opt("language", "the language of the mnemonic, detected if omitted"))
+		.arg(args::arg("passphrase", "the BIP-39 passphrase").long("passphrase"))
+		.arg(args::opt("address", "an address of the wallet"))
+		.arg(args::opt("fingerprint", "the fingerprint of the master key"))
+		.arg(args::opt("gap", "the number of receive and change addresses to check, \
+			at most 10000").default_value("10"))
+		.arg(args::flag("swapped", "also try all swaps of two words"))
+		.arg(args::opt("threads", "the number of threads to use, defaults to the number of CPUs"))
+}
+
+fn parse_gap(s: &str) -> Result<u32, String> {
+	let gap = s.parse::<u32>().map_err(|e| e.to_string())?;
+	if gap == 0 || gap > MAX_GAP {
+		return Err(format!("gap must be between 1 and {}", MAX_GAP));
+	}
+	Ok(gap)
+}
+
+fn exec_recover_missing<'a>(args: &clap::ArgMatches<'a>) {
+	let network = args.network();
+	let words = mnemonic_words(args.value_of("mnemonic").need("no mnemonic provided"));
+	let known = words.iter().filter(|w| *w != "?").map(|w| w.as_str()).collect::<Vec<_>>();
+	let language = mnemonic_language(args, &known);
+	if !hal::bip39::valid_word_count(words.len()) {
+		exit!("invalid number of words: {}", words.len());
+	}
+	let indices = words.iter().map(|w| if w == "?" {
+		None
+	} else {
+		Some(language.find_word(w).need(&format!("word not in the wordlist: {}", w)))
+	}).collect::<Vec<_>>();
+	let nb_unknown = indices.iter().filter(|w| w.is_none()).count();
+	if nb_unknown > 2 {
+		exit!("at most two unknown words are supported");
+	}
+
+	let target = match (args.value_of("address"), args.value_of("fingerprint")) {
+		(Some(_), Some(_)) => exit!("can't provide both --address and --fingerprint"),
+		(Some(a), None) => {
+			let address = a.parse::<Address<NetworkUnchecked>>().need("invalid address")
+				.require_network(network).need("address is not for the selected network");
+			let purpose = match address.address_type() {
+				Some(AddressType::P2pkh) => 44,
+				Some(AddressType::P2sh) => 49,
+				Some(AddressType::P2wpkh) => 84,
+				Some(AddressType::P2tr) => 86,
+				_ => exit!("unsupported address type"),
+			};
+			Some(RecoveryTarget::Address { purpose, script_pubkey: address.script_pubkey() })
+		}
+		(None, Some(fp)) => Some(RecoveryTarget::Fingerprint(fp.parse().need("invalid fingerprint"))),
+		(None, None) if nb_unknown > 1 => {
+			exit!("an --address or --fingerprint is needed for more than one unknown word")
+		}
+		(None, None) => None,
+	};
+	let gap = parse_gap(args.value_of("gap").unwrap_or("10")).need("invalid gap");
+	let passphrase = args.value_of("passphrase").unwrap_or("");
+
+	let mut bases = vec![indices.clone()];
+	if args.is_present("swapped") {
+		for i in 0..indices.len() {
+			for j in i + 1..indices.len() {
+				if indices[i] != indices[j] {
+					let mut swapped = indices.clone();
+					swapped.swap(i, j);
+					bases.push(swapped);
+				}
+			}
+		}
+	}
+
+	let threads = match args.value_of("threads") {
+		Some(t) => t.parse::<u16>().need("invalid number of threads"),
+		None => thread::available_parallelism().map(|n| n.get() as u16).unwrap_or(1),
+	};
+	if threads == 0 {
+		exit!("need at least one thread");
+	}
+
+	let candidates = AtomicU64::new(0);
+	let matches = Mutex::new(BTreeSet::new());
+	thread::scope(|s| {
+		for shard in 0..threads {
+			let (bases, target, candidates, matches) = (&bases, &target, &candidates, &matches);
+			s.spawn(move || for base in bases {
+				hal::bip39::fill_unknown_words(base, (shard, threads), |c| {
+					candidates.fetch_add(1, Ordering::Relaxed);
+					let mnemonic = Mnemonic::from_entropy_in(language, &hal::bip39::entropy(c))
+						.expect("valid entropy");
+					let is_match = match target {
+						Some(t) => t.is_match(&mnemonic.to_seed(passphrase), network, gap),
+						None => true,
+					};
+					if is_match {
+						matches.lock().unwrap().insert(mnemonic.to_string());
+					}
+				});
+			});
+		}
+	});
+
+	let info = hal::bip39::MnemonicRecoveryInfo {
+		candidates: candidates.into_inner(),
+		matches: matches.into_inner().unwrap().into_iter().collect(),
+	};
+	args.print_output(&info);
+	if info.matches.is_empty() {
+		process::exit(1);
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -141,4 +486,13 @@ mod tests {
 			assert!(unique.insert(lang));
 		}
 	}
+
+	#[test]
+	fn test_parse_gap() {
+		assert_eq!(parse_gap("20"), Ok(20));
+		assert_eq!(parse_gap("10000"), Ok(10_000));
+		assert!(parse_gap("0").is_err());
+		assert!(parse_gap("2147483648").is_err());
+		assert!(parse_gap("-1").is_err());
+	}
 }
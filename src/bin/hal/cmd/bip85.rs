@@ -0,0 +1,141 @@
+use bip39::Mnemonic;
+use bitcoin::bip32;
+use clap;
+
+use crate::prelude::*;
+
+pub fn subcommand<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand_group("bip85", "BIP-85 deterministic entropy from a master key")
+		.subcommand(cmd_mnemonic())
+		.subcommand(cmd_wif())
+		.subcommand(cmd_xprv())
+		.subcommand(cmd_hex())
+		.subcommand(cmd_password())
+		.subcommand(cmd_dice())
+}
+
+pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
+	match args.subcommand() {
+		("mnemonic", Some(m)) => exec_mnemonic(m),
+		("wif", Some(m)) => exec_wif(m),
+		("xprv", Some(m)) => exec_xprv(m),
+		("hex", Some(m)) => exec_hex(m),
+		("password", Some(m)) => exec_password(m),
+		("dice", Some(m)) => exec_dice(m),
+		(_, _) => unreachable!("clap prints help"),
+	};
+}
+
+/// Create a BIP-85 subcommand with the common arguments.
+fn bip85_subcommand<'a>(name: &'static str, about: &'static str) -> clap::App<'a, 'a> {
+	cmd::subcommand(name, about)
+		.arg(args::arg("master", "the master xpriv or a BIP-39 mnemonic").required(true))
+		.arg(args::opt("passphrase", "the BIP-39 passphrase of the mnemonic"))
+		.arg(args::opt("index", "the index of the derived entropy").default_value("0"))
+}
+
+/// Get the master key and the index from the common arguments.
+fn master_and_index<'a>(args: &clap::ArgMatches<'a>) -> (bip32::Xpriv, u32) {
+	let master_str = args.value_of("master").need("no master key provided");
+	let master = if master_str.trim().contains(' ') {
+		let mnemonic = Mnemonic::parse(master_str).need("invalid mnemonic phrase");
+		let seed = mnemonic.to_seed(args.value_of("passphrase").unwrap_or(""));
+		bip32::Xpriv::new_master(args.network(), &seed).need("invalid seed")
+	} else {
+		if args.is_present("passphrase") {
+			exit!("a passphrase can only be used with a mnemonic");
+		}
+		let (xpriv, _, _) = hal::bip32::decode_slip132(master_str).need("invalid extended key");
+		xpriv.need("the master key should be an xpriv")
+	};
+	let index = args.value_of("index").unwrap_or("0").parse::<u32>().need("invalid index");
+	(master, index)
+}
+
+/// Parse a numeric argument that has a default value.
+fn number<'a>(args: &clap::ArgMatches<'a>, name: &str) -> u32 {
+	args.value_of(name).need("missing argument").parse::<u32>()
+		.need(&format!("invalid {}", name))
+}
+
+fn cmd_mnemonic<'a>() -> clap::App<'a, 'a> {
+	bip85_subcommand("mnemonic", "derive a BIP-39 mnemonic")
+		.arg(args::opt("words", "the number of words").default_value("12"))
+		.arg(args::opt("language", "the language of the mnemonic").default_value("english"))
+}
+
+fn exec_mnemonic<'a>(args: &clap::ArgMatches<'a>) {
+	let (master, index) = master_and_index(args);
+	let language = hal::bip39::parse_language(args.value_of("language").unwrap_or("english"))
+		.need("invalid language string");
+	let info = hal::bip85::mnemonic(
+		&master, language, number(args, "words"), index, args.network(),
+	).need("failed to derive mnemonic");
+	args.print_output(&info)
+}
+
+fn cmd_wif<'a>() -> clap::App<'a, 'a> {
+	bip85_subcommand("wif", "derive a WIF private key")
+}
+
+fn exec_wif<'a>(args: &clap::ArgMatches<'a>) {
+	let (master, index) = master_and_index(args);
+	let info = hal::bip85::wif(&master, index, args.network()).need("failed to derive key");
+	args.print_output(&info)
+}
+
+fn cmd_xprv<'a>() -> clap::App<'a, 'a> {
+	bip85_subcommand("xprv", "derive an extended private key")
+}
+
+fn exec_xprv<'a>(args: &clap::ArgMatches<'a>) {
+	let (master, index) = master_and_index(args);
+	let info = hal::bip85::xprv(&master, index, args.network()).need("failed to derive xprv");
+	args.print_output(&info)
+}
+
+fn cmd_hex<'a>() -> clap::App<'a, 'a> {
+	bip85_subcommand("hex", "derive hex entropy")
+		.arg(args::opt("bytes", "the number of bytes, from 16 to 64").default_value("32"))
+}
+
+fn exec_hex<'a>(args: &clap::ArgMatches<'a>) {
+	let (master, index) = master_and_index(args);
+	let info = hal::bip85::hex(&master, number(args, "bytes"), index)
+		.need("failed to derive entropy");
+	args.print_output(&info)
+}
+
+fn cmd_password<'a>() -> clap::App<'a, 'a> {
+	bip85_subcommand("password", "derive a password\n\n\
+		Base64 passwords are 20 to 86 characters long, base85 passwords 10 to 80 characters.")
+		.arg(args::opt("length", "the number of characters").default_value("21"))
+		.arg(args::opt("encoding", "the encoding of the password")
+			.possible_values(&["base64", "base85"])
+			.default_value("base64"))
+}
+
+fn exec_password<'a>(args: &clap::ArgMatches<'a>) {
+	let (master, index) = master_and_index(args);
+	let length = number(args, "length");
+	let info = match args.value_of("encoding").unwrap_or("base64") {
+		"base64" => hal::bip85::password_base64(&master, length, index),
+		"base85" => hal::bip85::password_base85(&master, length, index),
+		_ => unreachable!("clap checks possible values"),
+	}.need("failed to derive password");
+	args.print_output(&info)
+}
+
+fn cmd_dice<'a>() -> clap::App<'a, 'a> {
+	bip85_subcommand("dice", "derive dice rolls\n\n\
+		The rolls are numbered from 0 to the number of sides minus one.")
+		.arg(args::opt("sides", "the number of sides of the dice").default_value("6"))
+		.arg(args::opt("rolls", "the number of rolls").default_value("10"))
+}
+
+fn exec_dice<'a>(args: &clap::ArgMatches<'a>) {
+	let (master, index) = master_and_index(args);
+	let info = hal::bip85::dice(&master, number(args, "sides"), number(args, "rolls"), index)
+		.need("failed to derive dice rolls");
+	args.print_output(&info)
+}
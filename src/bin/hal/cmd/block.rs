@@ -1,7 +1,14 @@
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use bitcoin::consensus::encode::{deserialize, serialize};
-use bitcoin::{block, Block};
+use bitcoin::consensus::Params;
+use bitcoin::hashes::Hash;
+use bitcoin::pow::{CompactTarget, Target};
+use bitcoin::{block, Address, Amount, Block, OutPoint, ScriptBuf, TxMerkleNode, Txid};
 
 use hal::block::{BlockHeaderInfo, BlockInfo};
 use crate::prelude::*;
@@ -11,12 +18,20 @@ pub fn subcommand<'a>() -> clap::App<'a, 'a> {
 	cmd::subcommand_group("block", "manipulate blocks")
 		.subcommand(cmd_create())
 		.subcommand(cmd_decode())
+		.subcommand(cmd_scan())
+		.subcommand(cmd_template())
+		.subcommand(cmd_verify())
+		.subcommand(cmd_verify_headers())
 }
 
 pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
 	match args.subcommand() {
 		("create", Some(ref m)) => exec_create(&m),
 		("decode", Some(ref m)) => exec_decode(&m),
+		("scan", Some(m)) => exec_scan(m),
+		("template", Some(m)) => exec_template(m),
+		("verify", Some(m)) => exec_verify(m),
+		("verify-headers", Some(m)) => exec_verify_headers(m),
 		(_, _) => unreachable!("clap prints help"),
 	};
 }
@@ -27,9 +42,12 @@ fn cmd_create<'a>() -> clap::App<'a, 'a> {
 			args::arg("block-info", "the block info in JSON").required(false),
 			args::flag("raw-stdout", "output the raw bytes of the result to stdout")
 				.short("r"),
+			args::flag("grind", "grind the nonce until the block hash meets the target of the bits"),
+			args::opt("grind-target", "grind the nonce until the block hash meets this target in hex"),
 		])
 		.long_about(r#"
 Create a block from JSON. Use the same format as the `hal block decode` output.
+Fields derived from the header, like the block hash, target and difficulty, are ignored.
 
 It's possible to pass the JSON string as the first argument or pass it via stdin.
 
@@ -83,7 +101,7 @@ fn exec_create<'a>(args: &clap::ArgMatches<'a>) {
 	}
 
 	let mut used_network = cmd::tx::UsedNetwork::new(args.explicit_network());
-	let block = Block {
+	let mut block = Block {
 		header: create_block_header(info.header),
 		txdata: match (info.transactions, info.raw_transactions) {
 			(Some(_), Some(_)) => exit!("Can't provide transactions both in JSON and raw."),
@@ -98,6 +116,17 @@ fn exec_create<'a>(args: &clap::ArgMatches<'a>) {
 		},
 	};
 
+	let target = if let Some(target) = args.value_of("grind-target") {
+		Some(Target::from_unprefixed_hex(target.trim_start_matches("0x")).need("invalid target"))
+	} else if args.is_present("grind") {
+		Some(block.header.target())
+	} else {
+		None
+	};
+	if let Some(target) = target {
+		hal::block::grind_header(&mut block.header, target).need("failed to grind the nonce");
+	}
+
 	let block_bytes = serialize(&block);
 	if args.is_present("raw-stdout") {
 		::std::io::stdout().write_all(&block_bytes).unwrap();
@@ -142,3 +171,264 @@ fn exec_decode<'a>(args: &clap::ArgMatches<'a>) {
 		args.print_output(&info)
 	}
 }
+
+fn cmd_scan<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("scan", "stream the transactions of a blk*.dat file or raw blocks as JSON lines")
+		.args(&[
+			args::arg("file", "the blk*.dat file or binary blocks; read from stdin if omitted"),
+			args::opt("xor-key", "the key in hex that the block file is obfuscated with"),
+			args::opt("txid", "only output the transaction with this txid")
+				.multiple(true).number_of_values(1),
+			args::opt("address", "only output transactions with an output to this address")
+				.multiple(true).number_of_values(1),
+			args::opt("script-type", "only output transactions with an output of this type")
+				.possible_values(hal::tx::SCRIPT_TYPES).multiple(true).number_of_values(1),
+		])
+		.long_about(r#"
+Stream the transactions of a Bitcoin Core blk*.dat file or of concatenated binary blocks
+and print each as a line of JSON, with the hash of its block and its position in the block.
+Blocks are decoded one transaction at a time, so whole block files can be scanned.
+
+Bitcoin Core obfuscates block files with the key in the xor.dat file in the blocks
+directory. If --xor-key is not given, the xor.dat file next to the block file is used.
+
+When filters are given, transactions that match any of them are printed."#
+	)
+}
+
+fn exec_scan<'a>(args: &clap::ArgMatches<'a>) {
+	let network = args.network();
+	let txids = args.values_of("txid").into_iter().flatten()
+		.map(|t| t.parse::<Txid>().need("invalid txid"))
+		.collect::<HashSet<_>>();
+	let scripts = args.values_of("address").into_iter().flatten()
+		.map(|a| a.parse::<Address<_>>().need("invalid address")
+			.require_network(network).need("address is not for the selected network")
+			.script_pubkey())
+		.collect::<HashSet<_>>();
+	let script_types = args.values_of("script-type").into_iter().flatten().collect::<HashSet<_>>();
+	let filter = !txids.is_empty() || !scripts.is_empty() || !script_types.is_empty();
+
+	let (reader, xor_key): (Box<dyn Read>, _) = match args.value_of("file") {
+		Some(path) => {
+			let xor_path = Path::new(path).with_file_name("xor.dat");
+			let xor_key = match args.value_of("xor-key") {
+				Some(key) => hex::decode(key).need("invalid xor key hex"),
+				None if xor_path.is_file() => fs::read(xor_path).need("error reading xor.dat"),
+				None => Vec::new(),
+			};
+			(Box::new(File::open(path).need("failed to open block file")), xor_key)
+		}
+		None => {
+			let xor_key = args.value_of("xor-key")
+				.map(|k| hex::decode(k).need("invalid xor key hex")).unwrap_or_default();
+			(Box::new(io::stdin()), xor_key)
+		}
+	};
+
+	let stdout = io::stdout();
+	let mut stdout = stdout.lock();
+	let reader = hal::block::XorReader::new(reader, xor_key);
+	hal::block::stream_blocks(reader, network.magic(), |header, index, tx| {
+		if filter {
+			let matches = txids.contains(&tx.compute_txid()) || tx.output.iter().any(|o| {
+				scripts.contains(&o.script_pubkey)
+					|| script_types.contains(hal::tx::script_type(&o.script_pubkey))
+			});
+			if !matches {
+				return;
+			}
+		}
+		let info = hal::block::BlockTransactionInfo {
+			block_hash: header.block_hash(),
+			index,
+			transaction: hal::GetInfo::get_info(&tx, network),
+		};
+		let line = serde_json::to_string(&info).need("failed to encode JSON");
+		if writeln!(stdout, "{}", line).is_err() {
+			// The output was closed, like when piped into head.
+			std::process::exit(0);
+		}
+	}).need("failed to read blocks");
+}
+
+fn cmd_template<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("template", "assemble a block with a new coinbase from raw transactions")
+		.args(&[
+			args::arg("raw-tx", "the raw transactions in hex to include after the coinbase")
+				.multiple(true),
+			args::opt("height", "the height of the block").required(true),
+			args::opt("prev-block-hash", "the hash of the previous block").required(true),
+			args::opt("address", "the address to pay the coinbase to"),
+			args::opt("script", "the scriptPubKey in hex to pay the coinbase to"),
+			args::opt("subsidy", "the block subsidy in satoshi, the network's subsidy by default"),
+			args::opt("fees", "the fees of the transactions in satoshi").default_value("0"),
+			args::opt("extra-nonce", "the extra nonce in hex to put in the coinbase scriptSig"),
+			args::flag("no-witness-commitment", "don't add a witness commitment to the coinbase"),
+			args::opt("version", "the block version").default_value("536870912"),
+			args::opt("time", "the block time, the current time by default"),
+			args::opt("bits", "the compact target in hex, the network's maximum by default"),
+			args::flag("grind", "grind the nonce until the block hash meets the target"),
+			args::flag("raw-stdout", "output the raw bytes of the result to stdout")
+				.short("r"),
+		])
+		.long_about(r#"
+Assemble a block with a coinbase for the given height that pays the subsidy and the fees to
+the given address or script, followed by the given raw transactions. The merkle root is set
+and a witness commitment is added to the coinbase, like Bitcoin Core does.
+
+Use --grind to find a nonce so that the block has valid proof of work, which is only
+feasible for low difficulty like on regtest."#
+	)
+}
+
+fn exec_template<'a>(args: &clap::ArgMatches<'a>) {
+	let network = args.network();
+	let height = args.value_of("height").need("no height provided")
+		.parse::<u32>().need("invalid height");
+	let script_pubkey = match (args.value_of("address"), args.value_of("script")) {
+		(Some(_), Some(_)) => exit!("can't provide both an address and a script"),
+		(None, None) => exit!("provide either an address or a script to pay to"),
+		(Some(addr), None) => addr.parse::<Address<_>>().need("invalid address")
+			.require_network(network).need("address is not for the selected network")
+			.script_pubkey(),
+		(None, Some(hex)) => ScriptBuf::from(hex::decode(hex).need("invalid script hex")),
+	};
+	let subsidy = match args.value_of("subsidy") {
+		Some(s) => Amount::from_sat(s.parse().need("invalid subsidy")),
+		None => hal::block::block_subsidy(height, network),
+	};
+	let fees = Amount::from_sat(args.value_of("fees").need("no fees").parse().need("invalid fees"));
+	let extra_nonce = args.value_of("extra-nonce")
+		.map(|n| hex::decode(n).need("invalid extra nonce hex")).unwrap_or_default();
+	let coinbase = hal::block::create_coinbase(
+		height, script_pubkey, subsidy + fees, &extra_nonce, !args.is_present("no-witness-commitment"),
+	).need("failed to create coinbase");
+	let transactions = args.values_of("raw-tx").into_iter().flatten()
+		.map(|tx| deserialize(&hex::decode(tx).need("invalid raw transaction hex"))
+			.need("invalid raw transaction"))
+		.collect();
+
+	let time = match args.value_of("time") {
+		Some(t) => t.parse().need("invalid time"),
+		None => SystemTime::now().duration_since(UNIX_EPOCH).need("invalid system time").as_secs() as u32,
+	};
+	let bits = match args.value_of("bits") {
+		Some(b) => CompactTarget::from_unprefixed_hex(b.trim_start_matches("0x")).need("invalid bits"),
+		None => Params::new(network).max_attainable_target.to_compact_lossy(),
+	};
+	let header = block::Header {
+		version: block::Version::from_consensus(
+			args.value_of("version").need("no version").parse().need("invalid version"),
+		),
+		prev_blockhash: args.value_of("prev-block-hash").need("no previous block hash")
+			.parse().need("invalid previous block hash"),
+		merkle_root: TxMerkleNode::all_zeros(),
+		time,
+		bits,
+		nonce: 0,
+	};
+
+	let mut block = hal::block::assemble_block(header, coinbase, transactions);
+	if args.is_present("grind") {
+		let target = block.header.target();
+		hal::block::grind_header(&mut block.header, target).need("failed to grind the nonce");
+	}
+
+	let block_bytes = serialize(&block);
+	if args.is_present("raw-stdout") {
+		::std::io::stdout().write_all(&block_bytes).unwrap();
+	} else {
+		print!("{}", hex::encode(&block_bytes));
+	}
+}
+
+fn cmd_verify<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("verify", "verify the transactions of a raw block against its header")
+		.arg(args::arg("raw-block", "the raw block in hex").required(false))
+		.arg(args::opt("height", "the height of the block, to check the BIP-34 height"))
+		.arg(args::opt("prevout", "the outpoint and value in satoshi of an output spent \
+			in the block, like <txid>:<vout>:<value>, to check the coinbase value")
+			.multiple(true).number_of_values(1))
+		.long_about(r#"
+Verify the merkle root, the coinbase, the BIP-141 witness commitment, the weight limit and,
+with --height, the BIP-34 height in the coinbase.
+
+When the outputs spent by the block are given with --prevout, the fees are calculated and
+the coinbase is checked to not claim more than the subsidy and the fees. Outputs that are
+created earlier in the block don't have to be given."#
+	)
+}
+
+fn exec_verify<'a>(args: &clap::ArgMatches<'a>) {
+	let hex_block = util::arg_or_stdin(args, "raw-block");
+	let raw_block = hex::decode(hex_block.as_ref()).need("could not decode raw block hex");
+	let block: Block = deserialize(&raw_block).need("invalid block format");
+	let height = args.value_of("height").map(|h| h.parse::<u32>().need("invalid height"));
+	let prevouts = args.values_of("prevout").map(|values| values.map(|p| {
+		let (outpoint, value) = p.rsplit_once(':').need("prevout must be <txid>:<vout>:<value>");
+		let outpoint = outpoint.parse::<OutPoint>().need("invalid prevout outpoint");
+		(outpoint, Amount::from_sat(value.parse().need("invalid prevout value")))
+	}).collect::<HashMap<_, _>>());
+
+	let info = hal::block::verify_block(&block, height, prevouts.as_ref(), args.network());
+	args.print_output(&info)
+}
+
+fn cmd_verify_headers<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("verify-headers", "verify a chain of block headers")
+		.arg(args::arg("headers", "file with the headers, either one in hex per line or \
+			concatenated raw headers; read from stdin if omitted"))
+		.arg(args::opt("start-height", "the height of the first header").default_value("0"))
+		.long_about(r#"
+Verify a chain of block headers against the consensus rules of the network.
+
+The headers are checked for prev-hash linkage, proof of work, difficulty adjustments and
+median time past. When starting at height 0, the first header must be the genesis block.
+Checks that need headers before the first one given are skipped.
+
+The first invalid header is reported, with the cumulative work of the valid headers."#
+	)
+}
+
+/// Parse block headers given in hex per line or as concatenated raw headers.
+fn parse_headers(data: &[u8]) -> Vec<block::Header> {
+	let is_hex = data.iter().all(|b| b.is_ascii_hexdigit() || b.is_ascii_whitespace());
+	let raw = if is_hex {
+		let text = String::from_utf8(data.to_vec()).expect("checked ASCII");
+		let mut raw = Vec::new();
+		for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+			let header = hex::decode(line).need("invalid hex header");
+			if header.len() != 80 {
+				exit!("headers must be 80 bytes each, got {} bytes", header.len());
+			}
+			raw.extend(header);
+		}
+		raw
+	} else {
+		data.to_vec()
+	};
+	if raw.len() % 80 != 0 {
+		exit!("raw headers must be a multiple of 80 bytes");
+	}
+	raw.chunks(80).map(|h| deserialize(h).need("invalid block header")).collect()
+}
+
+fn exec_verify_headers<'a>(args: &clap::ArgMatches<'a>) {
+	let mut data = Vec::new();
+	if let Some(path) = args.value_of("headers") {
+		File::open(path).need("failed to open headers file")
+			.read_to_end(&mut data).need("error reading headers file");
+	} else {
+		io::stdin().read_to_end(&mut data).need("error reading stdin");
+	}
+	let headers = parse_headers(&data);
+	if headers.is_empty() {
+		exit!("no headers provided");
+	}
+	let start_height = args.value_of("start-height").need("no start height")
+		.parse::<u32>().need("invalid start height");
+
+	let info = hal::block::verify_headers(&headers, start_height, args.network());
+	args.print_output(&info)
+}
@@ -0,0 +1,205 @@
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::secp256k1::rand::{self, RngCore};
+use clap;
+use secp256k1::{self, Scalar};
+
+use hal::frost::{FrostNonceInfo, PublicNonce, SecretNonce};
+
+use crate::prelude::*;
+
+pub fn subcommand<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand_group("frost", "simulate FROST threshold signing")
+		.long_about("simulate FROST threshold signing\n\n\
+			NOTE: this is a simulation for experimenting and producing test vectors. It doesn't \
+			implement RFC 9591 and is not interoperable with other FROST implementations: the \
+			key shares, nonces and signature shares only work with hal. The aggregated \
+			signatures are regular BIP-340 signatures.")
+		.subcommand(cmd_keygen())
+		.subcommand(cmd_nonce())
+		.subcommand(cmd_sign())
+		.subcommand(cmd_aggregate())
+}
+
+pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
+	match args.subcommand() {
+		("keygen", Some(m)) => exec_keygen(m),
+		("nonce", Some(m)) => exec_nonce(m),
+		("sign", Some(m)) => exec_sign(m),
+		("aggregate", Some(m)) => exec_aggregate(m),
+		(_, _) => unreachable!("clap prints help"),
+	};
+}
+
+/// Get the seed from the seed argument or generate a random one.
+fn seed(args: &clap::ArgMatches) -> Vec<u8> {
+	if let Some(hex) = args.value_of("seed") {
+		hex::decode(hex).need("invalid seed hex")
+	} else {
+		let mut seed = vec![0u8; 32];
+		rand::thread_rng().fill_bytes(&mut seed);
+		seed
+	}
+}
+
+/// Parse the 32-byte hex message, reversing it if asked.
+fn message(args: &clap::ArgMatches) -> [u8; 32] {
+	let msg_hex = args.value_of("message").need("no message given");
+	let mut msg_bytes = hex::decode(msg_hex).need("invalid hex message");
+	if args.is_present("reverse") {
+		msg_bytes.reverse();
+	}
+	if msg_bytes.len() != 32 {
+		exit!("message must be 32 bytes");
+	}
+	let mut msg = [0u8; 32];
+	msg.copy_from_slice(&msg_bytes);
+	msg
+}
+
+/// Parse a value prefixed with the signer index as in `<index>:<value>`.
+fn indexed<'a>(s: &'a str, what: &str) -> (u32, &'a str) {
+	let mut parts = s.splitn(2, ':');
+	match (parts.next(), parts.next()) {
+		(Some(i), Some(v)) => (i.parse().need(&format!("invalid signer index in {}", what)), v),
+		_ => exit!("{} should be of the form <index>:<value>", what),
+	}
+}
+
+fn commitments(args: &clap::ArgMatches) -> Vec<(u32, PublicNonce)> {
+	args.values_of("commitment").need("no nonce commitments provided").map(|s| {
+		let (i, hex) = indexed(s, "commitment");
+		let bytes = hex::decode(hex).need("invalid commitment hex");
+		(i, PublicNonce::from_slice(&bytes).need("invalid commitment"))
+	}).collect()
+}
+
+fn cmd_keygen<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("keygen", "generate key shares for a t-of-n group")
+		.arg(args::arg("threshold", "the number of signers needed to sign").required(true))
+		.arg(args::arg("participants", "the total number of participants").required(true))
+		.arg(args::flag("dkg", "simulate a distributed key generation instead of using \
+			a trusted dealer; the full transcript is included in the output"))
+		.arg(args::opt("secret", "the group secret key to share in hex or WIF, \
+			only for the trusted dealer"))
+		.arg(args::opt("seed", "hex seed to deterministically derive all secrets from"))
+}
+
+fn exec_keygen<'a>(args: &clap::ArgMatches<'a>) {
+	let threshold = args.value_of("threshold").need("no threshold given")
+		.parse::<u32>().need("invalid threshold");
+	let participants = args.value_of("participants").need("no number of participants given")
+		.parse::<u32>().need("invalid number of participants");
+	let seed = seed(args);
+
+	let info = if args.is_present("dkg") {
+		if args.is_present("secret") {
+			exit!("can't provide a group secret for DKG");
+		}
+		hal::frost::keygen_dkg(threshold, participants, &seed)
+	} else {
+		let secret = args.privkey("secret").map(|k| k.inner);
+		hal::frost::keygen_dealer(threshold, participants, secret, &seed)
+	}.need("key generation failed");
+	args.print_output(&info)
+}
+
+fn cmd_nonce<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("nonce", "generate a nonce pair and its commitment for a signer\n\n\
+		NOTE!! Never use the same secret nonce twice. Only use --seed for testing!")
+		.arg(args::arg("share", "the signer's secret share in hex").required(true))
+		.arg(args::opt("seed", "hex seed to deterministically derive the nonces from"))
+}
+
+fn exec_nonce<'a>(args: &clap::ArgMatches<'a>) {
+	let share = args.need_privkey("share").inner;
+	let secnonce = hal::frost::nonce_gen(&share, &seed(args)).need("nonce generation failed");
+
+	let info = FrostNonceInfo {
+		secret_nonce: secnonce.serialize().to_vec().into(),
+		public_nonce: secnonce.public_nonce().serialize().to_vec().into(),
+	};
+	args.print_output(&info)
+}
+
+fn cmd_sign<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand(
+		"sign",
+		"create a signature share\n\nNOTE!! For SHA-256-d hashes, the --reverse \
+		flag must be used because Bitcoin Core reverses the hex order for those!",
+	)
+	.arg(args::arg("index", "the signer's index").required(true))
+	.arg(args::arg("share", "the signer's secret share in hex").required(true))
+	.arg(args::arg("group-key", "the group public key").required(true))
+	.arg(args::arg("message", "the message to be signed in hex (must be 32 bytes)").required(true))
+	.arg(args::opt("secnonce", "the signer's secret nonce in hex").required(true))
+	.arg(args::opt("commitment", "the nonce commitment of each signer, \
+		including our own, as <index>:<public-nonce>")
+		.multiple(true).number_of_values(1).required(true))
+	.arg(args::flag("reverse", "reverse the message"))
+}
+
+fn exec_sign<'a>(args: &clap::ArgMatches<'a>) {
+	let index = args.value_of("index").need("no index given")
+		.parse::<u32>().need("invalid signer index");
+	let share = args.need_privkey("share").inner;
+	let group_key = args.need_xonly_pubkey("group-key");
+	let msg = message(args);
+	let secnonce = {
+		let hex = args.value_of("secnonce").need("no secret nonce given");
+		let bytes = hex::decode(hex).need("invalid secret nonce hex");
+		SecretNonce::from_slice(&bytes).need("invalid secret nonce")
+	};
+	let commitments = commitments(args);
+
+	let sig_share = hal::frost::sign(index, &share, &group_key, &secnonce, &commitments, &msg)
+		.need("signing failed");
+	print!("{}", hex::encode(sig_share.to_be_bytes()));
+}
+
+fn cmd_aggregate<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand(
+		"aggregate",
+		"aggregate signature shares into a BIP-340 signature\n\nNOTE!! For SHA-256-d hashes, \
+		the --reverse flag must be used because Bitcoin Core reverses the hex order for those!",
+	)
+	.arg(args::arg("group-key", "the group public key").required(true))
+	.arg(args::arg("message", "the message to be signed in hex (must be 32 bytes)").required(true))
+	.arg(args::opt("threshold", "the number of signers needed to sign").required(true))
+	.arg(args::opt("commitment", "the nonce commitment of each signer as <index>:<public-nonce>")
+		.multiple(true).number_of_values(1).required(true))
+	.arg(args::opt("share", "the signature share of each signer as <index>:<share>")
+		.multiple(true).number_of_values(1).required(true))
+	.arg(args::opt("public-share", "the public key share of a signer as <index>:<pubkey>, \
+		used to verify its signature share")
+		.multiple(true).number_of_values(1))
+	.arg(args::flag("reverse", "reverse the message"))
+}
+
+fn exec_aggregate<'a>(args: &clap::ArgMatches<'a>) {
+	let group_key = args.need_xonly_pubkey("group-key");
+	let threshold = args.value_of("threshold").need("no threshold given")
+		.parse::<u32>().need("invalid threshold");
+	let msg = message(args);
+	let commitments = commitments(args);
+	let sig_shares = args.values_of("share").need("no signature shares given").map(|s| {
+		let (i, hex) = indexed(s, "signature share");
+		let bytes = <[u8; 32]>::from_hex(hex).need("invalid signature share hex");
+		(i, Scalar::from_be_bytes(bytes).need("invalid signature share"))
+	}).collect::<Vec<_>>();
+
+	for s in args.values_of("public-share").into_iter().flatten() {
+		let (i, pk) = indexed(s, "public share");
+		let pk = pk.parse::<secp256k1::PublicKey>().need("invalid public share");
+		let sig_share = sig_shares.iter().find(|s| s.0 == i)
+			.need(&format!("no signature share for signer {}", i)).1;
+		let valid = hal::frost::verify_share(i, &pk, &group_key, &commitments, &msg, sig_share)
+			.need("error verifying signature share");
+		if !valid {
+			exit!("signature share of signer {} is invalid", i);
+		}
+	}
+
+	let sig = hal::frost::aggregate(&group_key, threshold, &commitments, &msg, &sig_shares)
+		.need("aggregation failed");
+	print!("{:x}", &sig);
+}
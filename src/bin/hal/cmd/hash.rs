@@ -1,7 +1,10 @@
+use std::fmt;
 use std::io::Write;
 
 use bitcoin::hashes::hex::FromHex;
-use bitcoin::hashes::{sha256, sha256d, Hash};
+use bitcoin::hashes::{
+	hash160, hmac, ripemd160, sha256, sha256d, sha512, siphash24, Hash, HashEngine,
+};
 
 use crate::prelude::*;
 
@@ -9,51 +12,214 @@ pub fn subcommand<'a>() -> clap::App<'a, 'a> {
 	cmd::subcommand_group("hash", "commands to hash data")
 		.subcommand(cmd_sha256())
 		.subcommand(cmd_sha256d())
+		.subcommand(cmd_sha512())
+		.subcommand(cmd_ripemd160())
+		.subcommand(cmd_hash160())
+		.subcommand(cmd_hmac_sha256())
+		.subcommand(cmd_hmac_sha512())
+		.subcommand(cmd_siphash())
+		.subcommand(cmd_tagged())
 }
 
 pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
 	match args.subcommand() {
 		("sha256", Some(ref m)) => exec_sha256(&m),
 		("sha256d", Some(ref m)) => exec_sha256d(&m),
+		("sha512", Some(m)) => exec_sha512(m),
+		("ripemd160", Some(m)) => exec_ripemd160(m),
+		("hash160", Some(m)) => exec_hash160(m),
+		("hmac-sha256", Some(m)) => exec_hmac_sha256(m),
+		("hmac-sha512", Some(m)) => exec_hmac_sha512(m),
+		("siphash", Some(m)) => exec_siphash(m),
+		("tagged", Some(m)) => exec_tagged(m),
 		(_, _) => unreachable!("clap prints help"),
 	};
 }
 
-fn cmd_sha256<'a>() -> clap::App<'a, 'a> {
-	cmd::subcommand("sha256", "hash input with SHA-256")
-		.arg(args::arg("hex", "the input bytes in hex to hash").required(true))
-		.arg(args::flag("raw-stdout", "output the raw bytes of the result to stdout")
-			.short("r"))
+/// The arguments to provide the input and control the output of a hash command.
+fn io_args<'a>() -> Vec<clap::Arg<'a, 'a>> {
+	vec![
+		args::arg("input", "the input bytes in hex to hash; read from stdin if omitted"),
+		args::flag("utf8", "interpret the input as a UTF-8 string instead of hex")
+			.short("u"),
+		args::flag("raw-stdout", "output the raw bytes of the result to stdout")
+			.short("r"),
+	]
 }
 
-fn exec_sha256<'a>(args: &clap::ArgMatches<'a>) {
-	let hex = args.value_of("hex").need("no input bytes given");
-	let bytes = Vec::<u8>::from_hex(&hex).need("invalid hex");
+/// Get the bytes to hash from the input argument or from stdin.
+fn input_bytes<'a>(args: &clap::ArgMatches<'a>) -> Vec<u8> {
+	let input = util::arg_or_stdin(args, "input");
+	if args.is_present("utf8") {
+		input.as_bytes().to_vec()
+	} else {
+		Vec::<u8>::from_hex(&input).need("invalid hex")
+	}
+}
 
-	let ret = sha256::Hash::hash(&bytes);
+fn print_hash<'a, H: Hash + fmt::Display>(args: &clap::ArgMatches<'a>, hash: H) {
 	if args.is_present("raw-stdout") {
-		::std::io::stdout().write_all(&ret[..]).unwrap();
+		::std::io::stdout().write_all(&hash[..]).unwrap();
+	} else {
+		print!("{}", hash);
+	}
+}
+
+/// Get the HMAC or SipHash key from the key argument.
+fn key_bytes<'a>(args: &clap::ArgMatches<'a>) -> Vec<u8> {
+	let key = args.value_of("key").need("no key given");
+	if args.is_present("utf8-key") {
+		key.as_bytes().to_vec()
 	} else {
-		print!("{}", ret);
+		Vec::<u8>::from_hex(key).need("invalid key hex")
 	}
 }
 
+fn cmd_sha256<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("sha256", "hash input with SHA-256").args(&io_args())
+}
+
+fn exec_sha256<'a>(args: &clap::ArgMatches<'a>) {
+	print_hash(args, sha256::Hash::hash(&input_bytes(args)));
+}
+
 fn cmd_sha256d<'a>() -> clap::App<'a, 'a> {
-	cmd::subcommand("sha256d", "hash input with double SHA-256")
-		.arg(args::arg("hex", "the input bytes in hex to hash").required(true))
-		.arg(args::flag("raw-stdout", "output the raw bytes of the result to stdout")
-			.short("r"))
+	cmd::subcommand("sha256d", "hash input with double SHA-256").args(&io_args())
 }
 
 fn exec_sha256d<'a>(args: &clap::ArgMatches<'a>) {
-	let hex = args.value_of("hex").need("no input bytes given");
-	let bytes = Vec::<u8>::from_hex(&hex).need("invalid hex");
+	print_hash(args, sha256d::Hash::hash(&input_bytes(args)));
+}
 
-	let ret = sha256d::Hash::hash(&bytes);
-	if args.is_present("raw-stdout") {
-		::std::io::stdout().write_all(&ret[..]).unwrap();
-	} else {
-		print!("{}", ret);
-	}
+fn cmd_sha512<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("sha512", "hash input with SHA-512").args(&io_args())
+}
+
+fn exec_sha512<'a>(args: &clap::ArgMatches<'a>) {
+	print_hash(args, sha512::Hash::hash(&input_bytes(args)));
+}
+
+fn cmd_ripemd160<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("ripemd160", "hash input with RIPEMD-160").args(&io_args())
+}
+
+fn exec_ripemd160<'a>(args: &clap::ArgMatches<'a>) {
+	print_hash(args, ripemd160::Hash::hash(&input_bytes(args)));
+}
+
+fn cmd_hash160<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("hash160", "hash input with RIPEMD-160 of SHA-256").args(&io_args())
+}
+
+fn exec_hash160<'a>(args: &clap::ArgMatches<'a>) {
+	print_hash(args, hash160::Hash::hash(&input_bytes(args)));
+}
+
+fn hmac<H: Hash>(key: &[u8], data: &[u8]) -> hmac::Hmac<H> {
+	let mut engine = hmac::HmacEngine::<H>::new(key);
+	engine.input(data);
+	hmac::Hmac::from_engine(engine)
+}
+
+fn cmd_hmac_sha256<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("hmac-sha256", "calculate an HMAC-SHA256 of the input")
+		.arg(args::opt("key", "the HMAC key in hex").required(true))
+		.arg(args::flag("utf8-key", "interpret the key as a UTF-8 string instead of hex"))
+		.args(&io_args())
+}
+
+fn exec_hmac_sha256<'a>(args: &clap::ArgMatches<'a>) {
+	print_hash(args, hmac::<sha256::Hash>(&key_bytes(args), &input_bytes(args)));
+}
+
+fn cmd_hmac_sha512<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("hmac-sha512", "calculate an HMAC-SHA512 of the input")
+		.arg(args::opt("key", "the HMAC key in hex").required(true))
+		.arg(args::flag("utf8-key", "interpret the key as a UTF-8 string instead of hex"))
+		.args(&io_args())
+}
+
+fn exec_hmac_sha512<'a>(args: &clap::ArgMatches<'a>) {
+	print_hash(args, hmac::<sha512::Hash>(&key_bytes(args), &input_bytes(args)));
+}
+
+fn cmd_siphash<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("siphash", "hash input with SipHash-2-4\n\n\
+		The output is the little-endian encoding of the 64-bit result.")
+		.arg(args::opt("key", "the 16-byte key in hex").required(true))
+		.args(&io_args())
+}
+
+/// SipHash-2-4 with the 16-byte key split into two little-endian halves.
+fn siphash(key: &[u8; 16], data: &[u8]) -> siphash24::Hash {
+	let mut k0 = [0u8; 8];
+	let mut k1 = [0u8; 8];
+	k0.copy_from_slice(&key[0..8]);
+	k1.copy_from_slice(&key[8..16]);
+	siphash24::Hash::hash_with_keys(u64::from_le_bytes(k0), u64::from_le_bytes(k1), data)
+}
+
+fn exec_siphash<'a>(args: &clap::ArgMatches<'a>) {
+	let key = <[u8; 16]>::from_hex(args.value_of("key").need("no key given"))
+		.need("invalid key: must be 16 bytes in hex");
+	print_hash(args, siphash(&key, &input_bytes(args)));
+}
+
+fn cmd_tagged<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("tagged", "hash input with a BIP-340 tagged hash\n\n\
+		Any tag can be used, for example: TapLeaf, TapBranch, TapTweak, TapSighash, \
+		BIP0340/challenge, BIP0340/aux or BIP0340/nonce.")
+		.arg(args::arg("tag", "the tag as a UTF-8 string").required(true))
+		.args(&io_args())
+}
+
+/// The BIP-340 tagged hash SHA-256(SHA-256(tag) || SHA-256(tag) || data).
+fn tagged_hash(tag: &str, data: &[u8]) -> sha256::Hash {
+	let tag_hash = sha256::Hash::hash(tag.as_bytes());
+	let mut engine = sha256::Hash::engine();
+	engine.input(tag_hash.as_ref());
+	engine.input(tag_hash.as_ref());
+	engine.input(data);
+	sha256::Hash::from_engine(engine)
 }
 
+fn exec_tagged<'a>(args: &clap::ArgMatches<'a>) {
+	let tag = args.value_of("tag").need("no tag given");
+	print_hash(args, tagged_hash(tag, &input_bytes(args)));
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_known_answers() {
+		// RFC 4231 test case 2.
+		let (key, data) = (b"Jefe", b"what do ya want for nothing?");
+		assert_eq!(
+			hmac::<sha256::Hash>(key, data).to_string(),
+			"5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+		);
+		assert_eq!(
+			hmac::<sha512::Hash>(key, data).to_string(),
+			"164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+			9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+		);
+
+		// The SipHash-2-4 reference vector for a 15-byte input, 0xa129ca6149be45e5.
+		let mut key = [0u8; 16];
+		key.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+		let data = (0..15).collect::<Vec<u8>>();
+		assert_eq!(siphash(&key, &data).as_u64(), 0xa129ca6149be45e5);
+		assert_eq!(siphash(&key, &data).to_string(), "e545be4961ca29a1");
+
+		// The BIP-341 tweak of the internal key of the first wallet test vector.
+		let internal_key = Vec::<u8>::from_hex(
+			"d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d",
+		).unwrap();
+		assert_eq!(
+			tagged_hash("TapTweak", &internal_key).to_string(),
+			"b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70",
+		);
+	}
+}
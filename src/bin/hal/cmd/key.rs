@@ -1,11 +1,15 @@
+use std::io::Write;
 use std::process;
+use std::str::FromStr;
 
 use bitcoin::secp256k1;
 use bitcoin::secp256k1::rand;
 use bitcoin::hashes::hex::FromHex;
+use bitcoin::taproot::TapNodeHash;
 use clap;
 
 use hal::{self, GetInfo};
+use hal::adaptor::{AdaptorSignature, EcdsaAdaptorSignature, SchnorrAdaptorSignature};
 
 use crate::prelude::*;
 
@@ -15,6 +19,8 @@ pub fn subcommand<'a>() -> clap::App<'a, 'a> {
 		.subcommand(cmd_generate())
 		.subcommand(cmd_derive())
 		.subcommand(cmd_inspect())
+		.subcommand(cmd_encrypt())
+		.subcommand(cmd_decrypt())
 		.subcommand(cmd_ecdsa_sign())
 		.subcommand(cmd_ecdsa_verify())
 		.subcommand(cmd_schnorr_sign())
@@ -22,6 +28,13 @@ pub fn subcommand<'a>() -> clap::App<'a, 'a> {
 		.subcommand(cmd_negate_pubkey())
 		.subcommand(cmd_pubkey_tweak_add())
 		.subcommand(cmd_pubkey_combine())
+		.subcommand(cmd_ecdh())
+		.subcommand(cmd_taproot_tweak())
+		.subcommand(cmd_taproot_verify())
+		.subcommand(cmd_adaptor_sign())
+		.subcommand(cmd_adaptor_verify())
+		.subcommand(cmd_adaptor_complete())
+		.subcommand(cmd_adaptor_extract())
 }
 
 pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
@@ -29,6 +42,8 @@ pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
 		("generate", Some(ref m)) => exec_generate(&m),
 		("derive", Some(ref m)) => exec_derive(&m),
 		("inspect", Some(ref m)) => exec_inspect(&m),
+		("encrypt", Some(m)) => exec_encrypt(m),
+		("decrypt", Some(m)) => exec_decrypt(m),
 		("ecdsa-sign", Some(ref m)) => exec_ecdsa_sign(&m),
 		("ecdsa-verify", Some(ref m)) => exec_ecdsa_verify(&m),
 		("schnorr-sign", Some(ref m)) => exec_schnorr_sign(&m),
@@ -38,6 +53,13 @@ pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
 		("negate-pubkey", Some(ref m)) => exec_negate_pubkey(&m),
 		("pubkey-tweak-add", Some(ref m)) => exec_pubkey_tweak_add(&m),
 		("pubkey-combine", Some(ref m)) => exec_pubkey_combine(&m),
+		("ecdh", Some(m)) => exec_ecdh(m),
+		("taproot-tweak", Some(m)) => exec_taproot_tweak(m),
+		("taproot-verify", Some(m)) => exec_taproot_verify(m),
+		("adaptor-sign", Some(m)) => exec_adaptor_sign(m),
+		("adaptor-verify", Some(m)) => exec_adaptor_verify(m),
+		("adaptor-complete", Some(m)) => exec_adaptor_complete(m),
+		("adaptor-extract", Some(m)) => exec_adaptor_extract(m),
 		(_, _) => unreachable!("clap prints help"),
 	};
 }
@@ -45,13 +67,17 @@ pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
 fn cmd_generate<'a>() -> clap::App<'a, 'a> {
 	cmd::subcommand("generate", "generate a new ECDSA keypair")
 		.unset_setting(clap::AppSettings::ArgRequiredElseHelp)
+		.args(&args::opts_physical_entropy())
+		.args(&args::opts_encrypt())
 }
 
 fn exec_generate<'a>(args: &clap::ArgMatches<'a>) {
 	let network = args.network();
 
-	let entropy: [u8; 32] = rand::random();
-	let secret_key = secp256k1::SecretKey::from_slice(&entropy[..]).unwrap();
+	let entropy = util::physical_entropy(args, 32).unwrap_or_else(|| {
+		rand::random::<[u8; 32]>().to_vec()
+	});
+	let secret_key = secp256k1::SecretKey::from_slice(&entropy[..]).need("invalid private key");
 	let privkey = bitcoin::PrivateKey {
 		compressed: true,
 		network: network.into(),
@@ -59,7 +85,7 @@ fn exec_generate<'a>(args: &clap::ArgMatches<'a>) {
 	};
 
 	let info = privkey.get_info(network);
-	args.print_output(&info)
+	args.print_secret_output(&info)
 }
 
 fn cmd_derive<'a>() -> clap::App<'a, 'a> {
@@ -85,6 +111,84 @@ fn exec_inspect<'a>(args: &clap::ArgMatches<'a>) {
 	args.print_output(&info)
 }
 
+fn cmd_encrypt<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand(
+		"encrypt",
+		"encrypt a private key with a passphrase using BIP-38\n\n\
+		With --ec-multiply, a new key is generated for which the private key is never shown. \
+		With --intermediate-code, a new key is generated for the owner of the intermediate \
+		code, without knowing the passphrase or the private key.",
+	)
+	.unset_setting(clap::AppSettings::ArgRequiredElseHelp)
+	.arg(args::arg("privkey", "the private key to encrypt"))
+	.args(&args::opts_passphrase())
+	.arg(args::flag("ec-multiply", "generate a new key using EC multiplication"))
+	.arg(args::opt("lot", "the lot number for EC multiplication"))
+	.arg(args::opt("sequence", "the sequence number for EC multiplication"))
+	.arg(args::opt("intermediate-code", "generate a new key from an intermediate code"))
+	.arg(args::flag("uncompressed", "use an uncompressed public key for a new key"))
+}
+
+fn exec_encrypt<'a>(args: &clap::ArgMatches<'a>) {
+	let network = args.network().into();
+	let compressed = !args.is_present("uncompressed");
+
+	let info = if let Some(code) = args.value_of("intermediate-code") {
+		if args.is_present("privkey") || args.is_present("passphrase-file")
+			|| args.is_present("insecure-passphrase")
+		{
+			exit!("an intermediate code can't be combined with a private key or a passphrase");
+		}
+		hal::bip38::encrypt_ec_multiply(code, &rand::random(), compressed, network)
+			.need("invalid intermediate code")
+	} else if args.is_present("ec-multiply") {
+		if args.is_present("privkey") {
+			exit!("EC multiplication generates a new key, it can't encrypt a given private key");
+		}
+		let passphrase = util::passphrase(args, "passphrase-file", "insecure-passphrase", true);
+		let lot_sequence = match (args.value_of("lot"), args.value_of("sequence")) {
+			(Some(lot), Some(sequence)) => Some((
+				lot.parse::<u32>().need("invalid lot number"),
+				sequence.parse::<u32>().need("invalid sequence number"),
+			)),
+			(None, None) => None,
+			_ => exit!("both a lot and a sequence number should be given"),
+		};
+		let code = hal::bip38::intermediate_code(&passphrase, &rand::random(), lot_sequence)
+			.need("failed to create intermediate code");
+		hal::bip38::encrypt_ec_multiply(&code, &rand::random(), compressed, network)
+			.need("failed to encrypt key")
+	} else {
+		let privkey = args.need_privkey("privkey");
+		let passphrase = util::passphrase(args, "passphrase-file", "insecure-passphrase", true);
+		hal::bip38::encrypt(&privkey, &passphrase)
+	};
+	args.print_output(&info)
+}
+
+fn cmd_decrypt<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand(
+		"decrypt",
+		"decrypt a BIP-38 private key or secret output that was encrypted with --encrypt",
+	)
+	.arg(args::arg("encrypted", "the encrypted key or output, read from stdin if omitted"))
+	.args(&args::opts_passphrase())
+}
+
+fn exec_decrypt<'a>(args: &clap::ArgMatches<'a>) {
+	let encrypted = util::arg_or_stdin(args, "encrypted");
+	let passphrase = util::passphrase(args, "passphrase-file", "insecure-passphrase", false);
+
+	if hal::age::is_armored(&encrypted) {
+		let data = hal::age::decrypt(&encrypted, &passphrase).need("failed to decrypt output");
+		::std::io::stdout().write_all(&data).unwrap();
+	} else {
+		let privkey = hal::bip38::decrypt(encrypted.trim(), &passphrase)
+			.need("failed to decrypt private key");
+		args.print_output(&privkey.get_info(args.network()))
+	}
+}
+
 fn cmd_ecdsa_sign<'a>() -> clap::App<'a, 'a> {
 	cmd::subcommand(
 		"ecdsa-sign",
@@ -305,3 +409,220 @@ fn exec_pubkey_combine<'a>(args: &clap::ArgMatches<'a>) {
 	}
 }
 
+
+fn cmd_ecdh<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("ecdh", "compute an ECDH shared secret between a private and a public key")
+		.arg(args::arg("privkey", "the private key in hex or WIF").required(true))
+		.arg(args::arg("pubkey", "the public key in hex").required(true))
+}
+
+fn exec_ecdh<'a>(args: &clap::ArgMatches<'a>) {
+	let privkey = args.need_privkey("privkey");
+	let pubkey = args.need_pubkey("pubkey");
+
+	let info = hal::key::EcdhInfo::new(&privkey.inner, &pubkey.inner);
+	args.print_output(&info)
+}
+
+/// Parse the optional taproot merkle root argument.
+fn merkle_root<'a>(args: &clap::ArgMatches<'a>) -> Option<TapNodeHash> {
+	args.value_of("merkle-root").map(|s| {
+		TapNodeHash::from_str(s).need("invalid merkle root")
+	})
+}
+
+fn cmd_taproot_tweak<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("taproot-tweak", "tweak an internal key into a BIP-341 taproot output key")
+		.arg(args::arg("internal-key", "the internal public key; \
+			can be omitted when --privkey is given"))
+		.arg(args::opt("privkey", "the internal private key in hex or WIF, \
+			to also calculate the tweaked private key"))
+		.arg(args::opt("merkle-root", "the merkle root of the script tree in hex; \
+			omit for key-path-only outputs"))
+}
+
+fn exec_taproot_tweak<'a>(args: &clap::ArgMatches<'a>) {
+	let privkey = args.privkey("privkey");
+	let internal_key = match (args.flexible_pubkey("internal-key"), privkey) {
+		(Some(FlexiblePubkey::XOnly(pk)), _) => pk,
+		(Some(FlexiblePubkey::Regular(pk)), _) => pk.inner.x_only_public_key().0,
+		(None, Some(sk)) => sk.inner.x_only_public_key(&SECP).0,
+		(None, None) => exit!("need either an internal key or --privkey"),
+	};
+	if let Some(sk) = privkey {
+		if sk.inner.x_only_public_key(&SECP).0 != internal_key {
+			exit!("private key doesn't match the internal key");
+		}
+	}
+
+	let info = hal::key::TaprootTweakInfo::new(
+		internal_key, merkle_root(args), privkey.as_ref().map(|k| &k.inner), args.network(),
+	);
+	args.print_output(&info)
+}
+
+fn cmd_taproot_verify<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("taproot-verify", "verify that a taproot output key commits to \
+		the given internal key and merkle root")
+		.arg(args::arg("output-key", "the taproot output key").required(true))
+		.arg(args::arg("internal-key", "the internal public key").required(true))
+		.arg(args::opt("merkle-root", "the merkle root of the script tree in hex; \
+			omit for key-path-only outputs"))
+}
+
+fn exec_taproot_verify<'a>(args: &clap::ArgMatches<'a>) {
+	let internal_key = args.need_xonly_pubkey("internal-key");
+
+	// If the full output key is given, also check its parity.
+	let (output_key, parity) = match args.flexible_pubkey("output-key").need("no output key provided") {
+		FlexiblePubkey::XOnly(pk) => (pk, None),
+		FlexiblePubkey::Regular(pk) => {
+			let (pk, parity) = pk.inner.x_only_public_key();
+			(pk, Some(parity))
+		}
+	};
+	let valid = hal::key::verify_taproot_commitment(internal_key, merkle_root(args), output_key, parity);
+
+	if valid {
+		println!("Output key commits to the internal key and merkle root.");
+	} else {
+		eprintln!("Output key does NOT commit to the internal key and merkle root!");
+		process::exit(1);
+	}
+}
+
+/// Parse the 32-byte message argument, reversing it if asked.
+fn adaptor_message<'a>(args: &clap::ArgMatches<'a>) -> [u8; 32] {
+	let msg_hex = args.value_of("message").need("no message given");
+	let mut msg = <[u8; 32]>::from_hex(msg_hex).need("invalid message: must be 32 bytes in hex");
+	if args.is_present("reverse") {
+		msg.reverse();
+	}
+	msg
+}
+
+fn adaptor_signature<'a>(args: &clap::ArgMatches<'a>) -> AdaptorSignature {
+	let hex = args.value_of("adaptor-signature").need("no adaptor signature provided");
+	let bytes = hex::decode(hex).need("invalid adaptor signature: not hex");
+	AdaptorSignature::from_slice(&bytes).need("invalid adaptor signature")
+}
+
+fn cmd_adaptor_sign<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand(
+		"adaptor-sign",
+		"create an adaptor signature encrypted with an adaptor point\n\n\
+		Creates a Schnorr adaptor signature by default, use --ecdsa for ECDSA.\n\n\
+		WARNING: adaptor signatures use a hal-only format, prefixed with \"hal\" in hex. \
+		They are NOT compatible with secp256k1-zkp or any other implementation.\n\n\
+		NOTE!! For SHA-256-d hashes, the --reverse \
+		flag must be used because Bitcoin Core reverses the hex order for those!",
+	)
+	.arg(args::arg("privkey", "the private key in hex or WIF").required(true))
+	.arg(args::arg("adaptor-point", "the adaptor point T in hex").required(true))
+	.arg(args::arg("message", "the message to be signed in hex (must be 32 bytes)").required(true))
+	.arg(args::flag("ecdsa", "create an ECDSA adaptor signature"))
+	.arg(args::flag("reverse", "reverse the message"))
+}
+
+fn exec_adaptor_sign<'a>(args: &clap::ArgMatches<'a>) {
+	let privkey = args.need_privkey("privkey");
+	let adaptor = args.need_pubkey("adaptor-point");
+	let msg = adaptor_message(args);
+	let mut aux = [0u8; 32];
+	rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut aux);
+
+	let sig = if args.is_present("ecdsa") {
+		AdaptorSignature::Ecdsa(EcdsaAdaptorSignature::sign(&privkey.inner, &adaptor.inner, &msg, &aux)
+			.need("failed to create adaptor signature"))
+	} else {
+		AdaptorSignature::Schnorr(SchnorrAdaptorSignature::sign(&privkey.inner, &adaptor.inner, &msg, &aux)
+			.need("failed to create adaptor signature"))
+	};
+	print!("{}", hex::encode(sig.serialize()));
+}
+
+fn cmd_adaptor_verify<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand(
+		"adaptor-verify",
+		"verify Schnorr or ECDSA adaptor signatures\n\n\
+		Only adaptor signatures in hal's own format, created by adaptor-sign, are supported.\n\n\
+		NOTE!! For SHA-256-d hashes, the --reverse \
+		flag must be used because Bitcoin Core reverses the hex order for those!",
+	)
+	.arg(args::arg("message", "the message to be signed in hex (must be 32 bytes)").required(true))
+	.arg(args::arg("pubkey", "the public key in hex").required(true))
+	.arg(args::arg("adaptor-point", "the adaptor point T in hex").required(true))
+	.arg(args::arg("adaptor-signature", "the adaptor signature in hex").required(true))
+	.arg(args::flag("reverse", "reverse the message"))
+}
+
+fn exec_adaptor_verify<'a>(args: &clap::ArgMatches<'a>) {
+	let msg = adaptor_message(args);
+	let adaptor = args.need_pubkey("adaptor-point");
+
+	let valid = match adaptor_signature(args) {
+		AdaptorSignature::Schnorr(sig) => {
+			sig.verify(&args.need_xonly_pubkey("pubkey"), &adaptor.inner, &msg)
+		}
+		AdaptorSignature::Ecdsa(sig) => {
+			sig.verify(&args.need_pubkey("pubkey").inner, &adaptor.inner, &msg)
+		}
+	};
+
+	if valid {
+		println!("Adaptor signature is valid.");
+	} else {
+		eprintln!("Adaptor signature is invalid!");
+		process::exit(1);
+	}
+}
+
+fn cmd_adaptor_complete<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("adaptor-complete", "complete an adaptor signature using the adaptor secret")
+		.arg(args::arg("adaptor-signature", "the adaptor signature in hex").required(true))
+		.arg(args::arg("secret", "the adaptor secret t in hex or WIF").required(true))
+}
+
+fn exec_adaptor_complete<'a>(args: &clap::ArgMatches<'a>) {
+	let secret = args.need_privkey("secret");
+
+	match adaptor_signature(args) {
+		AdaptorSignature::Schnorr(sig) => print!("{:x}", &sig.complete(&secret.inner)),
+		AdaptorSignature::Ecdsa(sig) => {
+			args.print_output(&sig.complete(&secret.inner).get_info(args.network()))
+		}
+	}
+}
+
+fn cmd_adaptor_extract<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("adaptor-extract", "extract the adaptor secret from an adaptor signature \
+		and the completed signature")
+		.arg(args::arg("adaptor-signature", "the adaptor signature in hex").required(true))
+		.arg(args::arg("signature", "the completed Schnorr or ECDSA signature in hex").required(true))
+		.arg(args::arg("adaptor-point", "the adaptor point T in hex").required(true))
+}
+
+fn exec_adaptor_extract<'a>(args: &clap::ArgMatches<'a>) {
+	let adaptor = args.need_pubkey("adaptor-point");
+	let sig_bytes = {
+		let hex = args.value_of("signature").need("no signature provided");
+		hex::decode(hex).need("invalid signature: not hex")
+	};
+
+	let secret = match adaptor_signature(args) {
+		AdaptorSignature::Schnorr(pre) => {
+			let sig = secp256k1::schnorr::Signature::from_slice(&sig_bytes)
+				.need("invalid Schnorr signature");
+			pre.extract(&sig, &adaptor.inner)
+		}
+		AdaptorSignature::Ecdsa(pre) => {
+			let sig = if sig_bytes.len() == 64 {
+				secp256k1::ecdsa::Signature::from_compact(&sig_bytes).need("invalid signature")
+			} else {
+				secp256k1::ecdsa::Signature::from_der(&sig_bytes).need("invalid DER signature")
+			};
+			pre.extract(&sig, &adaptor.inner)
+		}
+	}.need("failed to extract the adaptor secret");
+	print!("{}", hex::encode(secret.secret_bytes()));
+}
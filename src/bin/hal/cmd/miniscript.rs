@@ -2,12 +2,21 @@
 use bitcoin::hex::DisplayHex;
 use bitcoin::ScriptBuf;
 use clap;
+use bitcoin::bip32::ChildNumber;
 use hal::miniscript::{
-	DescriptorInfo, MiniscriptInfo, MiniscriptKeyType, Miniscripts, PolicyInfo, ScriptContexts,
+	DerivedDescriptorInfo, DerivedKeyInfo, DescriptorInfo, MiniscriptInfo, MiniscriptKeyType,
+	Miniscripts, PolicyInfo, ScriptContexts,
+};
+use miniscript::descriptor::checksum::desc_checksum;
+use miniscript::descriptor::{
+	DerivPaths, DescriptorMultiXKey, DescriptorPublicKey, DescriptorSecretKey, KeyMap, Wildcard,
 };
 use miniscript::miniscript::{BareCtx, Legacy, Miniscript, Segwitv0};
 use miniscript::policy::Liftable;
-use miniscript::{policy, Descriptor, FromStrKey, MiniscriptKey};
+use miniscript::{
+	policy, Descriptor, ForEachKey, FromStrKey, MiniscriptKey, TranslateErr,
+	TranslatePk, Translator,
+};
 
 use crate::prelude::*;
 
@@ -15,6 +24,7 @@ pub fn subcommand<'a>() -> clap::App<'a, 'a> {
 	cmd::subcommand_group("miniscript", "work with miniscript (alias: ms)")
 		.alias("ms")
 		.subcommand(cmd_descriptor())
+		.subcommand(cmd_normalize())
 		.subcommand(cmd_inspect())
 		.subcommand(cmd_parse())
 		.subcommand(cmd_policy())
@@ -24,6 +34,7 @@ pub fn subcommand<'a>() -> clap::App<'a, 'a> {
 pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
 	match args.subcommand() {
 		("descriptor", Some(ref m)) => exec_descriptor(&m),
+		("normalize", Some(m)) => exec_normalize(m),
 		("inspect", Some(ref m)) => exec_inspect(&m),
 		("parse", Some(ref m)) => exec_parse(&m),
 		("policy", Some(ref m)) => exec_policy(&m),
@@ -33,31 +44,273 @@ pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
 }
 
 fn cmd_descriptor<'a>() -> clap::App<'a, 'a> {
-	cmd::subcommand("descriptor", "get information about an output descriptor")
-		.args(&[args::arg("descriptor", "the output descriptor to inspect").required(false)])
+	cmd::subcommand("descriptor", "get information about an output descriptor\n\n\
+		Descriptors with extended keys are derived at each index of the range, \
+		multipath descriptors like <0;1> are expanded into each of their paths.")
+		.args(&[
+			args::arg("descriptor", "the output descriptor to inspect").required(false),
+			args::opt("range", "the range of indices to derive as <start>..<end> (exclusive), \
+				<start>..=<end> (inclusive) or a single index; defaults to index 0"),
+			args::flag("private", "include the derived private keys, \
+				the descriptor must contain private keys"),
+		])
+}
+
+/// Check the checksum of the descriptor if it has one and return the descriptor
+/// without the checksum.
+fn check_checksum(desc_str: &str) -> Result<&str, String> {
+	let (desc, checksum) = match desc_str.rsplit_once('#') {
+		Some((desc, checksum)) => (desc, checksum),
+		None => return Ok(desc_str),
+	};
+	let expected = desc_checksum(desc).map_err(|e| format!("invalid descriptor: {}", e))?;
+	if checksum != expected {
+		return Err(format!("invalid descriptor checksum: expected {}, got {}", expected, checksum));
+	}
+	Ok(desc)
+}
+
+/// Verify the checksum of the descriptor if it has one and return the descriptor
+/// without the checksum.
+fn verify_checksum(desc_str: &str) -> &str {
+	check_checksum(desc_str).unwrap_or_else(|e| exit!("{}", e))
+}
+
+/// Get the checksum of a descriptor as it's displayed, which includes the checksum.
+fn descriptor_checksum(desc: &str) -> String {
+	match desc.rsplit_once('#') {
+		Some((_, checksum)) => checksum.to_owned(),
+		None => desc_checksum(desc).need("invalid descriptor"),
+	}
+}
+
+/// Derive the private key for a descriptor secret key at the given index.
+fn derive_secret_key(
+	key: &DescriptorSecretKey,
+	index: u32,
+	network: bitcoin::Network,
+) -> Option<bitcoin::PrivateKey> {
+	match key {
+		DescriptorSecretKey::Single(ref single) => Some(single.key),
+		DescriptorSecretKey::XPrv(ref xprv) => {
+			let path = match xprv.wildcard {
+				Wildcard::None => xprv.derivation_path.clone(),
+				Wildcard::Unhardened => xprv.derivation_path.child(ChildNumber::Normal { index }),
+				Wildcard::Hardened => xprv.derivation_path.child(ChildNumber::Hardened { index }),
+			};
+			let derived = xprv.xkey.derive_priv(&SECP, &path).ok()?;
+			Some(bitcoin::PrivateKey::new(derived.private_key, network))
+		}
+		// Multipath keys are split into a key per path by parse_descriptor_keys.
+		DescriptorSecretKey::MultiXPrv(_) => None,
+	}
+}
+
+/// Combine the public keys of the paths of a multipath private key into a multipath key.
+fn combine_multipath_keys(keys: Vec<DescriptorPublicKey>) -> Result<DescriptorPublicKey, String> {
+	let xpubs = keys.into_iter().map(|k| match k {
+		DescriptorPublicKey::XPub(xpub) => xpub,
+		_ => unreachable!("single path of a multipath key"),
+	}).collect::<Vec<_>>();
+	let first = &xpubs[0];
+	if xpubs.iter().any(|x| x.origin != first.origin || x.xkey != first.xkey) {
+		return Err("multipath private keys can't have hardened steps in the multipath step".into());
+	}
+	Ok(DescriptorPublicKey::MultiXPub(DescriptorMultiXKey {
+		origin: first.origin.clone(),
+		xkey: first.xkey,
+		derivation_paths: DerivPaths::new(xpubs.iter().map(|x| x.derivation_path.clone()).collect())
+			.expect("at least one path"),
+		wildcard: first.wildcard,
+	}))
+}
+
+/// Replaces multipath private keys by their multipath public key and keeps the private key
+/// of each of their paths.
+struct MultiXPrvTranslator {
+	path_keys: Vec<KeyMap>,
+}
+
+impl Translator<String, String, String> for MultiXPrvTranslator {
+	fn pk(&mut self, pk: &String) -> Result<String, String> {
+		let key = match pk.parse::<DescriptorSecretKey>() {
+			Ok(key @ DescriptorSecretKey::MultiXPrv(_)) => key,
+			_ => return Ok(pk.clone()),
+		};
+		let mut public_keys = Vec::new();
+		for (i, single) in key.into_single_keys().into_iter().enumerate() {
+			let public = single.to_public(&SECP).map_err(|e| e.to_string())?;
+			public_keys.push(public.clone());
+			if self.path_keys.len() <= i {
+				self.path_keys.push(KeyMap::new());
+			}
+			self.path_keys[i].insert(public, single);
+		}
+		Ok(combine_multipath_keys(public_keys)?.to_string())
+	}
+
+	fn sha256(&mut self, hash: &String) -> Result<String, String> {
+		Ok(hash.clone())
+	}
+
+	fn hash256(&mut self, hash: &String) -> Result<String, String> {
+		Ok(hash.clone())
+	}
+
+	fn ripemd160(&mut self, hash: &String) -> Result<String, String> {
+		Ok(hash.clone())
+	}
+
+	fn hash160(&mut self, hash: &String) -> Result<String, String> {
+		Ok(hash.clone())
+	}
+}
+
+/// Parse a descriptor with descriptor keys and get the private keys for each of the
+/// single-path descriptors it expands into.
+///
+/// Miniscript can't parse multipath private keys, so they are replaced by their public
+/// keys before parsing and added to the key map of each path separately.
+fn parse_descriptor_keys(
+	desc_str: &str,
+) -> Result<(Descriptor<DescriptorPublicKey>, Vec<KeyMap>), String> {
+	let mut translator = MultiXPrvTranslator { path_keys: Vec::new() };
+	let desc_str = match desc_str.parse::<Descriptor<String>>() {
+		Ok(desc) => desc.translate_pk(&mut translator).map_err(|e| match e {
+			TranslateErr::TranslatorErr(e) => e,
+			TranslateErr::OuterError(e) => e.to_string(),
+		})?.to_string(),
+		// Let miniscript report the error.
+		Err(_) => desc_str.to_owned(),
+	};
+
+	let (desc, keymap) = Descriptor::parse_descriptor(&SECP, &desc_str).map_err(|e| e.to_string())?;
+	let nb_paths = desc.clone().into_single_descriptors().map_err(|e| e.to_string())?.len();
+	let keymaps = (0..nb_paths).map(|i| {
+		let mut keymap = keymap.clone();
+		keymap.extend(translator.path_keys.get(i).cloned().unwrap_or_default());
+		keymap
+	}).collect();
+	Ok((desc, keymaps))
+}
+
+/// Derive a descriptor with extended keys at each index of the range.
+fn derive_descriptor(
+	desc: &Descriptor<DescriptorPublicKey>,
+	keymaps: Option<&[KeyMap]>,
+	(start, end): (u32, u32),
+	network: bitcoin::Network,
+) -> Vec<DerivedDescriptorInfo> {
+	let singles = desc.clone().into_single_descriptors().need("invalid multipath descriptor");
+	let multipath = desc.is_multipath();
+	// Without a wildcard, all indices derive the same descriptor.
+	let end = if desc.has_wildcard() { end } else { start + 1 };
+
+	let mut ret = Vec::new();
+	for (branch, single) in singles.iter().enumerate() {
+		let mut single_keys = Vec::new();
+		single.for_each_key(|k| {
+			single_keys.push(k.clone());
+			true
+		});
+		for index in start..end {
+			let derived = single.at_derivation_index(index).need("failed to derive descriptor")
+				.derived_descriptor(&SECP).need("failed to derive descriptor");
+			let keys = single_keys.iter().map(|key| {
+				let definite = key.clone().at_derivation_index(index)
+					.need("failed to derive descriptor key");
+				// Descriptors can mix private and public keys.
+				let private_key = keymaps.and_then(|keymaps| keymaps[branch].get(key)).map(|secret| {
+					derive_secret_key(secret, index, network).need("failed to derive private key")
+				});
+				DerivedKeyInfo {
+					public_key: definite.derive_public_key(&SECP)
+						.need("failed to derive descriptor key"),
+					master_fingerprint: definite.master_fingerprint(),
+					derivation_path: definite.full_derivation_path(),
+					private_key,
+				}
+			}).collect();
+			ret.push(DerivedDescriptorInfo {
+				index,
+				multipath_index: if multipath { Some(branch) } else { None },
+				descriptor: derived.to_string(),
+				address: derived.address(network).map(|a| a.to_string()).ok(),
+				script_pubkey: derived.script_pubkey().into_bytes().into(),
+				keys,
+			});
+		}
+	}
+	ret
 }
 
 fn exec_descriptor<'a>(args: &clap::ArgMatches<'a>) {
 	let desc_str = util::arg_or_stdin(args, "descriptor");
+	let desc_str = verify_checksum(&desc_str);
 	let network = args.network();
+	let range = util::derivation_range(args);
 
 	let info = desc_str
 		.parse::<Descriptor<bitcoin::PublicKey>>()
-		.map(|desc| DescriptorInfo {
-			descriptor: desc.to_string(),
-			key_type: MiniscriptKeyType::PublicKey,
-			address: desc.address(network).map(|a| a.to_string()).ok(),
-			script_pubkey: Some(desc.script_pubkey().into_bytes().into()),
-			unsigned_script_sig: Some(desc.unsigned_script_sig().into_bytes().into()),
-			witness_script: desc.explicit_script().map(|s| s.into_bytes().into()).ok(),
-			max_satisfaction_weight: desc.max_weight_to_satisfy().ok().map(|w| w.to_wu()),
-			policy: policy::Liftable::lift(&desc).map(|pol| pol.to_string()).ok(),
+		.map(|desc| {
+			if args.is_present("private") {
+				exit!("the descriptor doesn't contain private keys");
+			}
+			DescriptorInfo {
+				descriptor: desc.to_string(),
+				checksum: descriptor_checksum(&desc.to_string()),
+				key_type: MiniscriptKeyType::PublicKey,
+				address: desc.address(network).map(|a| a.to_string()).ok(),
+				script_pubkey: Some(desc.script_pubkey().into_bytes().into()),
+				unsigned_script_sig: Some(desc.unsigned_script_sig().into_bytes().into()),
+				witness_script: desc.explicit_script().map(|s| s.into_bytes().into()).ok(),
+				max_satisfaction_weight: desc.max_weight_to_satisfy().ok().map(|w| w.to_wu()),
+				policy: policy::Liftable::lift(&desc).map(|pol| pol.to_string()).ok(),
+				derived: None,
+			}
 		})
 		.or_else(|e| {
 			debug!("Can't parse descriptor with public keys: {}", e);
+			// Then try with descriptor keys, which can be extended and private keys.
+			parse_descriptor_keys(desc_str).map(|(desc, keymaps)| {
+				let keymaps = if args.is_present("private") {
+					if keymaps.iter().all(|k| k.is_empty()) {
+						exit!("the descriptor doesn't contain private keys");
+					}
+					Some(keymaps)
+				} else {
+					None
+				};
+				let derived = derive_descriptor(&desc, keymaps.as_deref(), range, network);
+				// Only show the top-level script for descriptors that are not ranged.
+				let single = if desc.has_wildcard() || desc.is_multipath() {
+					None
+				} else {
+					derived.first()
+				};
+				DescriptorInfo {
+					descriptor: desc.to_string(),
+					checksum: descriptor_checksum(&desc.to_string()),
+					key_type: MiniscriptKeyType::DescriptorPublicKey,
+					address: single.and_then(|d| d.address.clone()),
+					script_pubkey: single.map(|d| d.script_pubkey.clone()),
+					unsigned_script_sig: None,
+					witness_script: None,
+					max_satisfaction_weight: desc.max_weight_to_satisfy().ok().map(|w| w.to_wu()),
+					policy: policy::Liftable::lift(&desc).map(|pol| pol.to_string()).ok(),
+					derived: Some(derived),
+				}
+			})
+		})
+		.or_else(|e| {
+			debug!("Can't parse descriptor with descriptor keys: {}", e);
+			if args.is_present("private") {
+				exit!("invalid descriptor: {}", e);
+			}
 			// Then try with strings.
 			desc_str.parse::<Descriptor<String>>().map(|desc| DescriptorInfo {
 				descriptor: desc.to_string(),
+				checksum: descriptor_checksum(&desc.to_string()),
 				key_type: MiniscriptKeyType::String,
 				address: None,
 				script_pubkey: None,
@@ -65,12 +318,60 @@ fn exec_descriptor<'a>(args: &clap::ArgMatches<'a>) {
 				witness_script: None,
 				max_satisfaction_weight: desc.max_weight_to_satisfy().ok().map(|w| w.to_wu()),
 				policy: policy::Liftable::lift(&desc).map(|pol| pol.to_string()).ok(),
+				derived: None,
 			})
 		})
 		.need("invalid miniscript");
 	args.print_output(&info);
 }
 
+fn cmd_normalize<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("normalize", "normalize an output descriptor into its canonical form\n\n\
+		Private keys are replaced by their public keys, all hardened derivation steps \
+		use the same marker and the checksum is added. An invalid checksum is an error.")
+		.args(&[
+			args::arg("descriptor", "the output descriptor to normalize").required(false),
+			args::opt("hardened-marker", "the marker to use for hardened derivation steps")
+				.possible_values(&["h", "'"])
+				.default_value("'"),
+		])
+}
+
+/// Normalize the descriptor, using the given marker for hardened derivation steps.
+fn normalize_descriptor(desc_str: &str, hardened_marker: char) -> Result<String, String> {
+	let desc_str = check_checksum(desc_str)?;
+
+	// Descriptors are displayed with ' as the hardened marker.
+	let normalized = parse_descriptor_keys(desc_str)
+		.map(|(desc, _)| desc.to_string())
+		.or_else(|e| {
+			debug!("Can't parse descriptor with descriptor keys: {}", e);
+			let desc = desc_str.parse::<Descriptor<String>>().map_err(|e| e.to_string())?;
+			// Never print private keys that couldn't be replaced.
+			if !desc.for_each_key(|k| k.parse::<DescriptorSecretKey>().is_err()) {
+				return Err(e);
+			}
+			Ok(desc.to_string())
+		})
+		.map_err(|e| format!("invalid descriptor: {}", e))?;
+	if hardened_marker == '\'' {
+		return Ok(normalized);
+	}
+	let desc = normalized.rsplit_once('#').map(|(d, _)| d).unwrap_or(&normalized)
+		.replace('\'', &hardened_marker.to_string());
+	let checksum = desc_checksum(&desc).map_err(|e| format!("invalid descriptor: {}", e))?;
+	Ok(format!("{}#{}", desc, checksum))
+}
+
+fn exec_normalize<'a>(args: &clap::ArgMatches<'a>) {
+	let desc_str = util::arg_or_stdin(args, "descriptor");
+	let marker = match args.value_of("hardened-marker") {
+		Some("h") => 'h',
+		_ => '\'',
+	};
+	print!("{}", normalize_descriptor(&desc_str, marker).unwrap_or_else(|e| exit!("{}", e)));
+}
+
 fn cmd_inspect<'a>() -> clap::App<'a, 'a> {
 	cmd::subcommand("inspect", "inspect miniscripts")
 		.arg(args::arg("miniscript", "the miniscript to inspect").required(false))
@@ -437,3 +738,80 @@ impl FromScriptContexts for MiniscriptInfo {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_multipath_private_keys() {
+		let tprv = "tprv8ZgxMBicQKsPd7Uf69XL1XwhmjHopUGep8GuEiJDZmbQz6o58LninorQAfcKZWARbtRtfnLcJ5MQ2AtHcQJCCRUcMRvmDUjyEmNUWwx8UbK";
+		let (desc, keymaps) = parse_descriptor_keys(&format!("wpkh({}/84h/1h/0h/<0;1>/*)", tprv)).unwrap();
+		assert!(desc.is_multipath());
+		assert!(!desc.to_string().contains("tprv"));
+		assert_eq!(keymaps.len(), 2);
+
+		let derived = derive_descriptor(&desc, Some(&keymaps), (0, 1), bitcoin::Network::Testnet);
+		assert_eq!(derived.len(), 2);
+		for (info, path) in derived.iter().zip(["84'/1'/0'/0/0", "84'/1'/0'/1/0"].iter()) {
+			let xprv = tprv.parse::<bitcoin::bip32::Xpriv>().unwrap()
+				.derive_priv(&SECP, &path.parse::<bitcoin::bip32::DerivationPath>().unwrap()).unwrap();
+			assert_eq!(info.keys[0].private_key.unwrap().inner, xprv.private_key);
+		}
+
+		assert!(parse_descriptor_keys(&format!("wpkh({}/<0h;1h>/*)", tprv)).is_err());
+	}
+
+	#[test]
+	fn test_mixed_private_public_keys() {
+		let tprv = "tprv8ZgxMBicQKsPd7Uf69XL1XwhmjHopUGep8GuEiJDZmbQz6o58LninorQAfcKZWARbtRtfnLcJ5MQ2AtHcQJCCRUcMRvmDUjyEmNUWwx8UbK";
+		let xprv = tprv.parse::<bitcoin::bip32::Xpriv>().unwrap();
+		let tpub = bitcoin::bip32::Xpub::from_priv(&SECP, &xprv)
+			.derive_pub(&SECP, &"1".parse::<bitcoin::bip32::DerivationPath>().unwrap()).unwrap();
+		let desc_str = format!("wsh(multi(1,{}/0/*,{}/1/*))", tprv, tpub);
+		let (desc, keymaps) = parse_descriptor_keys(&desc_str).unwrap();
+		let derived = derive_descriptor(&desc, Some(&keymaps), (0, 2), bitcoin::Network::Testnet);
+		assert_eq!(derived.len(), 2);
+		for (i, info) in derived.iter().enumerate() {
+			let path = format!("0/{}", i).parse::<bitcoin::bip32::DerivationPath>().unwrap();
+			let expected = xprv.derive_priv(&SECP, &path).unwrap().private_key;
+			assert_eq!(info.keys[0].private_key.unwrap().inner, expected);
+			assert_eq!(info.keys[1].private_key, None);
+		}
+
+		// A key that is a prefix of a multipath key in the same descriptor.
+		let desc_str = format!("wsh(multi(1,{0}/0/*,{0}/0/<0;1>/*))", tprv);
+		let (desc, keymaps) = parse_descriptor_keys(&desc_str).unwrap();
+		let derived = derive_descriptor(&desc, Some(&keymaps), (0, 1), bitcoin::Network::Testnet);
+		assert_eq!(derived.len(), 2);
+		let first = xprv.derive_priv(&SECP, &"0/0".parse::<bitcoin::bip32::DerivationPath>().unwrap())
+			.unwrap().private_key;
+		for (info, path) in derived.iter().zip(["0/0/0", "0/1/0"].iter()) {
+			let path = path.parse::<bitcoin::bip32::DerivationPath>().unwrap();
+			let expected = xprv.derive_priv(&SECP, &path).unwrap().private_key;
+			assert_eq!(info.keys[0].private_key.unwrap().inner, first);
+			assert_eq!(info.keys[1].private_key.unwrap().inner, expected);
+		}
+	}
+
+	#[test]
+	fn test_normalize() {
+		// The BIP-380 example with both hardened markers.
+		let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+		let apostrophe = format!("pkh([d34db33f/44'/0'/0']{}/1/*)#ml40v0wf", xpub);
+		let h = format!("pkh([d34db33f/44h/0h/0h]{}/1/*)#gw2sk4e2", xpub);
+		assert_eq!(check_checksum(&apostrophe).unwrap(), &apostrophe[..apostrophe.len() - 9]);
+		assert_eq!(check_checksum(&h).unwrap(), &h[..h.len() - 9]);
+
+		let without_checksum = format!("pkh([d34db33f/44h/0'/0h]{}/1/*)", xpub);
+		assert_eq!(normalize_descriptor(&without_checksum, '\'').unwrap(), apostrophe);
+		assert_eq!(normalize_descriptor(&without_checksum, 'h').unwrap(), h);
+		assert_eq!(normalize_descriptor(&h, '\'').unwrap(), apostrophe);
+		assert_eq!(normalize_descriptor(&apostrophe, 'h').unwrap(), h);
+		assert_eq!(normalize_descriptor(&h, 'h').unwrap(), h);
+
+		let wrong = format!("{}#gw2sk4e3", &h[..h.len() - 9]);
+		assert!(check_checksum(&wrong).unwrap_err().contains("expected gw2sk4e2"));
+		assert!(normalize_descriptor(&wrong, 'h').is_err());
+	}
+}
@@ -3,7 +3,9 @@ pub mod ark;
 pub mod bech32;
 pub mod bip32;
 pub mod bip39;
+pub mod bip85;
 pub mod block;
+pub mod frost;
 pub mod hash;
 pub mod key;
 pub mod ln;
@@ -13,6 +15,9 @@ pub mod miniscript;
 pub mod psbt;
 pub mod random;
 pub mod script;
+pub mod seed;
+pub mod silentpayment;
+pub mod slip39;
 pub mod tx;
 
 /// Build a list of all built-in subcommands.
@@ -23,7 +28,9 @@ pub fn subcommands() -> Vec<clap::App<'static, 'static>> {
 		bech32::subcommand(),
 		bip32::subcommand(),
 		bip39::subcommand(),
+		bip85::subcommand(),
 		block::subcommand(),
+		frost::subcommand(),
 		hash::subcommand(),
 		key::subcommand(),
 		ln::subcommand(),
@@ -33,6 +40,9 @@ pub fn subcommands() -> Vec<clap::App<'static, 'static>> {
 		psbt::subcommand(),
 		random::subcommand(),
 		script::subcommand(),
+		seed::subcommand(),
+		silentpayment::subcommand(),
+		slip39::subcommand(),
 		tx::subcommand(),
 	]
 }
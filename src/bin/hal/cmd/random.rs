@@ -1,5 +1,3 @@
-use std::io::Write;
-
 use bitcoin::secp256k1::rand::{self, RngCore};
 
 use crate::prelude::*;
@@ -21,6 +19,7 @@ fn cmd_bytes<'a>() -> clap::App<'a, 'a> {
 		.arg(args::arg("number", "the number of bytes").required(true))
 		.arg(args::flag("raw-stdout", "output the raw bytes of the result to stdout")
 			.short("r"))
+		.args(&args::opts_encrypt())
 }
 
 fn exec_bytes<'a>(args: &clap::ArgMatches<'a>) {
@@ -31,9 +30,9 @@ fn exec_bytes<'a>(args: &clap::ArgMatches<'a>) {
 	rand::thread_rng().fill_bytes(&mut bytes);
 	
 	if args.is_present("raw-stdout") {
-		::std::io::stdout().write_all(&bytes).unwrap();
+		args.write_secret(&bytes);
 	} else {
-		print!("{}", hex::encode(&bytes));
+		args.write_secret(hex::encode(&bytes).as_bytes());
 	}
 }
 
@@ -0,0 +1,49 @@
+use clap;
+
+use crate::prelude::*;
+
+pub fn subcommand<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand_group("seed", "non-BIP-39 wallet seed formats")
+		.subcommand(cmd_electrum())
+		.subcommand(cmd_aezeed())
+}
+
+pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
+	match args.subcommand() {
+		("electrum", Some(m)) => exec_electrum(m),
+		("aezeed", Some(m)) => exec_aezeed(m),
+		(_, _) => unreachable!("clap prints help"),
+	};
+}
+
+fn cmd_electrum<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand(
+		"electrum",
+		"get the seed and keystores of an Electrum v2 mnemonic\n\n\
+		Standard, segwit and 2fa seeds are supported, old Electrum v1 seeds are not.",
+	)
+	.arg(args::arg("mnemonic", "the mnemonic phrase").required(true))
+	.arg(args::opt("passphrase", "the seed extension"))
+}
+
+fn exec_electrum<'a>(args: &clap::ArgMatches<'a>) {
+	let mnemonic = args.value_of("mnemonic").need("no mnemonic provided");
+	let passphrase = args.value_of("passphrase").unwrap_or("");
+	let info = hal::electrum::decode(mnemonic, passphrase, args.network())
+		.need("invalid Electrum mnemonic");
+	args.print_output(&info)
+}
+
+fn cmd_aezeed<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("aezeed", "decipher an LND aezeed mnemonic")
+		.arg(args::arg("mnemonic", "the mnemonic phrase").required(true))
+		.arg(args::opt("passphrase", "the aezeed passphrase"))
+}
+
+fn exec_aezeed<'a>(args: &clap::ArgMatches<'a>) {
+	let mnemonic = args.value_of("mnemonic").need("no mnemonic provided");
+	let passphrase = args.value_of("passphrase").unwrap_or("");
+	let info = hal::aezeed::decode(mnemonic, passphrase, args.network())
+		.need("invalid aezeed");
+	args.print_output(&info)
+}
@@ -0,0 +1,129 @@
+use std::str::FromStr;
+
+use bitcoin::{Network, ScriptBuf, Transaction};
+use bitcoin::consensus::encode::deserialize;
+use clap;
+
+use hal::silentpayment::{SilentPaymentAddress, SilentPaymentAddressInfo};
+
+use crate::prelude::*;
+
+pub fn subcommand<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand_group("silentpayment", "work with BIP-352 silent payments\n\n\
+		Only the receiving side is supported: creating addresses and scanning transactions. \
+		Deriving the outputs to pay to a silent payment address as a sender is out of scope.")
+		.subcommand(cmd_create())
+		.subcommand(cmd_inspect())
+		.subcommand(cmd_scan())
+}
+
+pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
+	match args.subcommand() {
+		("create", Some(m)) => exec_create(m),
+		("inspect", Some(m)) => exec_inspect(m),
+		("scan", Some(m)) => exec_scan(m),
+		(_, _) => unreachable!("clap prints help"),
+	};
+}
+
+/// Parse an argument that can be either a public or a private key.
+fn pub_or_privkey<'a>(
+	args: &clap::ArgMatches<'a>,
+	key: &str,
+) -> (secp256k1::PublicKey, Option<secp256k1::SecretKey>) {
+	let s = args.value_of(key).need(&format!("no {} provided", key));
+	if let Ok(pk) = bitcoin::PublicKey::from_str(s) {
+		if !pk.compressed {
+			exit!("the {} must be a compressed public key", key);
+		}
+		(pk.inner, None)
+	} else {
+		let sk = args.need_privkey(key).inner;
+		(sk.public_key(&SECP), Some(sk))
+	}
+}
+
+/// Parse the labels argument.
+fn labels<'a>(args: &clap::ArgMatches<'a>) -> Vec<u32> {
+	args.values_of("label").into_iter().flatten()
+		.map(|m| m.parse::<u32>().need("invalid label: must be a number"))
+		.collect()
+}
+
+fn cmd_create<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("create", "create a silent payment address")
+		.arg(args::arg("scan-key", "the scan public key; \
+			a private key in hex or WIF is needed to use a label").required(true))
+		.arg(args::arg("spend-key", "the spend public or private key").required(true))
+		.arg(args::opt("label", "the label m to apply, 0 is reserved for change"))
+}
+
+fn exec_create<'a>(args: &clap::ArgMatches<'a>) {
+	let (scan_key, scan_privkey) = pub_or_privkey(args, "scan-key");
+	let (mut spend_key, _) = pub_or_privkey(args, "spend-key");
+
+	let label = labels(args).first().map(|m| {
+		let sk = scan_privkey.need("a private scan key is needed to use a label");
+		(*m, hal::silentpayment::label_tweak(&sk, *m))
+	});
+	if let Some((m, _)) = label {
+		spend_key = hal::silentpayment::labeled_spend_key(&spend_key, &scan_privkey.unwrap(), m)
+			.need("invalid label");
+	}
+
+	let address = SilentPaymentAddress {
+		testnet: args.network() != Network::Bitcoin,
+		version: 0,
+		scan_key,
+		spend_key,
+	};
+	let mut info = SilentPaymentAddressInfo::new(&address);
+	if let Some((m, tweak)) = label {
+		info.label = Some(m);
+		info.label_tweak = Some(tweak.to_be_bytes().to_vec().into());
+	}
+	args.print_output(&info)
+}
+
+fn cmd_inspect<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("inspect", "inspect a silent payment address")
+		.arg(args::arg("address", "the address").required(true))
+}
+
+fn exec_inspect<'a>(args: &clap::ArgMatches<'a>) {
+	let address_str = args.value_of("address").need("no address provided");
+	let address = SilentPaymentAddress::from_str(address_str).need("invalid silent payment address");
+	args.print_output(&SilentPaymentAddressInfo::new(&address))
+}
+
+fn cmd_scan<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("scan", "scan a transaction for silent payment outputs\n\n\
+		Computes the shared secret for the scan key and derives the expected \
+		outputs to find the outputs that pay to the spend key and labels.")
+		.arg(args::arg("tx", "the raw transaction in hex").required(true))
+		.arg(args::opt("prevout", "the scriptPubKey in hex of the output spent by each input, \
+			in the order of the inputs")
+			.multiple(true).number_of_values(1).required(true))
+		.arg(args::opt("scan-key", "the private scan key in hex or WIF").required(true))
+		.arg(args::opt("spend-key", "the spend public key; \
+			if a private key is given, the private keys of the outputs are calculated")
+			.required(true))
+		.arg(args::opt("label", "a label m to scan for").multiple(true).number_of_values(1))
+}
+
+fn exec_scan<'a>(args: &clap::ArgMatches<'a>) {
+	let tx_hex = args.value_of("tx").need("no transaction provided");
+	let tx = deserialize::<Transaction>(&hex::decode(tx_hex).need("invalid tx hex"))
+		.need("invalid transaction");
+	let prevouts = args.values_of("prevout").need("no prevouts provided")
+		.map(|s| ScriptBuf::from(hex::decode(s).need("invalid prevout scriptPubKey hex")))
+		.collect::<Vec<_>>();
+	let scan_key = args.need_privkey("scan-key").inner;
+	let (spend_key, spend_privkey) = pub_or_privkey(args, "spend-key");
+
+	let prevouts = prevouts.iter().map(|s| s.as_script()).collect::<Vec<_>>();
+	let info = hal::silentpayment::scan(
+		&tx, &prevouts, &scan_key, &spend_key, spend_privkey.as_ref(), &labels(args),
+	).need("failed to scan transaction");
+	args.print_output(&info)
+}
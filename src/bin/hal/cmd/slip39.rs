@@ -0,0 +1,129 @@
+use std::io::{self, BufRead};
+
+use bip39::Mnemonic;
+use bitcoin::secp256k1::rand::{self, RngCore};
+use clap;
+
+use hal::slip39::{GroupInfo, MasterSecretInfo, Share, ShareInfo, SplitInfo};
+
+use crate::prelude::*;
+
+pub fn subcommand<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand_group("slip39", "SLIP-39 Shamir mnemonic shares")
+		.subcommand(cmd_split())
+		.subcommand(cmd_combine())
+		.subcommand(cmd_inspect())
+}
+
+pub fn execute<'a>(args: &clap::ArgMatches<'a>) {
+	match args.subcommand() {
+		("split", Some(m)) => exec_split(m),
+		("combine", Some(m)) => exec_combine(m),
+		("inspect", Some(m)) => exec_inspect(m),
+		(_, _) => unreachable!("clap prints help"),
+	};
+}
+
+/// Parse a group of the form `<threshold>-of-<count>`.
+fn group(s: &str) -> (u8, u8) {
+	let (t, n) = s.split_once("-of-").need("groups should be of the form <threshold>-of-<count>");
+	(t.parse().need("invalid group threshold"), n.parse().need("invalid group share count"))
+}
+
+fn cmd_split<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("split", "split a master secret into SLIP-39 shares\n\n\
+		When using the entropy of a BIP-39 mnemonic, note that the resulting wallet is \
+		different, because SLIP-39 uses the master secret directly as the BIP-32 seed.")
+		.unset_setting(clap::AppSettings::ArgRequiredElseHelp)
+		.arg(args::opt("secret", "the master secret in hex; if omitted, a random one is generated"))
+		.arg(args::opt("bip39", "use the entropy of a BIP-39 mnemonic as the master secret"))
+		.arg(args::opt("bits", "the number of bits of a generated master secret")
+			.possible_values(&["128", "256"])
+			.default_value("128"))
+		.arg(args::opt("group", "a group of shares as <threshold>-of-<count>")
+			.multiple(true).number_of_values(1)
+			.default_value("1-of-1"))
+		.arg(args::opt("group-threshold", "the number of groups needed to recover the secret")
+			.default_value("1"))
+		.arg(args::opt("passphrase", "the passphrase to encrypt the master secret with"))
+		.arg(args::opt("iteration-exponent", "the exponent of the number of PBKDF2 iterations")
+			.default_value("1"))
+}
+
+fn exec_split<'a>(args: &clap::ArgMatches<'a>) {
+	let master_secret = match (args.value_of("secret"), args.value_of("bip39")) {
+		(Some(_), Some(_)) => exit!("can't provide both --secret and --bip39"),
+		(Some(hex), None) => hex::decode(hex).need("invalid master secret hex"),
+		(None, Some(m)) => {
+			Mnemonic::parse(m).need("invalid BIP-39 mnemonic").to_entropy()
+		}
+		(None, None) => {
+			let bits = args.value_of("bits").unwrap_or("128").parse::<usize>()
+				.need("invalid number of bits");
+			let mut secret = vec![0u8; bits / 8];
+			rand::thread_rng().fill_bytes(&mut secret);
+			secret
+		}
+	};
+	let groups = args.values_of("group").need("no groups given").map(group).collect::<Vec<_>>();
+	let group_threshold = args.value_of("group-threshold").unwrap_or("1").parse::<u8>()
+		.need("invalid group threshold");
+	let passphrase = args.value_of("passphrase").unwrap_or("");
+	let iteration_exponent = args.value_of("iteration-exponent").unwrap_or("1").parse::<u8>()
+		.need("invalid iteration exponent");
+
+	let shares = hal::slip39::split(
+		&master_secret, passphrase, group_threshold, &groups, iteration_exponent, true,
+		&mut rand::thread_rng(),
+	).need("failed to split the master secret");
+
+	let info = SplitInfo {
+		identifier: shares[0][0].identifier,
+		extendable: shares[0][0].extendable,
+		iteration_exponent,
+		group_threshold,
+		groups: shares.iter().map(|g| GroupInfo {
+			member_threshold: g[0].member_threshold,
+			shares: g.iter().map(|s| s.to_mnemonic()).collect(),
+		}).collect(),
+		secret: MasterSecretInfo::new(&master_secret, args.network())
+			.need("invalid master secret"),
+	};
+	args.print_output(&info)
+}
+
+fn cmd_combine<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("combine", "combine SLIP-39 shares to recover the master secret")
+		.unset_setting(clap::AppSettings::ArgRequiredElseHelp)
+		.arg(args::arg("shares", "the share mnemonics; if omitted, \
+			they are read from stdin, one per line").multiple(true))
+		.arg(args::opt("passphrase", "the passphrase the master secret was encrypted with"))
+}
+
+fn exec_combine<'a>(args: &clap::ArgMatches<'a>) {
+	let mnemonics = match args.values_of("shares") {
+		Some(s) => s.map(|s| s.to_owned()).collect::<Vec<_>>(),
+		None => io::stdin().lock().lines()
+			.map(|l| l.need("error reading stdin"))
+			.filter(|l| !l.trim().is_empty())
+			.collect(),
+	};
+	let shares = mnemonics.iter()
+		.map(|m| Share::from_mnemonic(m).need("invalid share"))
+		.collect::<Vec<_>>();
+
+	let passphrase = args.value_of("passphrase").unwrap_or("");
+	let secret = hal::slip39::combine(&shares, passphrase).need("failed to combine shares");
+	args.print_output(&MasterSecretInfo::new(&secret, args.network()).need("invalid master secret"))
+}
+
+fn cmd_inspect<'a>() -> clap::App<'a, 'a> {
+	cmd::subcommand("inspect", "inspect a single SLIP-39 share")
+		.arg(args::arg("share", "the share mnemonic").required(true))
+}
+
+fn exec_inspect<'a>(args: &clap::ArgMatches<'a>) {
+	let share = Share::from_mnemonic(args.value_of("share").need("no share given"))
+		.need("invalid share");
+	args.print_output(&ShareInfo::from(&share))
+}
@@ -11,6 +11,8 @@ extern crate clap;
 extern crate fern;
 extern crate hex;
 extern crate jobserver;
+extern crate regex;
+extern crate rpassword;
 extern crate serde_json;
 extern crate secp256k1;
 extern crate shell_escape;
@@ -135,7 +137,9 @@ fn main() {
 		("bech32", Some(ref m)) => cmd::bech32::execute(&m),
 		("bip32", Some(ref m)) => cmd::bip32::execute(&m),
 		("bip39", Some(ref m)) => cmd::bip39::execute(&m),
+		("bip85", Some(m)) => cmd::bip85::execute(m),
 		("block", Some(ref m)) => cmd::block::execute(&m),
+		("frost", Some(m)) => cmd::frost::execute(m),
 		("hash", Some(ref m)) => cmd::hash::execute(&m),
 		("key", Some(ref m)) => cmd::key::execute(&m),
 		("ln", Some(ref m)) => cmd::ln::execute(&m),
@@ -145,6 +149,9 @@ fn main() {
 		("psbt", Some(ref m)) => cmd::psbt::execute(&m),
 		("random", Some(ref m)) => cmd::random::execute(&m),
 		("script", Some(ref m)) => cmd::script::execute(&m),
+		("seed", Some(m)) => cmd::seed::execute(m),
+		("silentpayment", Some(m)) => cmd::silentpayment::execute(m),
+		("slip39", Some(m)) => cmd::slip39::execute(m),
 		("tx", Some(ref m)) => cmd::tx::execute(&m),
 		(cmd, subcommand_args) => {
 			// Try execute an external subcommand.
@@ -50,6 +50,31 @@ pub fn arg_or_stdin<'a>(args: &'a clap::ArgMatches<'a>, arg: &str) -> Cow<'a, st
 	}
 }
 
+/// Parse the range argument for wildcard derivation into an inclusive start and exclusive end.
+pub fn derivation_range<'a>(args: &clap::ArgMatches<'a>) -> (u32, u32) {
+	let s = match args.value_of("range") {
+		Some(s) => s,
+		None => return (0, 1),
+	};
+	let (start, end) = if let Some((start, end)) = s.split_once("..=") {
+		let end = end.parse::<u32>().need("invalid range end");
+		(start, end.checked_add(1).need("invalid range end"))
+	} else if let Some((start, end)) = s.split_once("..") {
+		(start, end.parse::<u32>().need("invalid range end"))
+	} else {
+		let index = s.parse::<u32>().need("invalid range index");
+		(s, index.checked_add(1).need("invalid range index"))
+	};
+	let start = start.parse::<u32>().need("invalid range start");
+	if start >= end {
+		exit!("invalid range: the range is empty");
+	}
+	if end > (1 << 31) {
+		exit!("invalid range: indices must be smaller than 2^31");
+	}
+	(start, end)
+}
+
 #[cfg(unix)]
 pub fn is_executable<P: AsRef<Path>>(path: P) -> bool {
 	use std::os::unix::prelude::*;
@@ -146,6 +171,72 @@ pub fn find_closest(cmd: &str) -> Option<String> {
 		.map(|slot| slot.1)
 }
 
+/// Get the entropy from dice rolls, coin flips or playing cards, if any were provided.
+///
+/// Exits if the input contains fewer bits of entropy than the requested number of bytes.
+pub fn physical_entropy<'a>(args: &clap::ArgMatches<'a>, nb_bytes: usize) -> Option<Vec<u8>> {
+	if more_than_one(&[args.is_present("dice"), args.is_present("coins"), args.is_present("cards")]) {
+		exit!("can only use one of --dice, --coins and --cards");
+	}
+	let entropy = if let Some(rolls) = args.value_of("dice") {
+		let sides = args.value_of("dice-sides").unwrap_or("6").parse::<u32>()
+			.need("invalid number of dice sides");
+		hal::entropy::dice(rolls, sides).need("invalid dice rolls")
+	} else if let Some(flips) = args.value_of("coins") {
+		hal::entropy::coins(flips).need("invalid coin flips")
+	} else if let Some(cards) = args.value_of("cards") {
+		hal::entropy::cards(cards, args.is_present("cards-replace")).need("invalid cards")
+	} else {
+		return None;
+	};
+
+	if !entropy.is_sufficient(nb_bytes * 8) {
+		exit!(
+			"not enough entropy: {} values provide {:.1} bits, need {} bits",
+			entropy.count, entropy.bits, nb_bytes * 8,
+		);
+	}
+	Some(entropy.hash()[0..nb_bytes].to_vec())
+}
+
+/// The environment variable a passphrase can be provided in.
+pub const PASSPHRASE_ENV: &str = "HAL_PASSPHRASE";
+
+/// Get a passphrase from the file given in the `file` option, the $HAL_PASSPHRASE environment
+/// variable or a prompt on the terminal, in that order. When `confirm` is set, a prompted
+/// passphrase has to be entered twice.
+///
+/// The `insecure` option takes the passphrase from the command line as a last resort, where
+/// other users can see it and it ends up in the shell history.
+pub fn passphrase<'a>(args: &clap::ArgMatches<'a>, file: &str, insecure: &str, confirm: bool) -> String {
+	if args.is_present(file) && args.is_present(insecure) {
+		exit!("can only use one of --{} and --{}", file, insecure);
+	}
+	let passphrase = if let Some(passphrase) = args.value_of(insecure) {
+		passphrase.to_owned()
+	} else if let Some(path) = args.value_of(file) {
+		let content = fs::read_to_string(path).need("failed to read passphrase file");
+		content.lines().next().unwrap_or("").to_owned()
+	} else if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+		passphrase
+	} else {
+		let err = format!(
+			"failed to prompt for a passphrase, use --{} or ${} instead", file, PASSPHRASE_ENV,
+		);
+		let passphrase = rpassword::prompt_password("Enter passphrase: ").need(&err);
+		if confirm && !passphrase.is_empty()
+			&& rpassword::prompt_password("Confirm passphrase: ").need(&err) != passphrase
+		{
+			exit!("the passphrases don't match");
+		}
+		passphrase
+	};
+	if passphrase.is_empty() {
+		exit!("the passphrase can't be empty");
+	}
+	passphrase
+}
+
 /// Return true if more than one of the input booleans is true.
 pub fn more_than_one(bools: &[bool]) -> bool {
 	let mut one = false;
@@ -1,8 +1,11 @@
 
-use bitcoin::{bip32, NetworkKind};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bitcoin::{base58, bip32, NetworkKind};
 use serde::{Deserialize, Serialize};
 
-use crate::address;
+use crate::{address, SECP};
 
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 pub struct DerivationInfo {
@@ -22,4 +25,531 @@ pub struct DerivationInfo {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub private_key: Option<bitcoin::secp256k1::SecretKey>,
 	pub addresses: address::Addresses,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub slip132: Option<Slip132Info>,
+}
+
+/// The single-sig script types with their BIP-44 style purpose and descriptor template.
+const SINGLE_SIG_TEMPLATES: [(u32, &str, &str, &str); 4] = [
+	(44, "p2pkh", "pkh(", ")"),
+	(49, "p2sh-p2wpkh", "sh(wpkh(", "))"),
+	(84, "p2wpkh", "wpkh(", ")"),
+	(86, "p2tr", "tr(", ")"),
+];
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct WalletDescriptorInfo {
+	pub purpose: u32,
+	pub script_type: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub path: Option<bip32::DerivationPath>,
+	/// The key expression, including the key origin when known.
+	pub key: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub receive: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub change: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub multipath: Option<String>,
+}
+
+impl WalletDescriptorInfo {
+	/// Create the info for a single-sig account key, with descriptors for the receive
+	/// and change branches.
+	fn single_sig(
+		purpose: u32,
+		script_type: &str,
+		path: Option<bip32::DerivationPath>,
+		key: String,
+		(prefix, suffix): (&str, &str),
+	) -> WalletDescriptorInfo {
+		let descriptor = |branch: &str| {
+			with_checksum(format!("{}{}/{}/*{}", prefix, key, branch, suffix))
+		};
+		WalletDescriptorInfo {
+			purpose,
+			script_type: script_type.to_owned(),
+			path,
+			receive: Some(descriptor("0")),
+			change: Some(descriptor("1")),
+			multipath: Some(descriptor("<0;1>")),
+			key,
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct WalletDescriptorsInfo {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub master_fingerprint: Option<bip32::Fingerprint>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub account: Option<u32>,
+	pub descriptors: Vec<WalletDescriptorInfo>,
+	/// The BIP-48 and BIP-87 key expressions to use in multisig descriptors.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub multisig_keys: Vec<WalletDescriptorInfo>,
+}
+
+/// Find the single-sig script type and descriptor template for a purpose.
+pub(crate) fn single_sig_template(purpose: u32) -> Option<(&'static str, &'static str, &'static str)> {
+	SINGLE_SIG_TEMPLATES.iter().find(|t| t.0 == purpose).map(|t| (t.1, t.2, t.3))
+}
+
+/// Find the purpose of a single-sig script type.
+pub fn single_sig_purpose(script_type: &str) -> Option<u32> {
+	SINGLE_SIG_TEMPLATES.iter().find(|t| t.1 == script_type).map(|t| t.0)
+}
+
+/// The address of the single-sig script type for a purpose.
+pub fn single_sig_address(
+	purpose: u32,
+	pk: &bitcoin::secp256k1::PublicKey,
+	network: bitcoin::Network,
+) -> Option<bitcoin::Address> {
+	let compressed = bitcoin::CompressedPublicKey(*pk);
+	Some(match purpose {
+		44 => bitcoin::Address::p2pkh(compressed, network),
+		49 => bitcoin::Address::p2shwpkh(&compressed, network),
+		84 => bitcoin::Address::p2wpkh(&compressed, network),
+		86 => bitcoin::Address::p2tr(&SECP, pk.x_only_public_key().0, None, network),
+		_ => return None,
+	})
+}
+
+/// Add the checksum to a descriptor.
+pub(crate) fn with_checksum(desc: String) -> String {
+	let checksum = miniscript::descriptor::checksum::desc_checksum(&desc)
+		.expect("valid descriptor characters");
+	format!("{}#{}", desc, checksum)
+}
+
+/// Format a key expression with its origin.
+pub(crate) fn key_expression(
+	fingerprint: bip32::Fingerprint,
+	path: &bip32::DerivationPath,
+	key: &dyn fmt::Display,
+) -> String {
+	if path.is_empty() {
+		format!("[{}]{}", fingerprint, key)
+	} else {
+		format!("[{}/{}]{}", fingerprint, path, key)
+	}
+}
+
+/// Get the standard wallet descriptors and multisig key expressions for the
+/// given account of a master key.
+///
+/// If private is set, the key expressions contain the account xpriv.
+pub fn wallet_descriptors(
+	master: &bip32::Xpriv,
+	account: u32,
+	private: bool,
+) -> Result<WalletDescriptorsInfo, &'static str> {
+	let fingerprint = master.fingerprint(&SECP);
+	let coin_type = match master.network {
+		NetworkKind::Main => 0,
+		NetworkKind::Test => 1,
+	};
+	let account_key = |path: &bip32::DerivationPath| -> Result<String, &'static str> {
+		let xpriv = master.derive_priv(&SECP, path).map_err(|_| "derivation error")?;
+		Ok(if private {
+			key_expression(fingerprint, path, &xpriv)
+		} else {
+			key_expression(fingerprint, path, &bip32::Xpub::from_priv(&SECP, &xpriv))
+		})
+	};
+	let hardened_path = |steps: &[u32]| -> Result<bip32::DerivationPath, &'static str> {
+		steps.iter().map(|i| bip32::ChildNumber::from_hardened_idx(*i))
+			.collect::<Result<Vec<_>, _>>()
+			.map(bip32::DerivationPath::from)
+			.map_err(|_| "invalid account number")
+	};
+
+	let mut descriptors = Vec::new();
+	for (purpose, script_type, prefix, suffix) in SINGLE_SIG_TEMPLATES.iter() {
+		let path = hardened_path(&[*purpose, coin_type, account])?;
+		let key = account_key(&path)?;
+		descriptors.push(WalletDescriptorInfo::single_sig(
+			*purpose, script_type, Some(path), key, (prefix, suffix),
+		));
+	}
+
+	let mut multisig_keys = Vec::new();
+	let multisig = [
+		(48, "p2sh-p2wsh", Some(1)),
+		(48, "p2wsh", Some(2)),
+		(87, "multisig", None),
+	];
+	for (purpose, script_type, bip48_script_type) in multisig.iter() {
+		let mut steps = vec![*purpose, coin_type, account];
+		steps.extend(bip48_script_type);
+		let path = hardened_path(&steps)?;
+		multisig_keys.push(WalletDescriptorInfo {
+			purpose: *purpose,
+			script_type: script_type.to_string(),
+			key: account_key(&path)?,
+			path: Some(path),
+			receive: None,
+			change: None,
+			multipath: None,
+		});
+	}
+
+	Ok(WalletDescriptorsInfo {
+		master_fingerprint: Some(fingerprint),
+		account: Some(account),
+		descriptors,
+		multisig_keys,
+	})
+}
+
+/// Get the standard single-sig wallet descriptors for an account-level
+/// extended key, optionally with the key origin.
+///
+/// If a purpose is given, or the origin path starts with a known purpose,
+/// only the descriptor for that script type is given.
+pub fn account_descriptors(
+	account_key: &dyn fmt::Display,
+	origin: Option<KeyOrigin>,
+	purpose: Option<u32>,
+) -> WalletDescriptorsInfo {
+	let key = match origin {
+		Some((fingerprint, ref path)) => key_expression(fingerprint, path, account_key),
+		None => account_key.to_string(),
+	};
+	let origin_purpose = origin.as_ref().and_then(|o| match o.1.as_ref().first() {
+		Some(bip32::ChildNumber::Hardened { index }) => Some(*index),
+		_ => None,
+	}).filter(|p| SINGLE_SIG_TEMPLATES.iter().any(|t| t.0 == *p));
+	let purpose = purpose.or(origin_purpose);
+	let descriptors = SINGLE_SIG_TEMPLATES.iter().filter(|t| {
+		purpose.map(|p| p == t.0).unwrap_or(true)
+	}).map(|(purpose, script_type, prefix, suffix)| {
+		let path = origin.as_ref().map(|o| o.1.clone());
+		WalletDescriptorInfo::single_sig(*purpose, script_type, path, key.clone(), (prefix, suffix))
+	}).collect();
+
+	WalletDescriptorsInfo {
+		master_fingerprint: origin.map(|o| o.0),
+		account: None,
+		descriptors,
+		multisig_keys: Vec::new(),
+	}
+}
+
+/// The extended key version bytes for a script type as defined in SLIP-132.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Slip132Version {
+	pub public_prefix: &'static str,
+	pub public_version: [u8; 4],
+	pub private_prefix: &'static str,
+	pub private_version: [u8; 4],
+	pub network: NetworkKind,
+	/// The script type, "unknown" for the standard versions which are used for any type.
+	pub script_type: &'static str,
+	/// The BIP-44 style purpose for single-sig script types.
+	pub purpose: Option<u32>,
+}
+
+const fn slip132(
+	public_prefix: &'static str,
+	public_version: u32,
+	private_prefix: &'static str,
+	private_version: u32,
+	network: NetworkKind,
+	script_type: &'static str,
+	purpose: Option<u32>,
+) -> Slip132Version {
+	Slip132Version {
+		public_prefix,
+		public_version: public_version.to_be_bytes(),
+		private_prefix,
+		private_version: private_version.to_be_bytes(),
+		network,
+		script_type,
+		purpose,
+	}
+}
+
+/// All SLIP-132 versions, the first for each network are the standard BIP-32 versions.
+pub const SLIP132_VERSIONS: [Slip132Version; 10] = [
+	slip132("xpub", 0x0488b21e, "xprv", 0x0488ade4, NetworkKind::Main, "unknown", None),
+	slip132("ypub", 0x049d7cb2, "yprv", 0x049d7878, NetworkKind::Main, "p2sh-p2wpkh", Some(49)),
+	slip132("zpub", 0x04b24746, "zprv", 0x04b2430c, NetworkKind::Main, "p2wpkh", Some(84)),
+	slip132("Ypub", 0x0295b43f, "Yprv", 0x0295b005, NetworkKind::Main, "p2sh-p2wsh-multisig", None),
+	slip132("Zpub", 0x02aa7ed3, "Zprv", 0x02aa7a99, NetworkKind::Main, "p2wsh-multisig", None),
+	slip132("tpub", 0x043587cf, "tprv", 0x04358394, NetworkKind::Test, "unknown", None),
+	slip132("upub", 0x044a5262, "uprv", 0x044a4e28, NetworkKind::Test, "p2sh-p2wpkh", Some(49)),
+	slip132("vpub", 0x045f1cf6, "vprv", 0x045f18bc, NetworkKind::Test, "p2wpkh", Some(84)),
+	slip132("Upub", 0x024289ef, "Uprv", 0x024285b5, NetworkKind::Test, "p2sh-p2wsh-multisig", None),
+	slip132("Vpub", 0x02575483, "Vprv", 0x02575048, NetworkKind::Test, "p2wsh-multisig", None),
+];
+
+impl Slip132Version {
+	/// Find the version with the given public or private prefix.
+	pub fn from_prefix(prefix: &str) -> Option<&'static Slip132Version> {
+		SLIP132_VERSIONS.iter().find(|v| v.public_prefix == prefix || v.private_prefix == prefix)
+	}
+
+	/// The standard BIP-32 version for the network.
+	pub fn standard(network: NetworkKind) -> &'static Slip132Version {
+		SLIP132_VERSIONS.iter().find(|v| v.network == network).expect("all networks present")
+	}
+
+	/// Whether this is the standard BIP-32 version.
+	pub fn is_standard(&self) -> bool {
+		self == Slip132Version::standard(self.network)
+	}
+
+	/// Encode the xpub with this version.
+	pub fn encode_xpub(&self, xpub: &bip32::Xpub) -> Result<String, &'static str> {
+		if xpub.network != self.network {
+			return Err("extended key network doesn't match the version");
+		}
+		let mut data = xpub.encode();
+		data[0..4].copy_from_slice(&self.public_version);
+		Ok(base58::encode_check(&data))
+	}
+
+	/// Encode the xpriv with this version.
+	pub fn encode_xpriv(&self, xpriv: &bip32::Xpriv) -> Result<String, &'static str> {
+		if xpriv.network != self.network {
+			return Err("extended key network doesn't match the version");
+		}
+		let mut data = xpriv.encode();
+		data[0..4].copy_from_slice(&self.private_version);
+		Ok(base58::encode_check(&data))
+	}
+}
+
+/// Decode an extended key in any of the SLIP-132 versions.
+pub fn decode_slip132(
+	s: &str,
+) -> Result<(Option<bip32::Xpriv>, bip32::Xpub, &'static Slip132Version), &'static str> {
+	let mut data = base58::decode_check(s).map_err(|_| "invalid base58 encoding")?;
+	if data.len() != 78 {
+		return Err("invalid extended key length");
+	}
+	let version = SLIP132_VERSIONS.iter().find(|v| {
+		data[0..4] == v.public_version || data[0..4] == v.private_version
+	}).ok_or("unknown extended key version")?;
+	let standard = Slip132Version::standard(version.network);
+
+	if data[0..4] == version.private_version {
+		data[0..4].copy_from_slice(&standard.private_version);
+		let xpriv = bip32::Xpriv::decode(&data).map_err(|_| "invalid extended private key")?;
+		Ok((Some(xpriv), bip32::Xpub::from_priv(&SECP, &xpriv), version))
+	} else {
+		data[0..4].copy_from_slice(&standard.public_version);
+		let xpub = bip32::Xpub::decode(&data).map_err(|_| "invalid extended public key")?;
+		Ok((None, xpub, version))
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct Slip132Info {
+	pub version: String,
+	pub script_type: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub descriptor: Option<String>,
+	/// The key encoded in all SLIP-132 versions for its network.
+	pub encodings: BTreeMap<String, String>,
+}
+
+impl Slip132Info {
+	pub fn new(
+		xpriv: Option<&bip32::Xpriv>,
+		xpub: &bip32::Xpub,
+		version: &Slip132Version,
+	) -> Slip132Info {
+		let mut encodings = BTreeMap::new();
+		for v in SLIP132_VERSIONS.iter().filter(|v| v.network == xpub.network) {
+			encodings.insert(v.public_prefix.to_owned(), v.encode_xpub(xpub).expect("same network"));
+			if let Some(xpriv) = xpriv {
+				encodings.insert(
+					v.private_prefix.to_owned(), v.encode_xpriv(xpriv).expect("same network"),
+				);
+			}
+		}
+
+		let descriptor = version.purpose.and_then(|purpose| {
+			SINGLE_SIG_TEMPLATES.iter().find(|t| t.0 == purpose)
+		}).map(|(_, _, prefix, suffix)| {
+			with_checksum(format!("{}{}/<0;1>/*{}", prefix, xpub, suffix))
+		});
+
+		Slip132Info {
+			version: if xpriv.is_some() { version.private_prefix } else { version.public_prefix }
+				.to_owned(),
+			script_type: version.script_type.to_owned(),
+			descriptor,
+			encodings,
+		}
+	}
+}
+
+/// The master key fingerprint and derivation path of a key.
+pub type KeyOrigin = (bip32::Fingerprint, bip32::DerivationPath);
+
+/// Parse a key origin in the form `[fingerprint/path]`, the brackets are optional.
+pub fn parse_key_origin(
+	s: &str,
+) -> Result<KeyOrigin, &'static str> {
+	let s = s.strip_prefix('[').unwrap_or(s);
+	let s = s.strip_suffix(']').unwrap_or(s);
+	let (fingerprint, path) = s.split_once('/').unwrap_or((s, ""));
+	let fingerprint = fingerprint.parse().map_err(|_| "invalid key origin fingerprint")?;
+	let path = if path.is_empty() {
+		bip32::DerivationPath::master()
+	} else {
+		format!("m/{}", path).parse().map_err(|_| "invalid key origin derivation path")?
+	};
+	Ok((fingerprint, path))
+}
+
+/// Split a key expression like `[d34db33f/84h/0h/0h]xpub.../0/*` into the key origin,
+/// the key and the derivation path after the key.
+pub fn split_key_expression(
+	s: &str,
+) -> Result<(Option<KeyOrigin>, &str, &str), &'static str> {
+	let (origin, rest) = if s.starts_with('[') {
+		let (origin, rest) = s.split_once(']').ok_or("unclosed key origin")?;
+		(Some(parse_key_origin(origin)?), rest)
+	} else {
+		(None, s)
+	};
+	let (key, path) = rest.split_once('/').unwrap_or((rest, ""));
+	Ok((origin, key, path))
+}
+
+/// The maximum number of paths a derivation path with ranges can expand into.
+pub const MAX_DERIVATION_PATHS: usize = 100_000;
+
+/// The paths described by a derivation path with ranges and wildcards.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ExpandedPath {
+	pub paths: Vec<bip32::DerivationPath>,
+	/// Whether the path contains ranges or wildcards, even if they cover a single index.
+	pub ranged: bool,
+}
+
+/// Expand a derivation path that can contain ranges like `0-99` and wildcards `*`
+/// into all the paths it describes.
+///
+/// Wildcards are expanded into the given range with exclusive end. At most
+/// [MAX_DERIVATION_PATHS] paths are allowed.
+pub fn expand_derivation_path(
+	path: &str,
+	(wildcard_start, wildcard_end): (u32, u32),
+) -> Result<ExpandedPath, &'static str> {
+	let path = path.trim_start_matches('m').trim_start_matches('/');
+	let mut steps = Vec::new();
+	let mut ranged = false;
+	let mut count = 1usize;
+	for step in path.split('/').filter(|s| !s.is_empty()) {
+		let (step, hardened) = match step.strip_suffix(|c| c == '\'' || c == 'h' || c == 'H') {
+			Some(step) => (step, true),
+			None => (step, false),
+		};
+		let (start, end) = if step == "*" {
+			ranged = true;
+			(wildcard_start, wildcard_end)
+		} else if let Some((start, end)) = step.split_once('-') {
+			ranged = true;
+			let start = start.parse::<u32>().map_err(|_| "invalid derivation path range")?;
+			let end = end.parse::<u32>().map_err(|_| "invalid derivation path range")?;
+			(start, end.checked_add(1).ok_or("invalid derivation path range")?)
+		} else {
+			let index = step.parse::<u32>().map_err(|_| "invalid derivation path index")?;
+			(index, index.checked_add(1).ok_or("invalid derivation path index")?)
+		};
+		if start >= end {
+			return Err("empty derivation path range");
+		}
+		count = count.checked_mul((end - start) as usize)
+			.filter(|c| *c <= MAX_DERIVATION_PATHS)
+			.ok_or("derivation path expands into more than 100000 paths")?;
+		steps.push((start..end, hardened));
+	}
+
+	let mut ret = vec![Vec::new()];
+	for (range, hardened) in steps {
+		let children = range.map(|i| if hardened {
+			bip32::ChildNumber::from_hardened_idx(i)
+		} else {
+			bip32::ChildNumber::from_normal_idx(i)
+		}).collect::<Result<Vec<_>, _>>().map_err(|_| "derivation path index out of range")?;
+		ret = ret.into_iter().flat_map(|prefix| children.iter().map(move |c| {
+			let mut path = prefix.clone();
+			path.push(*c);
+			path
+		})).collect();
+	}
+	Ok(ExpandedPath {
+		paths: ret.into_iter().map(bip32::DerivationPath::from).collect(),
+		ranged,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_wallet_descriptors() {
+		// The BIP-84 test vector mnemonic "abandon abandon ... about".
+		let seed = hex::decode(
+			"5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc1\
+			9a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4",
+		).unwrap();
+		let master = bip32::Xpriv::new_master(NetworkKind::Main, &seed).unwrap();
+		let info = wallet_descriptors(&master, 0, false).unwrap();
+		assert_eq!(info.master_fingerprint, Some("73c5da0a".parse().unwrap()));
+		let wpkh = info.descriptors.iter().find(|d| d.purpose == 84).unwrap();
+		assert_eq!(wpkh.key, "[73c5da0a/84'/0'/0']xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVF\
+			kKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V");
+		assert!(wpkh.receive.as_ref().unwrap().ends_with("/0/*)#wc3n3van"));
+		assert_eq!(info.multisig_keys.len(), 3);
+	}
+
+	#[test]
+	fn test_slip132() {
+		// The BIP-84 test vector account zpub and its xpub.
+		let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+		let (xpriv, xpub, version) = decode_slip132(zpub).unwrap();
+		assert!(xpriv.is_none());
+		assert_eq!(version.script_type, "p2wpkh");
+		assert_eq!(xpub.to_string(), "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V");
+		assert_eq!(version.encode_xpub(&xpub).unwrap(), zpub);
+
+		let info = Slip132Info::new(None, &xpub, Slip132Version::standard(NetworkKind::Main));
+		assert_eq!(info.script_type, "unknown");
+		assert_eq!(info.descriptor, None);
+	}
+
+	#[test]
+	fn test_expand_derivation_path() {
+		let expanded = expand_derivation_path("m/1h/0-2/*", (5, 7)).unwrap();
+		assert!(expanded.ranged);
+		assert_eq!(expanded.paths.len(), 6);
+		assert_eq!(expanded.paths[0].to_string(), "1'/0/5");
+		assert_eq!(expanded.paths[5].to_string(), "1'/2/6");
+		let expanded = expand_derivation_path("", (0, 1)).unwrap();
+		assert_eq!(expanded.paths, vec![bip32::DerivationPath::master()]);
+		assert!(!expanded.ranged);
+		assert!(expand_derivation_path("0/5-4", (0, 1)).is_err());
+
+		// A range of a single index is still a range.
+		let expanded = expand_derivation_path("0/*", (3, 4)).unwrap();
+		assert!(expanded.ranged);
+		assert_eq!(expanded.paths.len(), 1);
+
+		assert_eq!(expand_derivation_path("0-999/0-99", (0, 1)).unwrap().paths.len(), 100_000);
+		assert!(expand_derivation_path("0-999/0-100", (0, 1)).is_err());
+		assert!(expand_derivation_path("*/*", (0, 1 << 31)).is_err());
+
+		let (origin, key, path) = split_key_expression("[d34db33f/84h/0h/0h]xpub/0/*").unwrap();
+		assert_eq!(origin.unwrap().1.to_string(), "84'/0'/0'");
+		assert_eq!((key, path), ("xpub", "0/*"));
+	}
 }
@@ -0,0 +1,313 @@
+//! BIP-38 passphrase-protected private keys.
+//!
+//! Passphrases are used as UTF-8 as given, they are not normalized to NFC.
+
+use bitcoin::secp256k1::{self, Scalar};
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::{address, base58, Address, NetworkKind, PrivateKey, PublicKey};
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{scrypt, Aes256};
+use crate::SECP;
+
+const PREFIX_NON_EC: [u8; 2] = [0x01, 0x42];
+const PREFIX_EC: [u8; 2] = [0x01, 0x43];
+const FLAG_NON_EC: u8 = 0xc0;
+const FLAG_COMPRESSED: u8 = 0x20;
+const FLAG_LOT_SEQUENCE: u8 = 0x04;
+const MAGIC_INTERMEDIATE: [u8; 7] = [0x2c, 0xe9, 0xb3, 0xe1, 0xff, 0x39, 0xe2];
+const MAGIC_NO_LOT_SEQUENCE: u8 = 0x51;
+const MAGIC_LOT_SEQUENCE: u8 = 0x53;
+const MAX_LOT: u32 = 0xfffff;
+const MAX_SEQUENCE: u32 = 0xfff;
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct Bip38Info {
+	pub encrypted_private_key: String,
+	pub ec_multiply: bool,
+	pub compressed: bool,
+	pub address: Address<address::NetworkUnchecked>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub intermediate_code: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub lot: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub sequence: Option<u32>,
+}
+
+/// The address hash is the first 4 bytes of the hash of the P2PKH address.
+fn address_and_hash(pubkey: &PublicKey, network: NetworkKind) -> (Address, [u8; 4]) {
+	let address = Address::p2pkh(pubkey, network);
+	let hash = sha256d::Hash::hash(address.to_string().as_bytes());
+	let mut ret = [0u8; 4];
+	ret.copy_from_slice(&hash[0..4]);
+	(address, ret)
+}
+
+/// Encrypt two blocks of data after XORing them with the first half of the derived key.
+fn encrypt_halves(data: &[u8; 32], derived: &[u8]) -> [u8; 32] {
+	let mut key = [0u8; 32];
+	key.copy_from_slice(&derived[32..64]);
+	let aes = Aes256::new(&key);
+	let mut ret = [0u8; 32];
+	for (i, b) in ret.iter_mut().enumerate() {
+		*b = data[i] ^ derived[i];
+	}
+	for block in ret.chunks_mut(16) {
+		let mut buf = [0u8; 16];
+		buf.copy_from_slice(block);
+		aes.encrypt_block(&mut buf);
+		block.copy_from_slice(&buf);
+	}
+	ret
+}
+
+/// Decrypt a single block and XOR it with the part of the derived key at the offset.
+fn decrypt_block(block: &[u8], derived: &[u8], offset: usize) -> [u8; 16] {
+	let mut key = [0u8; 32];
+	key.copy_from_slice(&derived[32..64]);
+	let mut ret = [0u8; 16];
+	ret.copy_from_slice(block);
+	Aes256::new(&key).decrypt_block(&mut ret);
+	ret.iter_mut().zip(derived[offset..offset + 16].iter()).for_each(|(r, d)| *r ^= d);
+	ret
+}
+
+/// Encrypt a private key without EC multiplication.
+pub fn encrypt(privkey: &PrivateKey, passphrase: &str) -> Bip38Info {
+	let (address, address_hash) = address_and_hash(&privkey.public_key(&SECP), privkey.network);
+	let derived = scrypt(passphrase.as_bytes(), &address_hash, 14, 8, 8, 64);
+	let encrypted = encrypt_halves(&privkey.inner.secret_bytes(), &derived);
+
+	let mut data = PREFIX_NON_EC.to_vec();
+	data.push(FLAG_NON_EC | if privkey.compressed { FLAG_COMPRESSED } else { 0 });
+	data.extend(&address_hash);
+	data.extend(&encrypted);
+	Bip38Info {
+		encrypted_private_key: base58::encode_check(&data),
+		ec_multiply: false,
+		compressed: privkey.compressed,
+		address: address.into_unchecked(),
+		intermediate_code: None,
+		lot: None,
+		sequence: None,
+	}
+}
+
+/// Derive the passfactor from the passphrase and the owner entropy.
+fn passfactor(
+	passphrase: &str,
+	owner_entropy: &[u8; 8],
+	lot_sequence: bool,
+) -> Result<secp256k1::SecretKey, &'static str> {
+	let owner_salt = if lot_sequence { &owner_entropy[0..4] } else { &owner_entropy[..] };
+	let prefactor = scrypt(passphrase.as_bytes(), owner_salt, 14, 8, 8, 32);
+	let passfactor = if lot_sequence {
+		let mut data = prefactor;
+		data.extend(owner_entropy);
+		sha256d::Hash::hash(&data)[..].to_vec()
+	} else {
+		prefactor
+	};
+	secp256k1::SecretKey::from_slice(&passfactor).map_err(|_| "invalid passfactor")
+}
+
+/// Create an intermediate code for EC multiplication, optionally with a lot and sequence number.
+///
+/// When a lot and sequence number are used, only the first 4 bytes of the owner salt are used.
+pub fn intermediate_code(
+	passphrase: &str,
+	owner_salt: &[u8; 8],
+	lot_sequence: Option<(u32, u32)>,
+) -> Result<String, &'static str> {
+	let mut owner_entropy = *owner_salt;
+	if let Some((lot, sequence)) = lot_sequence {
+		if lot > MAX_LOT || sequence > MAX_SEQUENCE {
+			return Err("the lot should be at most 1048575 and the sequence at most 4095");
+		}
+		owner_entropy[4..8].copy_from_slice(&(lot * 4096 + sequence).to_be_bytes());
+	}
+	let passfactor = passfactor(passphrase, &owner_entropy, lot_sequence.is_some())?;
+	let passpoint = secp256k1::PublicKey::from_secret_key(&SECP, &passfactor);
+
+	let mut data = MAGIC_INTERMEDIATE.to_vec();
+	data.push(if lot_sequence.is_some() { MAGIC_LOT_SEQUENCE } else { MAGIC_NO_LOT_SEQUENCE });
+	data.extend(&owner_entropy);
+	data.extend(&passpoint.serialize());
+	Ok(base58::encode_check(&data))
+}
+
+/// Derive the key to encrypt the seed from the passpoint.
+fn ec_derived(
+	passpoint: &secp256k1::PublicKey,
+	address_hash: &[u8; 4],
+	owner_entropy: &[u8; 8],
+) -> Vec<u8> {
+	let mut salt = address_hash.to_vec();
+	salt.extend(owner_entropy);
+	scrypt(&passpoint.serialize(), &salt, 10, 1, 1, 64)
+}
+
+/// Create a new encrypted private key from an intermediate code, using the given random seed.
+///
+/// This doesn't require the passphrase and the private key is not known to the creator.
+pub fn encrypt_ec_multiply(
+	intermediate_code: &str,
+	seed: &[u8; 24],
+	compressed: bool,
+	network: NetworkKind,
+) -> Result<Bip38Info, &'static str> {
+	let data = base58::decode_check(intermediate_code).map_err(|_| "invalid base58 encoding")?;
+	if data.len() != 49 || data[0..7] != MAGIC_INTERMEDIATE {
+		return Err("invalid intermediate code");
+	}
+	let lot_sequence = match data[7] {
+		MAGIC_NO_LOT_SEQUENCE => false,
+		MAGIC_LOT_SEQUENCE => true,
+		_ => return Err("invalid intermediate code"),
+	};
+	let mut owner_entropy = [0u8; 8];
+	owner_entropy.copy_from_slice(&data[8..16]);
+	let passpoint = secp256k1::PublicKey::from_slice(&data[16..49])
+		.map_err(|_| "invalid passpoint")?;
+
+	let factor = sha256d::Hash::hash(seed).to_byte_array();
+	let factor = Scalar::from_be_bytes(factor).map_err(|_| "invalid seed")?;
+	let point = passpoint.mul_tweak(&SECP, &factor).map_err(|_| "invalid seed")?;
+	let pubkey = PublicKey { compressed, inner: point };
+	let (address, address_hash) = address_and_hash(&pubkey, network);
+
+	let derived = ec_derived(&passpoint, &address_hash, &owner_entropy);
+	let mut key = [0u8; 32];
+	key.copy_from_slice(&derived[32..64]);
+	let aes = Aes256::new(&key);
+	let mut part1 = [0u8; 16];
+	for i in 0..16 {
+		part1[i] = seed[i] ^ derived[i];
+	}
+	aes.encrypt_block(&mut part1);
+	let mut part2 = [0u8; 16];
+	for i in 0..16 {
+		let b = if i < 8 { part1[8 + i] } else { seed[8 + i] };
+		part2[i] = b ^ derived[16 + i];
+	}
+	aes.encrypt_block(&mut part2);
+
+	let mut encrypted = PREFIX_EC.to_vec();
+	encrypted.push(
+		if compressed { FLAG_COMPRESSED } else { 0 }
+			| if lot_sequence { FLAG_LOT_SEQUENCE } else { 0 },
+	);
+	encrypted.extend(&address_hash);
+	encrypted.extend(&owner_entropy);
+	encrypted.extend(&part1[0..8]);
+	encrypted.extend(&part2);
+
+	let lot_sequence = if lot_sequence {
+		let mut buf = [0u8; 4];
+		buf.copy_from_slice(&owner_entropy[4..8]);
+		Some(u32::from_be_bytes(buf))
+	} else {
+		None
+	};
+	Ok(Bip38Info {
+		encrypted_private_key: base58::encode_check(&encrypted),
+		ec_multiply: true,
+		compressed,
+		address: address.into_unchecked(),
+		intermediate_code: Some(intermediate_code.to_owned()),
+		lot: lot_sequence.map(|ls| ls / 4096),
+		sequence: lot_sequence.map(|ls| ls % 4096),
+	})
+}
+
+/// Decrypt an encrypted private key.
+///
+/// The network of the key is the network of the address that the address hash matches.
+pub fn decrypt(encrypted: &str, passphrase: &str) -> Result<PrivateKey, &'static str> {
+	let data = base58::decode_check(encrypted).map_err(|_| "invalid base58 encoding")?;
+	if data.len() != 39 {
+		return Err("invalid BIP-38 key length");
+	}
+	let prefix = [data[0], data[1]];
+	let flag = data[2];
+	let compressed = flag & FLAG_COMPRESSED != 0;
+	let mut address_hash = [0u8; 4];
+	address_hash.copy_from_slice(&data[3..7]);
+
+	let secret_key = if prefix == PREFIX_NON_EC {
+		if flag & !FLAG_COMPRESSED != FLAG_NON_EC {
+			return Err("invalid BIP-38 flag byte");
+		}
+		let derived = scrypt(passphrase.as_bytes(), &address_hash, 14, 8, 8, 64);
+		let mut secret = decrypt_block(&data[7..23], &derived, 0).to_vec();
+		secret.extend(&decrypt_block(&data[23..39], &derived, 16));
+		secp256k1::SecretKey::from_slice(&secret).map_err(|_| "invalid passphrase")?
+	} else if prefix == PREFIX_EC {
+		if flag & !(FLAG_COMPRESSED | FLAG_LOT_SEQUENCE) != 0 {
+			return Err("invalid BIP-38 flag byte");
+		}
+		let mut owner_entropy = [0u8; 8];
+		owner_entropy.copy_from_slice(&data[7..15]);
+		let passfactor = passfactor(passphrase, &owner_entropy, flag & FLAG_LOT_SEQUENCE != 0)?;
+		let passpoint = secp256k1::PublicKey::from_secret_key(&SECP, &passfactor);
+		let derived = ec_derived(&passpoint, &address_hash, &owner_entropy);
+
+		let part2 = decrypt_block(&data[23..39], &derived, 16);
+		let mut part1 = data[15..23].to_vec();
+		part1.extend(&part2[0..8]);
+		let mut seed = decrypt_block(&part1, &derived, 0).to_vec();
+		seed.extend(&part2[8..16]);
+
+		let factor = sha256d::Hash::hash(&seed).to_byte_array();
+		let factor = Scalar::from_be_bytes(factor).map_err(|_| "invalid passphrase")?;
+		passfactor.mul_tweak(&factor).map_err(|_| "invalid passphrase")?
+	} else {
+		return Err("invalid BIP-38 prefix");
+	};
+
+	[NetworkKind::Main, NetworkKind::Test].iter().map(|network| PrivateKey {
+		compressed,
+		network: *network,
+		inner: secret_key,
+	}).find(|privkey| {
+		address_and_hash(&privkey.public_key(&SECP), privkey.network).1 == address_hash
+	}).ok_or("invalid passphrase")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_bip38() {
+		let privkey = decrypt(
+			"6PRVWUbkzzsbcVac2qwfssoUJAN1Xhrg6bNk8J7Nzm5H7kxEbn2Nh2ZoGg", "TestingOneTwoThree",
+		).unwrap();
+		assert_eq!(privkey.to_string(), "5KN7MzqK5wt2TP1fQCYyHBtDrXdJuXbUzm4A9rKAteGu3Qi5CVR");
+
+		let privkey = "L44B5gGEpqEDRS9vVPz7QT35jcBG2r3CZwSwQ4fCewXAhAhqGVpP".parse().unwrap();
+		assert_eq!(
+			encrypt(&privkey, "TestingOneTwoThree").encrypted_private_key,
+			"6PYNKZ1EAgYgmQfmNVamxyXVWHzK5s6DGhwP4J5o44cvXdoY7sRzhtpUeo",
+		);
+	}
+
+	#[test]
+	fn test_bip38_ec_multiply() {
+		let privkey = decrypt(
+			"6PgNBNNzDkKdhkT6uJntUXwwzQV8Rr2tZcbkDcuC9DZRsS6AtHts4Ypo1j", "MOLON LABE",
+		).unwrap();
+		assert_eq!(privkey.to_string(), "5JLdxTtcTHcfYcmJsNVy1v2PMDx432JPoYcBTVVRHpPaxUrdtf8");
+
+		let code = intermediate_code("hal", &[1, 2, 3, 4, 5, 6, 7, 8], Some((42, 7))).unwrap();
+		assert!(code.starts_with("passphrase"));
+		let info = encrypt_ec_multiply(&code, &[9; 24], true, NetworkKind::Main).unwrap();
+		assert_eq!((info.lot, info.sequence), (Some(42), Some(7)));
+		let privkey = decrypt(&info.encrypted_private_key, "hal").unwrap();
+		assert_eq!(
+			Address::p2pkh(privkey.public_key(&SECP), privkey.network),
+			info.address.assume_checked(),
+		);
+	}
+}
@@ -2,11 +2,29 @@
 use std::borrow::Cow;
 
 use bip39lib::{Language, Mnemonic};
-use bitcoin::Network;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{address, Address, Network, NetworkKind};
 use bitcoin::bip32;
 use serde::{Deserialize, Serialize};
 
 use crate::{SECP, GetInfo, HexBytes};
+use crate::bip32::{key_expression, single_sig_address, single_sig_template, with_checksum};
+
+/// The name of a BIP-39 language.
+pub fn language_name(language: Language) -> &'static str {
+	match language {
+		Language::English => "english",
+		Language::Czech => "czech",
+		Language::French => "french",
+		Language::Italian => "italian",
+		Language::Japanese => "japanese",
+		Language::Korean => "korean",
+		Language::Portuguese => "portuguese",
+		Language::Spanish => "spanish",
+		Language::SimplifiedChinese => "simplified-chinese",
+		Language::TraditionalChinese => "traditional-chinese",
+	}
+}
 
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 pub struct MnemonicInfo {
@@ -29,18 +47,7 @@ impl MnemonicInfo {
 			mnemonic: mnemonic.to_string(),
 			entropy_bits: entropy.len() * 8,
 			entropy: entropy.into(),
-			language: match mnemonic.language() {
-				Language::English => "english",
-				Language::Czech => "czech",
-				Language::French => "french",
-				Language::Italian => "italian",
-				Language::Japanese => "japanese",
-				Language::Korean => "korean",
-				Language::Portuguese => "portuguese",
-				Language::Spanish => "spanish",
-				Language::SimplifiedChinese => "simplified-chinese",
-				Language::TraditionalChinese => "traditional-chinese",
-			},
+			language: language_name(mnemonic.language()),
 			passphrase: passphrase.to_owned(),
 			seed: GetInfo::get_info(&mnemonic.to_seed(passphrase), network),
 		}
@@ -60,19 +67,231 @@ pub struct SeedInfo {
 	pub bip32_xpub: bip32::Xpub,
 }
 
-impl GetInfo<SeedInfo> for [u8; 64] {
-	fn get_info(&self, network: Network) -> SeedInfo {
-		let xpriv = bip32::Xpriv::new_master(network, &self[..]).unwrap();
+impl SeedInfo {
+	/// Create the seed info for a BIP-32 seed of 16 to 64 bytes.
+	pub fn from_seed(seed: &[u8], network: Network) -> SeedInfo {
+		let xpriv = bip32::Xpriv::new_master(network, seed).unwrap();
 		let xpub =
 			bip32::Xpub::from_priv(&SECP, &xpriv);
 		SeedInfo {
-			seed: self.to_vec().into(),
+			seed: seed.to_vec().into(),
 			bip32_xpriv: xpriv,
 			bip32_xpub: xpub,
 		}
 	}
 }
 
+impl GetInfo<SeedInfo> for [u8; 64] {
+	fn get_info(&self, network: Network) -> SeedInfo {
+		SeedInfo::from_seed(&self[..], network)
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct AccountAddressInfo {
+	pub path: bip32::DerivationPath,
+	pub public_key: bitcoin::PublicKey,
+	pub address: Address<address::NetworkUnchecked>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct AccountInfo {
+	pub script_type: String,
+	pub path: bip32::DerivationPath,
+	pub fingerprint: bip32::Fingerprint,
+	pub xpub: bip32::Xpub,
+	/// The key expression with origin to use in descriptors.
+	pub key: String,
+	pub descriptor: String,
+	pub receive: Vec<AccountAddressInfo>,
+	pub change: Vec<AccountAddressInfo>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct MnemonicDerivationInfo {
+	pub master_fingerprint: bip32::Fingerprint,
+	pub accounts: Vec<AccountInfo>,
+}
+
+/// The standard coin type of BIP-44 style derivation paths for the network.
+pub fn coin_type(network: Network) -> u32 {
+	match NetworkKind::from(network) {
+		NetworkKind::Main => 0,
+		NetworkKind::Test => 1,
+	}
+}
+
+/// Derive the account at the path with the single-sig script type for the given
+/// purpose and the first receive and change addresses.
+pub fn derive_account(
+	master: &bip32::Xpriv,
+	path: &bip32::DerivationPath,
+	purpose: u32,
+	network: Network,
+	nb_addresses: u32,
+) -> Result<AccountInfo, &'static str> {
+	let (script_type, prefix, suffix) = single_sig_template(purpose)
+		.ok_or("unknown script type purpose")?;
+	let xpriv = master.derive_priv(&SECP, path).map_err(|_| "derivation error")?;
+	let xpub = bip32::Xpub::from_priv(&SECP, &xpriv);
+	let key = key_expression(master.fingerprint(&SECP), path, &xpub);
+
+	let addresses = |branch: u32| -> Result<Vec<AccountAddressInfo>, &'static str> {
+		(0..nb_addresses).map(|i| {
+			let child = [
+				bip32::ChildNumber::from_normal_idx(branch).expect("0 or 1"),
+				bip32::ChildNumber::from_normal_idx(i).map_err(|_| "too many addresses")?,
+			];
+			let pk = xpub.derive_pub(&SECP, &child).map_err(|_| "derivation error")?.public_key;
+			let address = single_sig_address(purpose, &pk, network)
+				.expect("purpose has a template");
+			Ok(AccountAddressInfo {
+				path: path.extend(child),
+				public_key: bitcoin::PublicKey::new(pk),
+				address: address.as_unchecked().clone(),
+			})
+		}).collect()
+	};
+
+	Ok(AccountInfo {
+		script_type: script_type.to_owned(),
+		path: path.clone(),
+		fingerprint: xpub.fingerprint(),
+		xpub,
+		descriptor: with_checksum(format!("{}{}/<0;1>/*{}", prefix, key, suffix)),
+		key,
+		receive: addresses(0)?,
+		change: addresses(1)?,
+	})
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct WordCheckInfo {
+	pub index: usize,
+	pub word: String,
+	pub in_wordlist: bool,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub suggestions: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct MnemonicCheckInfo {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub language: Option<&'static str>,
+	pub word_count: usize,
+	pub valid_word_count: bool,
+	pub words: Vec<WordCheckInfo>,
+	/// Whether the checksum is valid, only known when all words are in the wordlist.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub checksum_valid: Option<bool>,
+	pub valid: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct MnemonicRecoveryInfo {
+	/// The number of candidates with a valid checksum that were tried.
+	pub candidates: u64,
+	pub matches: Vec<String>,
+}
+
+/// Whether the number of words is valid for a mnemonic.
+pub fn valid_word_count(count: usize) -> bool {
+	(12..=24).contains(&count) && count % 3 == 0
+}
+
+/// Concatenate the 11 bits of each word index.
+fn index_bits(indices: &[u16]) -> Vec<u8> {
+	let mut ret = vec![0u8; (indices.len() * 11).div_ceil(8)];
+	for (i, idx) in indices.iter().enumerate() {
+		for b in 0..11 {
+			if (idx >> (10 - b)) & 1 == 1 {
+				let pos = i * 11 + b;
+				ret[pos / 8] |= 1 << (7 - pos % 8);
+			}
+		}
+	}
+	ret
+}
+
+/// Whether the mnemonic with the given word indices has a valid checksum.
+pub fn checksum_valid(indices: &[u16]) -> bool {
+	if !valid_word_count(indices.len()) {
+		return false;
+	}
+	let bits = index_bits(indices);
+	let entropy_len = indices.len() / 3 * 4;
+	let checksum_bits = indices.len() / 3;
+	let hash = sha256::Hash::hash(&bits[..entropy_len]);
+	hash[0] >> (8 - checksum_bits) == bits[entropy_len] >> (8 - checksum_bits)
+}
+
+/// The entropy of the mnemonic with the given word indices.
+pub fn entropy(indices: &[u16]) -> Vec<u8> {
+	let mut bits = index_bits(indices);
+	bits.truncate(indices.len() / 3 * 4);
+	bits
+}
+
+/// All the last words that make a valid mnemonic with the given first words.
+pub fn last_words(language: Language, indices: &[u16]) -> Result<Vec<&'static str>, &'static str> {
+	if !valid_word_count(indices.len() + 1) {
+		return Err("invalid number of words, should be one less than a valid mnemonic");
+	}
+	let mut candidate = indices.to_vec();
+	candidate.push(0);
+	Ok(language.word_list().iter().enumerate().filter(|(i, _)| {
+		*candidate.last_mut().unwrap() = *i as u16;
+		checksum_valid(&candidate)
+	}).map(|(_, w)| *w).collect())
+}
+
+/// Call f with all mnemonics with a valid checksum that can be made by filling in the
+/// unknown words.
+///
+/// To split the work, only the candidates of the given shard are tried, a shard being
+/// a pair of the shard index and the number of shards. The shards are based on the
+/// first unknown word.
+pub fn fill_unknown_words<F: FnMut(&[u16])>(
+	indices: &[Option<u16>],
+	(shard, nb_shards): (u16, u16),
+	mut f: F,
+) {
+	let unknown = indices.iter().enumerate().filter(|(_, w)| w.is_none())
+		.map(|(i, _)| i).collect::<Vec<_>>();
+	let mut candidate = indices.iter().map(|w| w.unwrap_or(0)).collect::<Vec<_>>();
+	if unknown.is_empty() {
+		if shard == 0 && checksum_valid(&candidate) {
+			f(&candidate);
+		}
+		return;
+	}
+
+	// Iterate over all combinations like an odometer.
+	let mut counters = vec![0u16; unknown.len()];
+	counters[0] = shard;
+	loop {
+		if counters[0] >= 2048 {
+			return;
+		}
+		for (pos, c) in unknown.iter().zip(counters.iter()) {
+			candidate[*pos] = *c;
+		}
+		if checksum_valid(&candidate) {
+			f(&candidate);
+		}
+
+		let mut i = unknown.len() - 1;
+		loop {
+			counters[i] += if i == 0 { nb_shards } else { 1 };
+			if i == 0 || counters[i] < 2048 {
+				break;
+			}
+			counters[i] = 0;
+			i -= 1;
+		}
+	}
+}
+
 /// Parse a BIP-39 language from string.
 ///
 /// Supported formats are (case-insensitive):
@@ -121,4 +340,41 @@ mod tests {
 			assert_eq!(Some(*l), parse_language(&l.to_string().to_uppercase()), "lang: {}", l);
 		}
 	}
+	#[test]
+	fn test_checksum() {
+		let mnemonic = Mnemonic::parse("abandon abandon abandon abandon abandon abandon \
+			abandon abandon abandon abandon abandon about").unwrap();
+		let indices = mnemonic.word_indices().map(|i| i as u16).collect::<Vec<_>>();
+		assert!(checksum_valid(&indices));
+		assert_eq!(entropy(&indices), vec![0; 16]);
+
+		let last = last_words(Language::English, &indices[..11]).unwrap();
+		assert_eq!(last.len(), 128);
+		assert!(last.contains(&"about"));
+
+		let mut unknown = indices.iter().map(|i| Some(*i)).collect::<Vec<_>>();
+		unknown[3] = None;
+		let mut found = Vec::new();
+		fill_unknown_words(&unknown, (0, 1), |c| found.push(c[3]));
+		assert!(found.contains(&0));
+		assert!(found.len() < 2048);
+	}
+
+	#[test]
+	fn test_derive_account() {
+		// BIP-86 test vector.
+		let mnemonic = Mnemonic::parse("abandon abandon abandon abandon abandon abandon \
+			abandon abandon abandon abandon abandon about").unwrap();
+		let master = bip32::Xpriv::new_master(Network::Bitcoin, &mnemonic.to_seed("")).unwrap();
+		let path = "m/86'/0'/0'".parse().unwrap();
+		let account = derive_account(&master, &path, 86, Network::Bitcoin, 1).unwrap();
+		assert_eq!(
+			account.receive[0].address.clone().assume_checked().to_string(),
+			"bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+		);
+		assert_eq!(
+			account.change[0].address.clone().assume_checked().to_string(),
+			"bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7",
+		);
+	}
 }
@@ -0,0 +1,406 @@
+use bip39lib::{Language, Mnemonic};
+use bitcoin::bip32;
+use bitcoin::hashes::{hmac, sha512, Hash, HashEngine};
+use bitcoin::{Network, NetworkKind, PrivateKey};
+use serde::{Deserialize, Serialize};
+
+use crate::bip39::MnemonicInfo;
+use crate::crypto::base64_encode;
+use crate::key::KeyInfo;
+use crate::{address, GetInfo, HexBytes, SECP};
+
+/// The BIP-85 purpose, "bip" on a phone keypad.
+pub const PURPOSE: u32 = 83696968;
+
+/// The application numbers.
+pub const APP_BIP39: u32 = 39;
+pub const APP_WIF: u32 = 2;
+pub const APP_XPRV: u32 = 32;
+pub const APP_HEX: u32 = 128169;
+pub const APP_PWD_BASE64: u32 = 707764;
+pub const APP_PWD_BASE85: u32 = 707785;
+pub const APP_DICE: u32 = 89101;
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct Bip85Info<T> {
+	pub path: bip32::DerivationPath,
+	pub derived_entropy: HexBytes,
+	#[serde(flatten)]
+	pub result: T,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct HexInfo {
+	pub hex: HexBytes,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct PasswordInfo {
+	pub password: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct DiceInfo {
+	pub sides: u32,
+	/// The rolls, from 0 to sides - 1.
+	pub rolls: Vec<u32>,
+}
+
+/// Derive the 64 bytes of entropy for the given application and parameters.
+///
+/// All path elements after the purpose are hardened.
+pub fn derive_entropy(
+	master: &bip32::Xpriv,
+	application: u32,
+	params: &[u32],
+) -> Result<(bip32::DerivationPath, [u8; 64]), &'static str> {
+	let path = [PURPOSE, application].iter().chain(params.iter())
+		.map(|i| bip32::ChildNumber::from_hardened_idx(*i))
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|_| "BIP-85 path element out of range")?;
+	let xpriv = master.derive_priv(&SECP, &path).map_err(|_| "derivation error")?;
+
+	let mut engine = hmac::HmacEngine::<sha512::Hash>::new(b"bip-entropy-from-k");
+	engine.input(&xpriv.private_key.secret_bytes());
+	let entropy = hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+	Ok((path.into(), entropy))
+}
+
+/// The BIP-85 language code of a BIP-39 language.
+pub fn language_code(language: Language) -> Result<u32, &'static str> {
+	Ok(match language {
+		Language::English => 0,
+		Language::Japanese => 1,
+		Language::Korean => 2,
+		Language::Spanish => 3,
+		Language::SimplifiedChinese => 4,
+		Language::TraditionalChinese => 5,
+		Language::French => 6,
+		Language::Italian => 7,
+		Language::Czech => 8,
+		Language::Portuguese => return Err("Portuguese is not supported by BIP-85"),
+	})
+}
+
+/// Derive a BIP-39 mnemonic.
+pub fn mnemonic(
+	master: &bip32::Xpriv,
+	language: Language,
+	words: u32,
+	index: u32,
+	network: Network,
+) -> Result<Bip85Info<MnemonicInfo>, &'static str> {
+	if !crate::bip39::valid_word_count(words as usize) {
+		return Err("invalid number of words");
+	}
+	let params = [language_code(language)?, words, index];
+	let (path, entropy) = derive_entropy(master, APP_BIP39, &params)?;
+	let len = words as usize * 4 / 3;
+	let mnemonic = Mnemonic::from_entropy_in(language, &entropy[0..len])
+		.map_err(|_| "invalid entropy")?;
+	Ok(Bip85Info {
+		path,
+		derived_entropy: entropy[..].into(),
+		result: MnemonicInfo::from_mnemonic_with_passphrase(&mnemonic, "", network),
+	})
+}
+
+/// Derive a WIF private key.
+pub fn wif(
+	master: &bip32::Xpriv,
+	index: u32,
+	network: Network,
+) -> Result<Bip85Info<KeyInfo>, &'static str> {
+	let (path, entropy) = derive_entropy(master, APP_WIF, &[index])?;
+	let key = PrivateKey::from_slice(&entropy[0..32], network)
+		.map_err(|_| "derived an invalid private key")?;
+	Ok(Bip85Info {
+		path,
+		derived_entropy: entropy[..].into(),
+		result: key.get_info(network),
+	})
+}
+
+/// Derive an extended private key.
+pub fn xprv(
+	master: &bip32::Xpriv,
+	index: u32,
+	network: Network,
+) -> Result<Bip85Info<crate::bip32::DerivationInfo>, &'static str> {
+	let (path, entropy) = derive_entropy(master, APP_XPRV, &[index])?;
+	let mut chain_code = [0u8; 32];
+	chain_code.copy_from_slice(&entropy[0..32]);
+	let xpriv = bip32::Xpriv {
+		network: NetworkKind::from(network),
+		depth: 0,
+		parent_fingerprint: Default::default(),
+		child_number: bip32::ChildNumber::from_normal_idx(0).unwrap(),
+		private_key: secp256k1::SecretKey::from_slice(&entropy[32..64])
+			.map_err(|_| "derived an invalid private key")?,
+		chain_code: chain_code.into(),
+	};
+	let xpub = bip32::Xpub::from_priv(&SECP, &xpriv);
+	Ok(Bip85Info {
+		path,
+		derived_entropy: entropy[..].into(),
+		result: crate::bip32::DerivationInfo {
+			network: xpub.network,
+			master_fingerprint: None,
+			path: None,
+			xpriv: Some(xpriv),
+			xpub,
+			chain_code: xpub.chain_code,
+			identifier: xpub.identifier(),
+			fingerprint: xpub.fingerprint(),
+			public_key: xpub.public_key,
+			private_key: Some(xpriv.private_key),
+			addresses: address::Addresses::from_pubkey(
+				&bitcoin::PublicKey::new(xpub.public_key), network,
+			),
+			slip132: None,
+		},
+	})
+}
+
+/// Derive hex entropy of 16 to 64 bytes.
+pub fn hex(
+	master: &bip32::Xpriv,
+	nb_bytes: u32,
+	index: u32,
+) -> Result<Bip85Info<HexInfo>, &'static str> {
+	if !(16..=64).contains(&nb_bytes) {
+		return Err("the number of bytes should be between 16 and 64");
+	}
+	let (path, entropy) = derive_entropy(master, APP_HEX, &[nb_bytes, index])?;
+	Ok(Bip85Info {
+		path,
+		derived_entropy: entropy[..].into(),
+		result: HexInfo {
+			hex: entropy[0..nb_bytes as usize].into(),
+		},
+	})
+}
+
+const BASE85_CHARS: &[u8] =
+	b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// Base85 encoding with the RFC 1924 alphabet, of data with a length that is a multiple of 4.
+fn base85(data: &[u8]) -> String {
+	let mut ret = String::new();
+	for chunk in data.chunks(4) {
+		let mut n = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+		let mut digits = [0u8; 5];
+		for d in digits.iter_mut().rev() {
+			*d = BASE85_CHARS[(n % 85) as usize];
+			n /= 85;
+		}
+		ret.extend(digits.iter().map(|d| *d as char));
+	}
+	ret
+}
+
+/// Derive a base64 password of 20 to 86 characters.
+pub fn password_base64(
+	master: &bip32::Xpriv,
+	length: u32,
+	index: u32,
+) -> Result<Bip85Info<PasswordInfo>, &'static str> {
+	if !(20..=86).contains(&length) {
+		return Err("base64 passwords should be between 20 and 86 characters");
+	}
+	let (path, entropy) = derive_entropy(master, APP_PWD_BASE64, &[length, index])?;
+	Ok(Bip85Info {
+		path,
+		derived_entropy: entropy[..].into(),
+		result: PasswordInfo {
+			password: base64_encode(&entropy, false)[0..length as usize].to_owned(),
+		},
+	})
+}
+
+/// Derive a base85 password of 10 to 80 characters.
+pub fn password_base85(
+	master: &bip32::Xpriv,
+	length: u32,
+	index: u32,
+) -> Result<Bip85Info<PasswordInfo>, &'static str> {
+	if !(10..=80).contains(&length) {
+		return Err("base85 passwords should be between 10 and 80 characters");
+	}
+	let (path, entropy) = derive_entropy(master, APP_PWD_BASE85, &[length, index])?;
+	Ok(Bip85Info {
+		path,
+		derived_entropy: entropy[..].into(),
+		result: PasswordInfo {
+			password: base85(&entropy)[0..length as usize].to_owned(),
+		},
+	})
+}
+
+/// The Keccak-f[1600] round constants.
+const KECCAK_RC: [u64; 24] = [
+	0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+	0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+	0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+	0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+	0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+	0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+const KECCAK_ROTC: [u32; 24] = [
+	1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+const KECCAK_PILN: [usize; 24] = [
+	10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+fn keccak_f(state: &mut [u64; 25]) {
+	for rc in KECCAK_RC.iter() {
+		// theta
+		let mut c = [0u64; 5];
+		for (x, c) in c.iter_mut().enumerate() {
+			*c = state[x] ^ state[x + 5] ^ state[x + 10] ^ state[x + 15] ^ state[x + 20];
+		}
+		for x in 0..5 {
+			let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+			for y in 0..5 {
+				state[5 * y + x] ^= d;
+			}
+		}
+		// rho and pi
+		let mut last = state[1];
+		for (rot, pi) in KECCAK_ROTC.iter().zip(KECCAK_PILN.iter()) {
+			let tmp = state[*pi];
+			state[*pi] = last.rotate_left(*rot);
+			last = tmp;
+		}
+		// chi
+		for row in state.chunks_mut(5) {
+			let mut r = [0u64; 5];
+			r.copy_from_slice(row);
+			for x in 0..5 {
+				row[x] = r[x] ^ (!r[(x + 1) % 5] & r[(x + 2) % 5]);
+			}
+		}
+		// iota
+		state[0] ^= rc;
+	}
+}
+
+/// The BIP-85 deterministic random number generator, SHAKE256 seeded with the entropy.
+pub struct Drng {
+	state: [u64; 25],
+	/// The number of bytes of the current block that have been read.
+	pos: usize,
+}
+
+impl Drng {
+	/// The SHAKE256 rate in bytes.
+	const RATE: usize = 136;
+
+	/// Create a new DRNG, the seed should be shorter than the rate.
+	pub fn new(seed: &[u8]) -> Drng {
+		assert!(seed.len() < Drng::RATE);
+		let mut block = [0u8; Drng::RATE];
+		block[0..seed.len()].copy_from_slice(seed);
+		block[seed.len()] ^= 0x1f;
+		block[Drng::RATE - 1] ^= 0x80;
+
+		let mut state = [0u64; 25];
+		for (lane, bytes) in state.iter_mut().zip(block.chunks(8)) {
+			let mut buf = [0u8; 8];
+			buf.copy_from_slice(bytes);
+			*lane = u64::from_le_bytes(buf);
+		}
+		keccak_f(&mut state);
+		Drng { state, pos: 0 }
+	}
+
+	pub fn read(&mut self, out: &mut [u8]) {
+		for byte in out.iter_mut() {
+			if self.pos == Drng::RATE {
+				keccak_f(&mut self.state);
+				self.pos = 0;
+			}
+			*byte = (self.state[self.pos / 8] >> (8 * (self.pos % 8))) as u8;
+			self.pos += 1;
+		}
+	}
+}
+
+/// Derive dice rolls.
+pub fn dice(
+	master: &bip32::Xpriv,
+	sides: u32,
+	nb_rolls: u32,
+	index: u32,
+) -> Result<Bip85Info<DiceInfo>, &'static str> {
+	if sides < 2 {
+		return Err("dice should have at least 2 sides");
+	}
+	if nb_rolls < 1 {
+		return Err("need at least one roll");
+	}
+	let (path, entropy) = derive_entropy(master, APP_DICE, &[sides, nb_rolls, index])?;
+
+	let bits_per_roll = 32 - (sides - 1).leading_zeros();
+	let bytes_per_roll = bits_per_roll.div_ceil(8) as usize;
+	let mut drng = Drng::new(&entropy);
+	let mut rolls = Vec::with_capacity(nb_rolls as usize);
+	while rolls.len() < nb_rolls as usize {
+		let mut buf = [0u8; 4];
+		drng.read(&mut buf[4 - bytes_per_roll..]);
+		let trial = u32::from_be_bytes(buf) >> (8 * bytes_per_roll as u32 - bits_per_roll);
+		if trial < sides {
+			rolls.push(trial);
+		}
+	}
+	Ok(Bip85Info {
+		path,
+		derived_entropy: entropy[..].into(),
+		result: DiceInfo { sides, rolls },
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::str::FromStr;
+
+	#[test]
+	fn test_vectors() {
+		let master = bip32::Xpriv::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap();
+
+		let (_, entropy) = derive_entropy(&master, 0, &[0]).unwrap();
+		assert_eq!(hex::encode(&entropy[..]), "efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f00b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7");
+
+		let info = mnemonic(&master, Language::English, 12, 0, Network::Bitcoin).unwrap();
+		assert_eq!(info.result.mnemonic, "girl mad pet galaxy egg matter matrix prison refuse sense ordinary nose");
+
+		let info = wif(&master, 0, Network::Bitcoin).unwrap();
+		assert_eq!(info.result.wif_private_key.unwrap().to_string(), "Kzyv4uF39d4Jrw2W7UryTHwZr1zQVNk4dAFyqE6BuMrMh1Za7uhp");
+
+		let info = xprv(&master, 0, Network::Bitcoin).unwrap();
+		assert_eq!(info.result.xpriv.unwrap().to_string(), "xprv9s21ZrQH143K2srSbCSg4m4kLvPMzcWydgmKEnMmoZUurYuBuYG46c6P71UGXMzmriLzCCBvKQWBUv3vPB3m1SATMhp3uEjXHJ42jFg7myX");
+
+		let info = hex(&master, 64, 0).unwrap();
+		assert_eq!(hex::encode(&info.result.hex.0), "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f878555d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c");
+
+		let info = password_base64(&master, 21, 0).unwrap();
+		assert_eq!(info.result.password, "dKLoepugzdVJvdL56ogNV");
+
+		let info = password_base85(&master, 12, 0).unwrap();
+		assert_eq!(info.result.password, "_s`{TW89)i4`");
+	}
+
+	#[test]
+	fn test_dice() {
+		let mut out = [0u8; 32];
+		Drng::new(&[]).read(&mut out);
+		assert_eq!(hex::encode(out), "46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762f");
+
+		let master = bip32::Xpriv::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap();
+		let info = dice(&master, 6, 10, 0).unwrap();
+		assert_eq!(info.result.rolls, vec![1, 0, 0, 2, 0, 1, 5, 5, 2, 4]);
+	}
+}
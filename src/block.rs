@@ -1,18 +1,52 @@
-use bitcoin::{block, Block, BlockHash, Network, TxMerkleNode, Txid};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Read};
+
+use bitcoin::blockdata::constants::{genesis_block, SUBSIDY_HALVING_INTERVAL};
+use bitcoin::consensus::encode::{self, Decodable, VarInt};
+use bitcoin::consensus::Params;
+use bitcoin::opcodes::all::OP_PUSHBYTES_0;
+use bitcoin::opcodes::{Class, ClassifyContext};
+use bitcoin::p2p::Magic;
+use bitcoin::pow::{CompactTarget, Target, Work};
+use bitcoin::script::{read_scriptint, Instruction, PushBytes};
+use bitcoin::{
+	absolute, block, transaction, Amount, Block, BlockHash, Network, OutPoint, ScriptBuf, Sequence,
+	Transaction, TxIn, TxMerkleNode, TxOut, Txid, Weight, Witness,
+};
+use chrono::{DateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::tx::TransactionInfo;
 use crate::{GetInfo, HexBytes};
 
-#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+/// The mask and value of the top bits of a version that signals with BIP-9 version bits.
+const VERSION_BITS_TOP_MASK: i32 = 0xe0000000u32 as i32;
+const VERSION_BITS_TOP_BITS: i32 = 0x20000000;
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 pub struct BlockHeaderInfo {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub block_hash: Option<BlockHash>,
 	pub version: i32,
+	/// The BIP-9 version bits that are set, if the version uses BIP-9 signalling.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub version_bits: Option<Vec<u8>>,
 	pub previous_block_hash: BlockHash,
 	pub merkle_root: TxMerkleNode,
 	pub time: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub timestamp: Option<DateTime<Utc>>,
 	pub bits: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub target: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub difficulty: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub work: Option<String>,
+	/// Whether the block hash meets the target.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub valid_pow: Option<bool>,
 	pub nonce: u32,
 }
 
@@ -21,16 +55,45 @@ impl<'a> GetInfo<BlockHeaderInfo> for block::Header {
 		BlockHeaderInfo {
 			block_hash: Some(self.block_hash()),
 			version: self.version.to_consensus(),
+			version_bits: version_bits(self.version),
 			previous_block_hash: self.prev_blockhash,
 			merkle_root: self.merkle_root,
 			time: self.time,
+			timestamp: Utc.timestamp_opt(self.time as i64, 0).single(),
 			bits: self.bits.to_consensus(),
+			target: Some(hex::encode(self.target().to_be_bytes())),
+			difficulty: Some(self.difficulty_float()),
+			work: Some(hex::encode(self.work().to_be_bytes())),
+			valid_pow: Some(self.validate_pow(self.target()).is_ok()),
 			nonce: self.nonce,
 		}
 	}
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+/// Get the BIP-9 version bits that are set in the version.
+fn version_bits(version: block::Version) -> Option<Vec<u8>> {
+	let version = version.to_consensus();
+	if version & VERSION_BITS_TOP_MASK != VERSION_BITS_TOP_BITS {
+		return None;
+	}
+	Some((0..29).filter(|bit| version & (1 << bit) != 0).collect())
+}
+
+/// Grind the nonce of the header until the block hash meets the target.
+///
+/// Starts from the current nonce and fails when all nonces are tried.
+pub fn grind_header(header: &mut block::Header, target: Target) -> Result<(), &'static str> {
+	let start = header.nonce;
+	while !target.is_met_by(header.block_hash()) {
+		header.nonce = header.nonce.wrapping_add(1);
+		if header.nonce == start {
+			return Err("no nonce meets the target, change the time or merkle root");
+		}
+	}
+	Ok(())
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 pub struct BlockInfo {
 	pub header: BlockHeaderInfo,
 	pub bip34_block_height: Option<u64>,
@@ -54,3 +117,864 @@ impl GetInfo<BlockInfo> for Block {
 		}
 	}
 }
+
+/// The number of blocks of which the median time is the minimum time of the next block.
+const MEDIAN_TIME_SPAN: usize = 11;
+/// The maximum that the first block of a difficulty period can go back in time on testnet4.
+const MAX_TIMEWARP: u32 = 600;
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct InvalidHeaderInfo {
+	/// The position of the header in the given sequence.
+	pub index: usize,
+	pub height: u32,
+	pub block_hash: BlockHash,
+	pub error: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct HeaderChainInfo {
+	pub nb_headers: usize,
+	pub nb_valid_headers: usize,
+	pub start_height: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tip_height: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tip_block_hash: Option<BlockHash>,
+	/// The cumulative work of the valid headers in hex, like Bitcoin Core's chainwork.
+	pub chainwork: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub first_invalid: Option<InvalidHeaderInfo>,
+}
+
+/// Get the bits the header at the given index should have, if they can be known from the
+/// preceding headers.
+fn required_bits(
+	headers: &[block::Header],
+	idx: usize,
+	start_height: u32,
+	params: &Params,
+) -> Option<CompactTarget> {
+	let interval = params.difficulty_adjustment_interval() as usize;
+	let height = start_height as usize + idx;
+	let prev = headers[idx - 1];
+	let limit = params.max_attainable_target.to_compact_lossy();
+
+	if height % interval != 0 {
+		if !params.allow_min_difficulty_blocks {
+			return Some(prev.bits);
+		}
+		// Blocks more than 20 minutes after the previous one can have the minimum difficulty,
+		// others have the difficulty of the last block that didn't use this rule.
+		if headers[idx].time as u64 > prev.time as u64 + 2 * params.pow_target_spacing {
+			return Some(limit);
+		}
+		let mut i = idx - 1;
+		while i > 0 && (start_height as usize + i) % interval != 0 && headers[i].bits == limit {
+			i -= 1;
+		}
+		if i == 0 && start_height != 0 && start_height as usize % interval != 0
+			&& headers[0].bits == limit
+		{
+			return None;
+		}
+		return Some(headers[i].bits);
+	}
+
+	if params.no_pow_retargeting {
+		return Some(prev.bits);
+	}
+	let first = headers[idx.checked_sub(interval)?];
+	let timespan = (prev.time as i64 - first.time as i64).max(0) as u64;
+	// Testnet4 retargets from the first block of the period to avoid the min-difficulty
+	// blocks at the end of a period lowering the difficulty, see BIP-94.
+	let last = if params.network == Network::Testnet4 { first.bits } else { prev.bits };
+	Some(CompactTarget::from_next_work_required(last, timespan, params))
+}
+
+/// Check the BIP-94 timewarp rule on testnet4: the first block of a difficulty period can't be
+/// more than [MAX_TIMEWARP] seconds before its predecessor.
+fn check_timewarp(
+	header: &block::Header,
+	prev: &block::Header,
+	height: usize,
+	params: &Params,
+) -> Result<(), &'static str> {
+	if params.network == Network::Testnet4
+		&& height % params.difficulty_adjustment_interval() as usize == 0
+		&& header.time < prev.time.saturating_sub(MAX_TIMEWARP)
+	{
+		return Err("time is too far before the previous block at a difficulty adjustment");
+	}
+	Ok(())
+}
+
+fn verify_header(
+	headers: &[block::Header],
+	idx: usize,
+	start_height: u32,
+	params: &Params,
+) -> Result<(), &'static str> {
+	let header = headers[idx];
+	if header.target() > params.max_attainable_target {
+		return Err("target is higher than the network's proof-of-work limit");
+	}
+	if header.validate_pow(header.target()).is_err() {
+		return Err("block hash doesn't meet the target");
+	}
+
+	if idx == 0 {
+		if start_height == 0 && header != genesis_block(params).header {
+			return Err("first header is not the network's genesis block");
+		}
+		return Ok(());
+	}
+
+	let prev = headers[idx - 1];
+	if header.prev_blockhash != prev.block_hash() {
+		return Err("previous block hash doesn't match the previous header");
+	}
+
+	if let Some(bits) = required_bits(headers, idx, start_height, params) {
+		if header.bits != bits {
+			return Err("incorrect difficulty bits");
+		}
+	}
+
+	check_timewarp(&header, &prev, start_height as usize + idx, params)?;
+
+	// Only check the median time past if we know all blocks it's calculated from.
+	if idx >= MEDIAN_TIME_SPAN || start_height == 0 {
+		let mut times = headers[idx.saturating_sub(MEDIAN_TIME_SPAN)..idx].iter()
+			.map(|h| h.time).collect::<Vec<_>>();
+		times.sort_unstable();
+		if header.time <= times[times.len() / 2] {
+			return Err("time is not after the median time of the previous blocks");
+		}
+	}
+
+	Ok(())
+}
+
+/// Verify a chain of block headers of which the first has the given height.
+///
+/// The headers are checked for linkage, proof of work, difficulty adjustments and median
+/// time past. Checks that need headers before the first one are skipped.
+pub fn verify_headers(
+	headers: &[block::Header],
+	start_height: u32,
+	network: Network,
+) -> HeaderChainInfo {
+	let params = Params::new(network);
+	let mut chainwork = Work::from_be_bytes([0; 32]);
+	let mut first_invalid = None;
+	for (idx, header) in headers.iter().enumerate() {
+		if let Err(e) = verify_header(headers, idx, start_height, &params) {
+			first_invalid = Some(InvalidHeaderInfo {
+				index: idx,
+				height: start_height + idx as u32,
+				block_hash: header.block_hash(),
+				error: e.to_owned(),
+			});
+			break;
+		}
+		chainwork = chainwork + header.work();
+	}
+
+	let nb_valid = first_invalid.as_ref().map(|i| i.index).unwrap_or(headers.len());
+	HeaderChainInfo {
+		nb_headers: headers.len(),
+		nb_valid_headers: nb_valid,
+		start_height,
+		tip_height: nb_valid.checked_sub(1).map(|i| start_height + i as u32),
+		tip_block_hash: nb_valid.checked_sub(1).map(|i| headers[i].block_hash()),
+		chainwork: hex::encode(chainwork.to_be_bytes()),
+		first_invalid,
+	}
+}
+
+/// The prefix of the output script with the witness commitment.
+const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+/// The halving interval on regtest.
+const REGTEST_HALVING_INTERVAL: u32 = 150;
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct BlockCheckInfo {
+	pub check: String,
+	pub valid: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct BlockVerificationInfo {
+	pub block_hash: BlockHash,
+	pub valid: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub height: Option<u32>,
+	pub weight: u64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub computed_merkle_root: Option<TxMerkleNode>,
+	#[serde(with = "bitcoin::amount::serde::as_sat::opt")]
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub subsidy: Option<Amount>,
+	#[serde(with = "bitcoin::amount::serde::as_sat::opt")]
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub fees: Option<Amount>,
+	#[serde(with = "bitcoin::amount::serde::as_sat::opt")]
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub coinbase_value: Option<Amount>,
+	pub checks: Vec<BlockCheckInfo>,
+}
+
+/// The block subsidy at the given height.
+pub fn block_subsidy(height: u32, network: Network) -> Amount {
+	let interval = match network {
+		Network::Regtest => REGTEST_HALVING_INTERVAL,
+		_ => SUBSIDY_HALVING_INTERVAL,
+	};
+	let halvings = height / interval;
+	if halvings >= 64 {
+		return Amount::ZERO;
+	}
+	Amount::from_sat(Amount::from_int_btc(50).to_sat() >> halvings)
+}
+
+fn check_coinbase(block: &Block) -> Result<(), String> {
+	let coinbase = block.txdata.first().ok_or("block has no transactions")?;
+	if !coinbase.is_coinbase() {
+		return Err("first transaction is not a coinbase".to_owned());
+	}
+	if !(2..=100).contains(&coinbase.input[0].script_sig.len()) {
+		return Err("coinbase scriptSig must be between 2 and 100 bytes".to_owned());
+	}
+	if let Some(i) = block.txdata.iter().skip(1).position(|tx| tx.is_coinbase()) {
+		return Err(format!("transaction {} is also a coinbase", i + 1));
+	}
+	if output_value(coinbase).is_none() {
+		return Err("coinbase output values overflow".to_owned());
+	}
+	Ok(())
+}
+
+/// The total value of the outputs of the transaction, or None if it overflows.
+fn output_value(tx: &Transaction) -> Option<Amount> {
+	tx.output.iter().try_fold(Amount::ZERO, |sum, o| sum.checked_add(o.value))
+}
+
+fn check_witness_commitment(block: &Block) -> Option<Result<(), String>> {
+	let has_witness = block.txdata.iter().any(|tx| tx.input.iter().any(|i| !i.witness.is_empty()));
+	let coinbase = block.txdata.first().filter(|tx| tx.is_coinbase())?;
+	let commitment = coinbase.output.iter().rev().map(|o| o.script_pubkey.as_bytes())
+		.find(|s| s.len() >= 38 && s[0..6] == WITNESS_COMMITMENT_PREFIX);
+	let commitment = match commitment {
+		Some(c) => &c[6..38],
+		None if has_witness => return Some(Err("block has witness data but no commitment".to_owned())),
+		None => return None,
+	};
+
+	let witness = &coinbase.input[0].witness;
+	if witness.len() != 1 || witness.nth(0).expect("one element").len() != 32 {
+		return Some(Err("coinbase witness must be a single 32-byte reserved value".to_owned()));
+	}
+	let root = block.witness_root().expect("block has transactions");
+	let computed = Block::compute_witness_commitment(&root, witness.nth(0).expect("one element"));
+	if computed[..] != commitment[..] {
+		return Some(Err(format!("witness commitment doesn't match, expected {}", computed)));
+	}
+	Some(Ok(()))
+}
+
+/// Read the height at the start of the coinbase scriptSig, regardless of the block version.
+fn coinbase_height(block: &Block) -> Option<u32> {
+	let script_sig = &block.txdata.first()?.input.first()?.script_sig;
+	match script_sig.instructions_minimal().next()?.ok()? {
+		Instruction::PushBytes(b) => u32::try_from(read_scriptint(b.as_bytes()).ok()?).ok(),
+		Instruction::Op(op) => match op.classify(ClassifyContext::Legacy) {
+			Class::PushNum(n) => u32::try_from(n).ok(),
+			_ => None,
+		},
+	}
+}
+
+fn check_bip34_height(block: &Block, height: u32) -> Result<(), String> {
+	let expected = ScriptBuf::builder().push_int(height as i64).into_script();
+	if block.txdata[0].input[0].script_sig.as_bytes().starts_with(expected.as_bytes()) {
+		return Ok(());
+	}
+	match coinbase_height(block) {
+		Some(h) => Err(format!("coinbase has height {} instead of {}", h, height)),
+		None => Err("coinbase doesn't start with the block height".to_owned()),
+	}
+}
+
+/// Calculate the fees of the block from the values of the spent outputs.
+///
+/// Outputs created earlier in the block don't need to be given. Outputs that are spent
+/// twice within the block are reported as a double spend.
+fn block_fees(block: &Block, prevouts: &HashMap<OutPoint, Amount>) -> Result<Amount, String> {
+	let mut available = prevouts.clone();
+	let mut spent = HashSet::new();
+	let mut fees = Amount::ZERO;
+	for tx in &block.txdata {
+		let txid = tx.compute_txid();
+		if !tx.is_coinbase() {
+			let mut input_value = Amount::ZERO;
+			for input in &tx.input {
+				let prevout = input.previous_output;
+				let value = match available.remove(&prevout) {
+					Some(v) => v,
+					None if spent.contains(&prevout) => {
+						return Err(format!("transaction {} double spends {}", txid, prevout));
+					}
+					None => return Err(format!("missing prevout {}", prevout)),
+				};
+				spent.insert(prevout);
+				input_value = input_value.checked_add(value)
+					.ok_or_else(|| format!("input values of transaction {} overflow", txid))?;
+			}
+			let output_value = output_value(tx)
+				.ok_or_else(|| format!("output values of transaction {} overflow", txid))?;
+			let fee = input_value.checked_sub(output_value)
+				.ok_or_else(|| format!("transaction {} spends more than its inputs", txid))?;
+			fees = fees.checked_add(fee).ok_or("block fees overflow")?;
+		}
+		for (vout, output) in tx.output.iter().enumerate() {
+			available.insert(OutPoint::new(txid, vout as u32), output.value);
+		}
+	}
+	Ok(fees)
+}
+
+/// Verify the contents of a block against its header.
+///
+/// When the height is not given, the BIP-34 height from the coinbase of a version 2 or
+/// later block is used, but can't be checked. The coinbase value is only checked when the values of the spent outputs are given.
+pub fn verify_block(
+	block: &Block,
+	height: Option<u32>,
+	prevouts: Option<&HashMap<OutPoint, Amount>>,
+	network: Network,
+) -> BlockVerificationInfo {
+	let params = Params::new(network);
+	let mut checks = Vec::new();
+	let mut add_check = |check: &str, result: Result<(), String>| {
+		checks.push(BlockCheckInfo {
+			check: check.to_owned(),
+			valid: result.is_ok(),
+			error: result.err(),
+		});
+	};
+
+	let computed_merkle_root = block.compute_merkle_root();
+	add_check("merkle-root", match computed_merkle_root {
+		Some(root) if root == block.header.merkle_root => Ok(()),
+		Some(_) => Err("merkle root doesn't match the header".to_owned()),
+		None => Err("block has no transactions".to_owned()),
+	});
+	let coinbase = check_coinbase(block);
+	let coinbase_valid = coinbase.is_ok();
+	add_check("coinbase", coinbase);
+	if let Some(result) = check_witness_commitment(block) {
+		add_check("witness-commitment", result);
+	}
+	let weight = block.weight();
+	add_check("weight", if weight <= Weight::MAX_BLOCK {
+		Ok(())
+	} else {
+		Err(format!("block weight {} exceeds the limit of {}", weight, Weight::MAX_BLOCK))
+	});
+
+	if let Some(height) = height {
+		if coinbase_valid && height >= params.bip34_height {
+			add_check("bip34-height", check_bip34_height(block, height));
+		}
+	}
+	// Only blocks since BIP-34 are known to start the coinbase with the height.
+	let height = height.or_else(|| {
+		if block.header.version.to_consensus() >= 2 {
+			coinbase_height(block)
+		} else {
+			None
+		}
+	});
+
+	let subsidy = height.map(|h| block_subsidy(h, network));
+	let coinbase_value = if coinbase_valid {
+		Some(output_value(&block.txdata[0]).expect("valid coinbase"))
+	} else {
+		None
+	};
+	let mut fees = None;
+	if let (Some(prevouts), true) = (prevouts, coinbase_valid) {
+		let result = match (block_fees(block, prevouts), subsidy) {
+			(Err(e), _) => Err(e),
+			(Ok(_), None) => Err("the block height is unknown".to_owned()),
+			(Ok(f), Some(subsidy)) => {
+				fees = Some(f);
+				let value = coinbase_value.expect("valid coinbase");
+				match subsidy.checked_add(f) {
+					None => Err("the subsidy and fees overflow".to_owned()),
+					Some(max) if value > max => Err(format!(
+						"coinbase value {} exceeds the subsidy and fees of {}",
+						value.to_sat(), max.to_sat(),
+					)),
+					Some(_) => Ok(()),
+				}
+			}
+		};
+		add_check("coinbase-value", result);
+	}
+
+	BlockVerificationInfo {
+		block_hash: block.block_hash(),
+		valid: checks.iter().all(|c| c.valid),
+		height,
+		weight: weight.to_wu(),
+		computed_merkle_root,
+		subsidy,
+		fees,
+		coinbase_value,
+		checks,
+	}
+}
+
+/// Create a coinbase transaction for the block at the given height.
+///
+/// The extra nonce is pushed after the height. With a witness commitment, the coinbase gets
+/// a zero witness reserved value and the commitment output is added when the block is
+/// assembled.
+pub fn create_coinbase(
+	height: u32,
+	script_pubkey: ScriptBuf,
+	value: Amount,
+	extra_nonce: &[u8],
+	witness_commitment: bool,
+) -> Result<Transaction, &'static str> {
+	let mut script_sig = ScriptBuf::builder().push_int(height as i64);
+	script_sig = if extra_nonce.is_empty() {
+		// The scriptSig must be at least two bytes.
+		script_sig.push_opcode(OP_PUSHBYTES_0)
+	} else {
+		let push = <&PushBytes>::try_from(extra_nonce).map_err(|_| "extra nonce is too long")?;
+		script_sig.push_slice(push)
+	};
+	let script_sig = script_sig.into_script();
+	if script_sig.len() > 100 {
+		return Err("coinbase scriptSig can't be longer than 100 bytes");
+	}
+
+	Ok(Transaction {
+		version: transaction::Version::TWO,
+		lock_time: absolute::LockTime::ZERO,
+		input: vec![TxIn {
+			previous_output: OutPoint::null(),
+			script_sig,
+			sequence: Sequence::MAX,
+			witness: if witness_commitment {
+				Witness::from_slice(&[[0u8; 32]])
+			} else {
+				Witness::new()
+			},
+		}],
+		output: vec![TxOut { value, script_pubkey }],
+	})
+}
+
+/// Assemble a block from the coinbase and transactions.
+///
+/// If the coinbase has a witness reserved value, the witness commitment output is added to
+/// it. The merkle root of the header is set.
+pub fn assemble_block(
+	header: block::Header,
+	coinbase: Transaction,
+	transactions: Vec<Transaction>,
+) -> Block {
+	let mut txdata = vec![coinbase];
+	txdata.extend(transactions);
+	let mut block = Block { header, txdata };
+
+	let reserved = block.txdata[0].input[0].witness.nth(0).map(|w| w.to_vec());
+	if let Some(reserved) = reserved {
+		let root = block.witness_root().expect("block has transactions");
+		let commitment = Block::compute_witness_commitment(&root, &reserved);
+		let mut script = WITNESS_COMMITMENT_PREFIX.to_vec();
+		script.extend(&commitment[..]);
+		block.txdata[0].output.push(TxOut { value: Amount::ZERO, script_pubkey: script.into() });
+	}
+	block.header.merkle_root = block.compute_merkle_root().expect("block has transactions");
+	block
+}
+
+/// A reader that undoes the XOR obfuscation of Bitcoin Core's block files.
+pub struct XorReader<R> {
+	inner: R,
+	key: Vec<u8>,
+	pos: usize,
+}
+
+impl<R: io::Read> XorReader<R> {
+	/// Create a new reader with the obfuscation key, an empty key means no obfuscation.
+	pub fn new(inner: R, key: Vec<u8>) -> XorReader<R> {
+		XorReader { inner, key, pos: 0 }
+	}
+}
+
+impl<R: io::Read> io::Read for XorReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		if !self.key.is_empty() {
+			for (i, b) in buf[..n].iter_mut().enumerate() {
+				*b ^= self.key[(self.pos + i) % self.key.len()];
+			}
+		}
+		self.pos += n;
+		Ok(n)
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct BlockTransactionInfo {
+	pub block_hash: BlockHash,
+	/// The position of the transaction in the block.
+	pub index: usize,
+	#[serde(flatten)]
+	pub transaction: TransactionInfo,
+}
+
+/// Read a block from the reader and pass its transactions to the callback one by one.
+fn stream_block<R, F>(reader: &mut R, callback: &mut F) -> Result<(), encode::Error>
+where
+	R: bitcoin::io::Read + ?Sized,
+	F: FnMut(&block::Header, usize, Transaction),
+{
+	let header = block::Header::consensus_decode(reader)?;
+	let nb_txs = VarInt::consensus_decode(reader)?.0;
+	for i in 0..nb_txs as usize {
+		callback(&header, i, Transaction::consensus_decode(reader)?);
+	}
+	Ok(())
+}
+
+/// Stream the transactions of a Bitcoin Core blk*.dat file or of concatenated raw blocks.
+///
+/// Block files are recognized by the network magic at the start. Only a single transaction
+/// is kept in memory at a time. Returns the number of blocks read.
+pub fn stream_blocks<R, F>(reader: R, magic: Magic, mut callback: F) -> Result<usize, String>
+where
+	R: io::Read,
+	F: FnMut(&block::Header, usize, Transaction),
+{
+	let mut reader = io::BufReader::new(reader);
+	if reader.fill_buf().map_err(|e| e.to_string())?.is_empty() {
+		return Ok(0);
+	}
+	let mut block_magic = [0u8; 4];
+	reader.read_exact(&mut block_magic).map_err(|e| e.to_string())?;
+
+	let mut nb_blocks = 0;
+	if block_magic != magic.to_bytes() {
+		// Raw blocks, of which we have already read the start of the first header.
+		let mut reader = io::BufReader::new(io::Cursor::new(block_magic).chain(reader));
+		while !reader.fill_buf().map_err(|e| e.to_string())?.is_empty() {
+			stream_block(&mut reader, &mut callback)
+				.map_err(|e| format!("invalid block {}: {}", nb_blocks, e))?;
+			nb_blocks += 1;
+		}
+		return Ok(nb_blocks);
+	}
+
+	loop {
+		// Block files are preallocated, so they can end with zeros.
+		if block_magic == [0; 4] {
+			break;
+		}
+		if block_magic != magic.to_bytes() {
+			return Err(format!("invalid network magic before block {}", nb_blocks));
+		}
+		let size = u32::consensus_decode(&mut reader).map_err(|e| e.to_string())?;
+		let mut block_reader = (&mut reader).take(size as u64);
+		stream_block(&mut block_reader, &mut callback).and_then(|_| {
+			if block_reader.limit() != 0 {
+				return Err(encode::Error::ParseFailed("block is shorter than its size"));
+			}
+			Ok(())
+		}).map_err(|e| format!("invalid block {}: {}", nb_blocks, e))?;
+		nb_blocks += 1;
+
+		if reader.fill_buf().map_err(|e| e.to_string())?.is_empty() {
+			break;
+		}
+		reader.read_exact(&mut block_magic)
+			.map_err(|e| format!("invalid network magic before block {}: {}", nb_blocks, e))?;
+	}
+	Ok(nb_blocks)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bitcoin::consensus::encode::deserialize;
+	use bitcoin::hashes::Hash;
+
+	/// Mine a regtest header on top of the previous one.
+	fn mine(prev: &block::Header, time: u32) -> block::Header {
+		let mut header = block::Header {
+			prev_blockhash: prev.block_hash(),
+			time,
+			nonce: 0,
+			..*prev
+		};
+		let target = header.target();
+		grind_header(&mut header, target).unwrap();
+		header
+	}
+
+	#[test]
+	fn test_verify_headers() {
+		let headers = [
+			"0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c",
+			"010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299",
+			"010000004860eb18bf1b1620e37e9490fc8a427514416fd75159ab86688e9a8300000000d5fdcc541e25de1c7a5addedf24858b8bb665c9f36ef744ee42c316022c90f9bb0bc6649ffff001d08d2bd61",
+		].iter().map(|h| deserialize(&hex::decode(h).unwrap()).unwrap()).collect::<Vec<block::Header>>();
+		let info = verify_headers(&headers, 0, Network::Bitcoin);
+		assert_eq!(info.first_invalid, None);
+		assert_eq!(info.tip_height, Some(2));
+		assert_eq!(info.chainwork, format!("{:064x}", 0x300030003u64));
+
+		let info = verify_headers(&headers[1..], 0, Network::Bitcoin);
+		assert_eq!(info.first_invalid.unwrap().error, "first header is not the network's genesis block");
+		let info = verify_headers(&[headers[0], headers[2]], 0, Network::Bitcoin);
+		assert_eq!(info.nb_valid_headers, 1);
+
+		let mut chain = vec![genesis_block(Network::Regtest).header];
+		for _ in 0..12 {
+			let time = chain.last().unwrap().time + 1;
+			chain.push(mine(chain.last().unwrap(), time));
+		}
+		assert_eq!(verify_headers(&chain, 0, Network::Regtest).first_invalid, None);
+		let time = chain[7].time;
+		chain.push(mine(chain.last().unwrap(), time));
+		let info = verify_headers(&chain, 0, Network::Regtest);
+		let invalid = info.first_invalid.unwrap();
+		assert_eq!(invalid.height, 13);
+		assert_eq!(invalid.error, "time is not after the median time of the previous blocks");
+	}
+
+	#[test]
+	fn test_header_info() {
+		// Mainnet block 1.
+		let header: block::Header = deserialize(&hex::decode("010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299").unwrap()).unwrap();
+		let info = header.get_info(Network::Bitcoin);
+		assert_eq!(info.version_bits, None);
+		assert_eq!(info.difficulty, Some(1.0));
+		assert_eq!(info.valid_pow, Some(true));
+		assert_eq!(info.work, Some(format!("{:064x}", 0x100010001u64)));
+		assert_eq!(info.timestamp.unwrap().to_rfc3339(), "2009-01-09T02:54:25+00:00");
+		let version = block::Version::from_consensus(0x20000005);
+		assert_eq!(version_bits(version), Some(vec![0, 2]));
+
+		let mut header = genesis_block(Network::Regtest).header;
+		header.nonce = 0;
+		let target = header.target();
+		grind_header(&mut header, target).unwrap();
+		assert_eq!(header.get_info(Network::Regtest).valid_pow, Some(true));
+	}
+
+	#[test]
+	fn test_verify_block_pre_bip34() {
+		// Mainnet block 1, of which the coinbase doesn't start with the height.
+		let block = deserialize::<Block>(&hex::decode("010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e362990101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704ffff001d0104ffffffff0100f2052a0100000043410496b538e853519c726a2c91e61ec11600ae1390813a627c66fb8be7947be63c52da7589379515d4e0a604f8141781e62294721166bf621e73a82cbf2342c858eeac00000000").unwrap()).unwrap();
+		let prevouts = HashMap::new();
+		let info = verify_block(&block, None, None, Network::Bitcoin);
+		assert!(info.valid, "{:?}", info.checks);
+		assert_eq!((info.height, info.subsidy), (None, None));
+
+		let info = verify_block(&block, Some(1), Some(&prevouts), Network::Bitcoin);
+		assert!(info.valid, "{:?}", info.checks);
+		assert_eq!(info.subsidy, Some(Amount::from_int_btc(50)));
+	}
+
+	#[test]
+	fn test_verify_block() {
+		let spent = OutPoint::new(Txid::all_zeros(), 0);
+		let tx = Transaction {
+			version: transaction::Version::TWO,
+			lock_time: absolute::LockTime::ZERO,
+			input: vec![TxIn {
+				previous_output: spent,
+				script_sig: ScriptBuf::new(),
+				sequence: Sequence::MAX,
+				witness: Witness::from_slice(&[[1u8; 33]]),
+			}],
+			output: vec![TxOut { value: Amount::from_sat(9_000), script_pubkey: ScriptBuf::new() }],
+		};
+		let coinbase = Transaction {
+			version: transaction::Version::TWO,
+			lock_time: absolute::LockTime::ZERO,
+			input: vec![TxIn {
+				previous_output: OutPoint::null(),
+				script_sig: ScriptBuf::from_hex("022c01").unwrap(),
+				sequence: Sequence::MAX,
+				witness: Witness::from_slice(&[[0u8; 32]]),
+			}],
+			output: vec![TxOut {
+				value: Amount::from_int_btc(50) + Amount::from_sat(1_000),
+				script_pubkey: ScriptBuf::new(),
+			}],
+		};
+		let mut block = Block {
+			header: genesis_block(Network::Signet).header,
+			txdata: vec![coinbase, tx],
+		};
+		block.header.version = block::Version::TWO;
+		let commitment = Block::compute_witness_commitment(&block.witness_root().unwrap(), &[0; 32]);
+		let mut script = WITNESS_COMMITMENT_PREFIX.to_vec();
+		script.extend(&commitment[..]);
+		block.txdata[0].output.push(TxOut { value: Amount::ZERO, script_pubkey: script.into() });
+		block.header.merkle_root = block.compute_merkle_root().unwrap();
+
+		let prevouts = vec![(spent, Amount::from_sat(10_000))].into_iter().collect();
+		let info = verify_block(&block, Some(300), Some(&prevouts), Network::Signet);
+		assert!(info.valid, "{:?}", info.checks);
+		assert_eq!(info.checks.len(), 6);
+		assert_eq!(info.fees, Some(Amount::from_sat(1_000)));
+
+		let info = verify_block(&block, Some(301), None, Network::Signet);
+		assert_eq!(info.checks.iter().filter(|c| !c.valid).map(|c| &c.check[..]).collect::<Vec<_>>(), vec!["bip34-height"]);
+
+		block.txdata[0].output[0].value += Amount::from_sat(1);
+		block.txdata[1].input[0].witness = Witness::from_slice(&[[2u8; 33]]);
+		let info = verify_block(&block, None, Some(&prevouts), Network::Signet);
+		let invalid = info.checks.iter().filter(|c| !c.valid).map(|c| &c.check[..]).collect::<Vec<_>>();
+		assert_eq!(invalid, vec!["merkle-root", "witness-commitment", "coinbase-value"]);
+		assert_eq!(info.height, Some(300));
+
+		// Spending the same output twice and values that overflow are failed checks.
+		let tx = block.txdata[1].clone();
+		block.txdata.push(tx.clone());
+		let err = block_fees(&block, &prevouts).unwrap_err();
+		assert!(err.contains("double spends"), "{}", err);
+		block.txdata.pop();
+		block.txdata[1].input.push(TxIn { previous_output: OutPoint::new(Txid::all_zeros(), 1), ..tx.input[0].clone() });
+		let mut prevouts = prevouts.clone();
+		prevouts.insert(spent, Amount::MAX);
+		prevouts.insert(OutPoint::new(Txid::all_zeros(), 1), Amount::MAX);
+		let err = block_fees(&block, &prevouts).unwrap_err();
+		assert!(err.contains("overflow"), "{}", err);
+		block.txdata[0].output[0].value = Amount::MAX;
+		block.txdata[0].output[1].value = Amount::ONE_SAT;
+		let info = verify_block(&block, None, Some(&prevouts), Network::Signet);
+		assert!(info.checks.iter().any(|c| c.check == "coinbase" && !c.valid));
+	}
+
+	#[test]
+	fn test_block_template() {
+		let spent = OutPoint::new(Txid::all_zeros(), 0);
+		let tx = Transaction {
+			version: transaction::Version::TWO,
+			lock_time: absolute::LockTime::ZERO,
+			input: vec![TxIn {
+				previous_output: spent,
+				script_sig: ScriptBuf::new(),
+				sequence: Sequence::MAX,
+				witness: Witness::from_slice(&[[1u8; 33]]),
+			}],
+			output: vec![TxOut { value: Amount::from_sat(9_000), script_pubkey: ScriptBuf::new() }],
+		};
+		let value = block_subsidy(300, Network::Signet) + Amount::from_sat(1_000);
+		let coinbase = create_coinbase(300, ScriptBuf::new(), value, &[1, 2], true).unwrap();
+
+		let mut header = genesis_block(Network::Signet).header;
+		header.version = block::Version::TWO;
+		let block = assemble_block(header, coinbase, vec![tx]);
+		assert_eq!(block.txdata.len(), 2);
+		assert_eq!(coinbase_height(&block), Some(300));
+		assert_eq!(block.header.merkle_root, block.compute_merkle_root().unwrap());
+
+		let prevouts = vec![(spent, Amount::from_sat(10_000))].into_iter().collect();
+		let info = verify_block(&block, Some(300), Some(&prevouts), Network::Signet);
+		assert!(info.valid, "{:?}", info.checks);
+	}
+
+	#[test]
+	fn test_testnet4_difficulty() {
+		let params = Params::new(Network::Testnet4);
+		let limit = params.max_attainable_target.to_compact_lossy();
+		let bits = CompactTarget::from_consensus(0x1c3fffff);
+		let mut headers = (0..2017u32).map(|i| {
+			let mut header = genesis_block(Network::Testnet4).header;
+			header.time = 1_714_777_860 + i * 600;
+			header.bits = bits;
+			header
+		}).collect::<Vec<_>>();
+
+		// Within a period, a block more than 20 minutes after its predecessor may use the minimum
+		// difficulty and the next ones return to the last regular difficulty.
+		headers[10].time += 1_201;
+		headers[10].bits = limit;
+		assert_eq!(required_bits(&headers[..11], 10, 0, &params), Some(limit));
+		headers[11].time = headers[10].time + 600;
+		assert_eq!(required_bits(&headers[..12], 11, 0, &params), Some(bits));
+		headers[11].time = headers[10].time + 1_200;
+		assert_eq!(required_bits(&headers[..12], 11, 0, &params), Some(bits));
+
+		// A retarget is based on the first block of the period, not the last one, which is a
+		// minimum difficulty block here. Taking twice the expected time doubles the target.
+		headers[2015].bits = limit;
+		headers[2015].time = headers[0].time + 2 * 14 * 24 * 60 * 60;
+		let next = required_bits(&headers, 2016, 0, &params).unwrap();
+		assert_eq!(next, CompactTarget::from_consensus(0x1c7ffffe));
+
+		// Timewarp: the first block of a period can be at most 10 minutes before its predecessor.
+		let prev = headers[2015];
+		let mut header = headers[2016];
+		header.time = prev.time - 600;
+		assert!(check_timewarp(&header, &prev, 2016, &params).is_ok());
+		header.time = prev.time - 601;
+		assert!(check_timewarp(&header, &prev, 2016, &params).is_err());
+		assert!(check_timewarp(&header, &prev, 2017, &params).is_ok());
+		assert!(check_timewarp(&header, &prev, 2016, &Params::new(Network::Testnet)).is_ok());
+	}
+
+	#[test]
+	fn test_stream_blocks() {
+		let block = genesis_block(Network::Regtest);
+		let raw = bitcoin::consensus::encode::serialize(&block);
+		let magic = Network::Regtest.magic();
+		let mut file = Vec::new();
+		for _ in 0..2 {
+			file.extend(magic.to_bytes());
+			file.extend((raw.len() as u32).to_le_bytes());
+			file.extend(&raw);
+		}
+		file.extend([0; 8]);
+		let key = vec![1, 2, 3, 4, 5, 6, 7, 8];
+		let obfuscated = file.iter().enumerate().map(|(i, b)| b ^ key[i % 8]).collect::<Vec<_>>();
+
+		let mut txids = Vec::new();
+		let reader = XorReader::new(&obfuscated[..], key);
+		let nb = stream_blocks(reader, magic, |_, _, tx| txids.push(tx.compute_txid())).unwrap();
+		assert_eq!(nb, 2);
+		assert_eq!(txids, vec![block.txdata[0].compute_txid(); 2]);
+
+		let nb = stream_blocks(&raw[..], magic, |h, i, _| assert_eq!((*h, i), (block.header, 0)));
+		assert_eq!(nb, Ok(1));
+
+		// Reads can return fewer bytes than the magic, like from a pipe.
+		struct ByteReader<'a>(&'a [u8]);
+		impl<'a> io::Read for ByteReader<'a> {
+			fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+				let len = std::cmp::min(1, buf.len());
+				self.0.read(&mut buf[..len])
+			}
+		}
+		let nb = stream_blocks(io::BufReader::with_capacity(3, ByteReader(&file)), magic, |_, _, _| {});
+		assert_eq!(nb, Ok(2));
+		let nb = stream_blocks(io::BufReader::with_capacity(3, ByteReader(&raw)), magic, |_, _, _| {});
+		assert_eq!(nb, Ok(1));
+		assert!(stream_blocks(&file[..file.len() - 10], magic, |_, _, _| {}).is_err());
+		assert_eq!(stream_blocks(&[][..], magic, |_, _, _| {}), Ok(0));
+	}
+}
@@ -0,0 +1,175 @@
+//! Cryptographic primitives for wallet seeds and key encryption.
+//!
+//! These are thin wrappers around the RustCrypto implementations, so that the
+//! rest of the crate doesn't have to deal with their generic interfaces.
+
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use blake2::digest::{Update, VariableOutput};
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::ChaCha20Poly1305;
+use sha2::{Sha256, Sha512};
+
+/// HKDF with HMAC-SHA256.
+pub fn hkdf_sha256(salt: &[u8], ikm: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+	let mut ret = vec![0u8; len];
+	hkdf::Hkdf::<Sha256>::new(Some(salt), ikm).expand(info, &mut ret)
+		.expect("HKDF output length is limited to 255 blocks");
+	ret
+}
+
+/// PBKDF2 with HMAC-SHA256.
+pub fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+	let mut ret = vec![0u8; len];
+	pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, iterations, &mut ret);
+	ret
+}
+
+/// PBKDF2 with HMAC-SHA512.
+pub fn pbkdf2_sha512(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+	let mut ret = vec![0u8; len];
+	pbkdf2::pbkdf2_hmac::<Sha512>(password, salt, iterations, &mut ret);
+	ret
+}
+
+/// BLAKE2b with the given output length in bytes, without a key.
+pub fn blake2b(out_len: usize, data: &[u8]) -> Vec<u8> {
+	let mut hasher = blake2::Blake2bVar::new(out_len).expect("invalid BLAKE2b output length");
+	hasher.update(data);
+	let mut ret = vec![0u8; out_len];
+	hasher.finalize_variable(&mut ret).expect("output has the requested length");
+	ret
+}
+
+/// scrypt with N = 2^log_n.
+pub fn scrypt(password: &[u8], salt: &[u8], log_n: u8, r: usize, p: usize, len: usize) -> Vec<u8> {
+	let params = scrypt::Params::new(log_n, r as u32, p as u32, len.clamp(10, 64))
+		.expect("invalid scrypt parameters");
+	let mut ret = vec![0u8; len];
+	scrypt::scrypt(password, salt, &params, &mut ret).expect("invalid scrypt output length");
+	ret
+}
+
+/// CRC-32C, with the Castagnoli polynomial.
+pub fn crc32c(data: &[u8]) -> u32 {
+	let mut crc = !0u32;
+	for byte in data {
+		crc ^= *byte as u32;
+		for _ in 0..8 {
+			crc = (crc >> 1) ^ (0x82f63b78 & (crc & 1).wrapping_neg());
+		}
+	}
+	!crc
+}
+
+/// A single AES encryption round, like the AESENC instruction.
+pub fn aes_round(state: &mut [u8; 16], round_key: &[u8; 16]) {
+	aes::hazmat::cipher_round(state.into(), round_key.into());
+}
+
+/// AES-256 block encryption and decryption.
+pub struct Aes256(aes::Aes256);
+
+impl Aes256 {
+	pub fn new(key: &[u8; 32]) -> Aes256 {
+		Aes256(aes::Aes256::new(key.into()))
+	}
+
+	pub fn encrypt_block(&self, block: &mut [u8; 16]) {
+		self.0.encrypt_block(block.into());
+	}
+
+	pub fn decrypt_block(&self, block: &mut [u8; 16]) {
+		self.0.decrypt_block(block.into());
+	}
+}
+
+/// ChaCha20-Poly1305 encryption without associated data, the tag is appended.
+pub fn chacha20poly1305_seal(key: &[u8; 32], nonce: &[u8; 12], plaintext: &[u8]) -> Vec<u8> {
+	ChaCha20Poly1305::new(key.into()).encrypt(nonce.into(), plaintext)
+		.expect("plaintext is not too long")
+}
+
+/// ChaCha20-Poly1305 decryption without associated data, returns None if the tag is invalid.
+pub fn chacha20poly1305_open(key: &[u8; 32], nonce: &[u8; 12], data: &[u8]) -> Option<Vec<u8>> {
+	ChaCha20Poly1305::new(key.into()).decrypt(nonce.into(), data).ok()
+}
+
+/// Base64 encoding, optionally with padding.
+pub fn base64_encode(data: &[u8], padding: bool) -> String {
+	let config = if padding { base64::STANDARD } else { base64::STANDARD_NO_PAD };
+	base64::encode_config(data, config)
+}
+
+/// Base64 decoding, padding is optional but non-zero trailing bits are rejected.
+pub fn base64_decode(s: &str) -> Option<Vec<u8>> {
+	base64::decode_config(s.trim_end_matches('='), base64::STANDARD_NO_PAD).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_primitives() {
+		assert_eq!(
+			hex::encode(blake2b(64, b"abc")),
+			"ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+			7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+		);
+		assert_eq!(
+			hex::encode(scrypt(b"", b"", 4, 1, 1, 64)),
+			"77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442\
+			fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906",
+		);
+		assert_eq!(crc32c(b"123456789"), 0xe3069283);
+
+		// The first round of FIPS-197 appendix B.
+		let mut state = [0u8; 16];
+		state.copy_from_slice(&hex::decode("193de3bea0f4e22b9ac68d2ae9f84808").unwrap());
+		let mut key = [0u8; 16];
+		key.copy_from_slice(&hex::decode("a0fafe1788542cb123a339392a6c7605").unwrap());
+		aes_round(&mut state, &key);
+		assert_eq!(hex::encode(state), "a49c7ff2689f352b6b5bea43026a5049");
+
+		let mut key = [0u8; 32];
+		key.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+		let aes = Aes256::new(&key);
+		let mut block = [0u8; 16];
+		block.copy_from_slice(&hex::decode("00112233445566778899aabbccddeeff").unwrap());
+		aes.encrypt_block(&mut block);
+		assert_eq!(hex::encode(block), "8ea2b7ca516745bfeafc49904b496089");
+		aes.decrypt_block(&mut block);
+		assert_eq!(hex::encode(block), "00112233445566778899aabbccddeeff");
+
+		let mut nonce = [0u8; 12];
+		nonce.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+		let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only \
+			one tip for the future, sunscreen would be it.";
+		let sealed = chacha20poly1305_seal(&key, &nonce, &plaintext[..]);
+		assert_eq!(
+			hex::encode(&sealed),
+			"c59a6c694c648521d9e71fb4fd737a0fac1dd789711bcb9992ff4ae516acc74f\
+			9fa04395c63988a10d123fbe45ddaf4011f1c510dd47defd77caf97eff3abeb9\
+			1714c5ed8a7a49c9b07f3d9c7e13aed1aeb62212091f844b7c32dc2eab086ccc\
+			2eac099699f9509bc4379797c0f497e99d41a39b46d8c6b5adf22caab9bee44a9b03",
+		);
+		assert_eq!(chacha20poly1305_open(&key, &nonce, &sealed).unwrap(), plaintext.to_vec());
+		assert!(chacha20poly1305_open(&key, &[0; 12], &sealed).is_none());
+
+		assert_eq!(
+			hex::encode(pbkdf2_sha256(b"password", b"salt", 2, 20)),
+			"ae4d0c95af6b46d32d0adff928f06dd02a303f8e",
+		);
+		assert_eq!(
+			hex::encode(hkdf_sha256(b"salt", b"ikm", b"info", 32)),
+			"fe8f9615d2374c0d17f77d1aeaf408c2e75fe0466073d0def23c733e2f862dfd",
+		);
+
+		assert_eq!(base64_encode(b"hal", true), "aGFs");
+		assert_eq!(base64_encode(b"hal!", true), "aGFsIQ==");
+		assert_eq!(base64_encode(b"hal!", false), "aGFsIQ");
+		assert_eq!(base64_decode("aGFsIQ==").unwrap(), b"hal!".to_vec());
+		assert_eq!(base64_decode("aGFsIQ").unwrap(), b"hal!".to_vec());
+		assert!(base64_decode("aGFsIR").is_none());
+	}
+}
@@ -0,0 +1,193 @@
+//! Electrum v2 seeds.
+//!
+//! Electrum seeds don't use the BIP-39 checksum, the seed version is instead encoded in the
+//! HMAC of the mnemonic. Old Electrum v1 seeds are not supported.
+
+use bitcoin::bip32;
+use bitcoin::hashes::{hmac, sha512, Hash, HashEngine};
+use bitcoin::{Network, NetworkKind};
+use serde::{Deserialize, Serialize};
+
+use crate::bip32::{Slip132Version, SLIP132_VERSIONS};
+use crate::bip39::SeedInfo;
+use crate::crypto::pbkdf2_sha512;
+use crate::SECP;
+
+/// The number of PBKDF2 iterations to derive the seed.
+const PBKDF2_ROUNDS: u32 = 2048;
+
+/// The Electrum v2 seed types with their version prefix.
+const SEED_TYPES: [(&str, &str); 4] = [
+	("standard", "01"),
+	("segwit", "100"),
+	("2fa", "101"),
+	("2fa-segwit", "102"),
+];
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ElectrumKeystoreInfo {
+	/// The derivation path of the keystore from the seed used for it.
+	pub path: bip32::DerivationPath,
+	pub xpriv: String,
+	pub xpub: String,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ElectrumSeedInfo {
+	pub mnemonic: String,
+	pub seed_type: String,
+	pub passphrase: String,
+	pub seed: SeedInfo,
+	/// The keystores as Electrum creates them, the 2fa seeds have the two keys of
+	/// the user, the third key is held by TrustedCoin.
+	pub keystores: Vec<ElectrumKeystoreInfo>,
+}
+
+/// Normalize the text like Electrum does.
+///
+/// Only ASCII text is supported, because Electrum's Unicode normalization and accent
+/// removal is not implemented.
+fn normalize(text: &str) -> Result<String, &'static str> {
+	if !text.is_ascii() {
+		return Err("only ASCII Electrum mnemonics and passphrases are supported");
+	}
+	Ok(text.to_ascii_lowercase().split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Get the Electrum v2 seed type of a normalized mnemonic.
+fn seed_type(mnemonic: &str) -> Option<&'static str> {
+	let mut engine = hmac::HmacEngine::<sha512::Hash>::new(b"Seed version");
+	engine.input(mnemonic.as_bytes());
+	let hash = hex::encode(&hmac::Hmac::<sha512::Hash>::from_engine(engine)[..]);
+	SEED_TYPES.iter().find(|(_, prefix)| hash.starts_with(prefix)).map(|(t, _)| *t)
+}
+
+/// Derive the 64-byte seed of a normalized mnemonic.
+fn to_seed(mnemonic: &str, passphrase: &str) -> Vec<u8> {
+	let salt = format!("electrum{}", passphrase);
+	pbkdf2_sha512(mnemonic.as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS, 64)
+}
+
+fn keystore(
+	seed: &[u8],
+	path: &str,
+	version: &Slip132Version,
+	network: Network,
+) -> ElectrumKeystoreInfo {
+	let path = path.parse::<bip32::DerivationPath>().expect("valid path");
+	let master = bip32::Xpriv::new_master(network, seed).expect("valid seed");
+	let xpriv = master.derive_priv(&SECP, &path).expect("valid path");
+	let xpub = bip32::Xpub::from_priv(&SECP, &xpriv);
+	ElectrumKeystoreInfo {
+		path,
+		xpriv: version.encode_xpriv(&xpriv).expect("same network"),
+		xpub: version.encode_xpub(&xpub).expect("same network"),
+	}
+}
+
+/// Decode an Electrum v2 mnemonic and derive its seed and keystores.
+pub fn decode(
+	mnemonic: &str,
+	passphrase: &str,
+	network: Network,
+) -> Result<ElectrumSeedInfo, &'static str> {
+	let mnemonic = normalize(mnemonic)?;
+	let passphrase = normalize(passphrase)?;
+	let seed_type = seed_type(&mnemonic).ok_or("not an Electrum v2 seed")?;
+
+	let kind = NetworkKind::from(network);
+	// Electrum uses the standard xpub version for p2pkh keystores.
+	let standard = Slip132Version::standard(kind);
+	let version = |script_type: &str| {
+		SLIP132_VERSIONS.iter().find(|v| v.network == kind && v.script_type == script_type)
+			.expect("all script types present")
+	};
+	let seed = to_seed(&mnemonic, &passphrase);
+	let keystores = match seed_type {
+		"standard" => vec![keystore(&seed, "m", standard, network)],
+		"segwit" => vec![keystore(&seed, "m/0'", version("p2wpkh"), network)],
+		"2fa" if mnemonic.split(' ').count() >= 20 => {
+			// Old 2fa seeds consist of two seeds of which the first has 12 words.
+			if !passphrase.is_empty() {
+				return Err("old 2fa seeds can't have a passphrase");
+			}
+			let words = mnemonic.split(' ').collect::<Vec<_>>();
+			vec![
+				keystore(&to_seed(&words[..12].join(" "), ""), "m", standard, network),
+				keystore(&to_seed(&words[12..].join(" "), ""), "m", standard, network),
+			]
+		}
+		"2fa" => vec![
+			keystore(&seed, "m/0'", standard, network),
+			keystore(&seed, "m/1'", standard, network),
+		],
+		"2fa-segwit" => vec![
+			keystore(&seed, "m/0'", version("p2wsh-multisig"), network),
+			keystore(&seed, "m/1'", version("p2wsh-multisig"), network),
+		],
+		_ => unreachable!(),
+	};
+
+	Ok(ElectrumSeedInfo {
+		seed_type: seed_type.to_owned(),
+		seed: SeedInfo::from_seed(&seed, network),
+		mnemonic,
+		passphrase,
+		keystores,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_electrum_seed() {
+		assert_eq!(
+			hex::encode(to_seed("foobar", "none")),
+			"741b72fd15effece6bfe5a26a52184f66811bd2be363190e07a42cca442b1a5bb22b3ad0eb338197287e6d314866c7fba863ac65d3f156087a5052ebc7157fce",
+		);
+
+		let info = decode(
+			"cycle rocket west magnet parrot shuffle foot correct salt library feed song",
+			"", Network::Bitcoin,
+		).unwrap();
+		assert_eq!(info.seed_type, "standard");
+		assert_eq!(
+			info.keystores[0].xpub,
+			"xpub661MyMwAqRbcFWohJWt7PHsFEJfZAvw9ZxwQoDa4SoMgsDDM1T7WK3u9E4edkC4ugRnZ8E4xDZRpk8Rnts3Nbt97dPwT52CwBdDWroaZf8U",
+		);
+
+		let info = decode(
+			"bitter grass shiver impose acquire brush forget axis eager alone wine silver",
+			"", Network::Bitcoin,
+		).unwrap();
+		assert_eq!(info.seed_type, "segwit");
+		assert_eq!(info.keystores[0].path, "m/0'".parse().unwrap());
+		assert_eq!(
+			info.keystores[0].xpub,
+			"zpub6nsHdRuY92FsMKdbn9BfjBCG6X8pyhCibNP6uDvpnw2cyrVhecvHRMa3Ne8kdJZxjxgwnpbHLkcR4bfnhHy6auHPJyDTQ3kianeuVLdkCYQ",
+		);
+
+		let info = decode(
+			"kiss live scene rude gate step hip quarter bunker oxygen motor glove",
+			"", Network::Bitcoin,
+		).unwrap();
+		assert_eq!(info.seed_type, "2fa");
+		assert_eq!(info.keystores.len(), 2);
+		assert_eq!(info.keystores[0].path, "m/0'".parse().unwrap());
+		assert_eq!(
+			info.keystores[0].xpub,
+			"xpub68qvwUg8sewQvcUgwxuTYr9rrgu5nfn6BwajQpYT9p8fXWxdCRHpN86UWruWJAD1ede8Sv8ERrTa22Gyc4SBfm7zFpcyoVWVBKCVwnw6s1J",
+		);
+		assert_eq!(info.keystores[1].path, "m/1'".parse().unwrap());
+		assert_eq!(
+			info.keystores[1].xpub,
+			"xpub68qvwUg8sewQxoXBXCTLrFKbHkx3QLY5M63EiejxTQRKSFPHjmWCwK8byvZMM2wZNYA3SmxXoma3M1zxhGESHZwtB7SwrxRgKXAG8dCD2eS",
+		);
+
+		let bip39 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+			abandon about";
+		assert!(decode(bip39, "", Network::Bitcoin).is_err());
+	}
+}
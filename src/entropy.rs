@@ -0,0 +1,163 @@
+use bitcoin::hashes::{sha256, Hash};
+
+/// Entropy from a physical source, like dice rolls, coin flips or playing cards.
+///
+/// The entropy is the SHA-256 hash of the normalized input. For six-sided dice, the
+/// normalized input is the string of rolls from 1 to 6 as they are entered, so the hash
+/// matches the dice roll entropy of Coldcard. The normalized coin flips and cards are
+/// specific to hal.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PhysicalEntropy {
+	/// The normalized input that is hashed.
+	pub data: String,
+	/// The number of values in the input.
+	pub count: usize,
+	/// The number of bits of entropy in the input.
+	pub bits: f64,
+}
+
+impl PhysicalEntropy {
+	/// Whether the input contains enough entropy for the given number of bits.
+	///
+	/// The entropy is rounded to a whole number of bits, so that 99 rolls of a six-sided dice
+	/// are sufficient for 256 bits, as with other tools.
+	pub fn is_sufficient(&self, bits: usize) -> bool {
+		self.bits.round() >= bits as f64
+	}
+
+	/// The SHA-256 hash of the normalized input.
+	pub fn hash(&self) -> sha256::Hash {
+		sha256::Hash::hash(self.data.as_bytes())
+	}
+}
+
+/// Whether a character separates values in the input.
+fn is_separator(c: char) -> bool {
+	c.is_whitespace() || c == ',' || c == '-'
+}
+
+/// Parse dice rolls from 1 to the number of sides.
+///
+/// For dice with up to 9 sides, the rolls can be given without separators.
+pub fn dice(rolls: &str, sides: u32) -> Result<PhysicalEntropy, &'static str> {
+	if sides < 2 {
+		return Err("dice should have at least 2 sides");
+	}
+	let values = if sides <= 9 {
+		rolls.chars().filter(|c| !is_separator(*c)).map(|c| {
+			c.to_digit(10).ok_or("invalid dice roll")
+		}).collect::<Result<Vec<_>, _>>()?
+	} else {
+		rolls.split(is_separator).filter(|s| !s.is_empty()).map(|s| {
+			s.parse::<u32>().map_err(|_| "invalid dice roll")
+		}).collect::<Result<Vec<_>, _>>()?
+	};
+	if values.iter().any(|v| *v < 1 || *v > sides) {
+		return Err("dice rolls should be between 1 and the number of sides");
+	}
+	let strings = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+	Ok(PhysicalEntropy {
+		data: strings.join(if sides <= 9 { "" } else { " " }),
+		count: values.len(),
+		bits: values.len() as f64 * (sides as f64).log2(),
+	})
+}
+
+/// Parse coin flips, as h/t or 1/0.
+pub fn coins(flips: &str) -> Result<PhysicalEntropy, &'static str> {
+	let data = flips.chars().filter(|c| !is_separator(*c)).map(|c| {
+		match c.to_ascii_lowercase() {
+			'h' | '1' => Ok('1'),
+			't' | '0' => Ok('0'),
+			_ => Err("coin flips should be h or t, or 1 or 0"),
+		}
+	}).collect::<Result<String, _>>()?;
+	Ok(PhysicalEntropy {
+		count: data.len(),
+		bits: data.len() as f64,
+		data,
+	})
+}
+
+/// Parse playing cards from a 52-card deck, like "AS 10H QD".
+///
+/// Ranks are A, 2 to 10 (or T), J, Q and K and suits are S, H, D and C. Without
+/// replacement, the cards are drawn from a single shuffled deck, so they must all be
+/// different. With replacement, each card is put back and the deck reshuffled before
+/// the next draw, so every card carries log2(52) bits.
+pub fn cards(cards: &str, replacement: bool) -> Result<PhysicalEntropy, &'static str> {
+	let chars = cards.chars().filter(|c| !is_separator(*c))
+		.map(|c| c.to_ascii_uppercase())
+		.collect::<Vec<_>>();
+	let mut normalized = Vec::new();
+	let mut i = 0;
+	while i < chars.len() {
+		let rank = match chars[i] {
+			'1' if chars.get(i + 1) == Some(&'0') => {
+				i += 1;
+				'T'
+			}
+			c @ ('A' | '2'..='9' | 'T' | 'J' | 'Q' | 'K') => c,
+			_ => return Err("invalid card rank"),
+		};
+		let suit = match chars.get(i + 1) {
+			Some(c @ ('S' | 'H' | 'D' | 'C')) => *c,
+			_ => return Err("invalid card suit"),
+		};
+		normalized.push(format!("{}{}", rank, suit));
+		i += 2;
+	}
+
+	let bits = if replacement {
+		normalized.len() as f64 * 52f64.log2()
+	} else {
+		let mut unique = normalized.clone();
+		unique.sort();
+		unique.dedup();
+		if unique.len() != normalized.len() {
+			return Err("cards drawn without replacement should all be different");
+		}
+		(0..normalized.len()).map(|i| ((52 - i) as f64).log2()).sum()
+	};
+	Ok(PhysicalEntropy {
+		data: normalized.concat(),
+		count: normalized.len(),
+		bits,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_physical_entropy() {
+		let rolls = "1234561234".repeat(10);
+		let e = dice(&rolls[0..99], 6).unwrap();
+		assert_eq!(e.count, 99);
+		assert!(e.is_sufficient(256));
+		assert!(!dice(&rolls[0..98], 6).unwrap().is_sufficient(256));
+		assert_eq!(e.hash(), sha256::Hash::hash(&rolls.as_bytes()[0..99]));
+		assert_eq!(dice("1, 2 6", 6).unwrap().data, "126");
+		assert_eq!(dice("12 20 1", 20).unwrap().data, "12 20 1");
+		assert!(dice("7", 6).is_err());
+
+		assert_eq!(coins("HtT 01").unwrap().data, "10001");
+
+		let e = cards("as 10h Td kc", false).unwrap();
+		assert_eq!(e.data, "ASTHTDKC");
+		assert_eq!(e.count, 4);
+		assert!(cards("AS AS", false).is_err());
+		assert_eq!(cards("AS AS", true).unwrap().bits, cards("AS AH", true).unwrap().bits);
+		assert!(cards("AS AH", false).unwrap().bits < cards("AS AH", true).unwrap().bits);
+		assert!((cards("AS AH", false).unwrap().bits - (52f64 * 51f64).log2()).abs() < 1e-9);
+		// A full deck without replacement carries log2(52!) bits.
+		let deck = ["A", "2", "3", "4", "5", "6", "7", "8", "9", "T", "J", "Q", "K"].iter()
+			.flat_map(|r| ["S", "H", "D", "C"].iter().map(move |s| format!("{}{}", r, s)))
+			.collect::<Vec<_>>().join(" ");
+		let e = cards(&deck, false).unwrap();
+		assert_eq!(e.count, 52);
+		assert!((e.bits - 225.581).abs() < 1e-3);
+		assert!(cards("1S", false).is_err());
+	}
+}
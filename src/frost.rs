@@ -0,0 +1,576 @@
+//! A simulation of FROST threshold Schnorr signing.
+//!
+//! This is not an implementation of RFC 9591 and it is not interoperable with
+//! it or with any other FROST implementation: the DKG, the nonce derivation
+//! and the binding factors use the custom "hal/FROST/..." tagged hashes below
+//! instead of the RFC's H1 to H5, and the challenge is the BIP-340 one. Key
+//! shares, nonces and signature shares can only be used with hal itself.
+//!
+//! Signatures produced by the group are plain BIP-340 signatures for the
+//! group's x-only public key. When the group key would have an odd Y
+//! coordinate, all shares are negated during key generation, so that signing
+//! itself never has to care about the key's parity.
+//!
+//! All secret values are derived from a seed, so that runs are reproducible
+//! and can be used as test vectors.
+
+use std::convert::TryInto;
+
+use bitcoin::hashes::{sha256t_hash_newtype, Hash, HashEngine};
+use secp256k1::{schnorr, Parity, PublicKey, Scalar, SecretKey, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+
+use crate::{scalar, HexBytes, SECP};
+
+// These tags are specific to hal, see the module documentation.
+sha256t_hash_newtype! {
+	pub struct FrostDeriveTag = hash_str("hal/FROST/derive");
+
+	/// Tagged hash used to derive all secret values from a seed.
+	#[hash_newtype(forward)]
+	pub struct FrostDeriveHash(_);
+
+	pub struct FrostPokTag = hash_str("hal/FROST/pok");
+
+	/// Tagged hash for the challenge of the DKG proof of knowledge.
+	#[hash_newtype(forward)]
+	pub struct FrostPokHash(_);
+
+	pub struct FrostBindingTag = hash_str("hal/FROST/binding");
+
+	/// Tagged hash for the nonce binding factors.
+	#[hash_newtype(forward)]
+	pub struct FrostBindingHash(_);
+
+	pub struct Bip340ChallengeTag = hash_str("BIP0340/challenge");
+
+	/// The BIP-340 challenge hash.
+	#[hash_newtype(forward)]
+	pub struct Bip340ChallengeHash(_);
+}
+
+/// A share of the group secret as held by a single participant.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct FrostShareInfo {
+	pub index: u32,
+	pub secret_share: SecretKey,
+	pub public_share: PublicKey,
+}
+
+/// A share that was dealt by a participant to another participant in the DKG.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct FrostDealtShareInfo {
+	pub recipient: u32,
+	pub share: SecretKey,
+}
+
+/// Everything a single participant broadcasts and sends during the DKG.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct FrostDkgParticipantInfo {
+	pub index: u32,
+	pub commitments: Vec<PublicKey>,
+	pub proof_of_knowledge: HexBytes,
+	pub shares: Vec<FrostDealtShareInfo>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct FrostKeygenInfo {
+	pub threshold: u32,
+	pub participants: u32,
+	pub group_public_key: XOnlyPublicKey,
+	/// Whether the shares were negated to give the group key an even Y.
+	pub negated: bool,
+	pub vss_commitments: Vec<PublicKey>,
+	pub shares: Vec<FrostShareInfo>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub dkg_transcript: Option<Vec<FrostDkgParticipantInfo>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct FrostNonceInfo {
+	pub secret_nonce: HexBytes,
+	pub public_nonce: HexBytes,
+}
+
+/// The secret nonce pair of a signer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SecretNonce {
+	pub hiding: SecretKey,
+	pub binding: SecretKey,
+}
+
+impl SecretNonce {
+	pub fn from_slice(bytes: &[u8]) -> Result<SecretNonce, &'static str> {
+		if bytes.len() != 64 {
+			return Err("secret nonce should be 64 bytes");
+		}
+		Ok(SecretNonce {
+			hiding: SecretKey::from_slice(&bytes[0..32]).map_err(|_| "invalid hiding nonce")?,
+			binding: SecretKey::from_slice(&bytes[32..64]).map_err(|_| "invalid binding nonce")?,
+		})
+	}
+
+	pub fn serialize(&self) -> [u8; 64] {
+		let mut ret = [0u8; 64];
+		ret[0..32].copy_from_slice(&self.hiding.secret_bytes());
+		ret[32..64].copy_from_slice(&self.binding.secret_bytes());
+		ret
+	}
+
+	pub fn public_nonce(&self) -> PublicNonce {
+		PublicNonce {
+			hiding: self.hiding.public_key(&SECP),
+			binding: self.binding.public_key(&SECP),
+		}
+	}
+}
+
+/// The public nonce commitments of a signer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PublicNonce {
+	pub hiding: PublicKey,
+	pub binding: PublicKey,
+}
+
+impl PublicNonce {
+	pub fn from_slice(bytes: &[u8]) -> Result<PublicNonce, &'static str> {
+		if bytes.len() != 66 {
+			return Err("public nonce should be 66 bytes");
+		}
+		Ok(PublicNonce {
+			hiding: PublicKey::from_slice(&bytes[0..33]).map_err(|_| "invalid hiding nonce")?,
+			binding: PublicKey::from_slice(&bytes[33..66]).map_err(|_| "invalid binding nonce")?,
+		})
+	}
+
+	pub fn serialize(&self) -> [u8; 66] {
+		let mut ret = [0u8; 66];
+		ret[0..33].copy_from_slice(&self.hiding.serialize());
+		ret[33..66].copy_from_slice(&self.binding.serialize());
+		ret
+	}
+}
+
+/// Derive a secret scalar from the seed for the given purpose.
+fn derive_scalar(seed: &[u8], purpose: &str, parts: &[&[u8]]) -> Result<Scalar, &'static str> {
+	let mut engine = FrostDeriveHash::engine();
+	engine.input(&(seed.len() as u32).to_be_bytes());
+	engine.input(seed);
+	engine.input(purpose.as_bytes());
+	for part in parts {
+		engine.input(part);
+	}
+	let ret = scalar::reduce(FrostDeriveHash::from_engine(engine).to_byte_array());
+	if scalar::is_zero(ret) {
+		return Err("derived a zero scalar, try another seed");
+	}
+	Ok(ret)
+}
+
+/// Evaluate the polynomial with the given coefficients in x.
+fn poly_eval(coefficients: &[Scalar], x: u32) -> Scalar {
+	let x = scalar::from_u32(x);
+	coefficients.iter().rev().fold(Scalar::ZERO, |acc, c| scalar::add(scalar::mul(acc, x), *c))
+}
+
+/// Check the share of participant with index x against the VSS commitments.
+fn vss_verify(commitments: &[PublicKey], x: u32, share: &SecretKey) -> bool {
+	let x = scalar::from_u32(x);
+	let mut pow = Scalar::ONE;
+	let mut terms = Vec::with_capacity(commitments.len());
+	for c in commitments {
+		terms.push(scalar::point_mul(c, pow).expect("x is non-zero"));
+		pow = scalar::mul(pow, x);
+	}
+	scalar::point_sum(&terms) == Some(share.public_key(&SECP))
+}
+
+fn check_params(threshold: u32, participants: u32) -> Result<(), &'static str> {
+	if threshold == 0 {
+		return Err("threshold should be at least 1");
+	}
+	if threshold > participants {
+		return Err("threshold can't be higher than the number of participants");
+	}
+	Ok(())
+}
+
+/// Generate the shares from the combined shares and commitments, negating
+/// everything if the group key has an odd Y coordinate.
+fn finalize_keygen(
+	threshold: u32,
+	mut shares: Vec<Scalar>,
+	mut commitments: Vec<PublicKey>,
+	dkg_transcript: Option<Vec<FrostDkgParticipantInfo>>,
+) -> Result<FrostKeygenInfo, &'static str> {
+	let (group_public_key, parity) = commitments[0].x_only_public_key();
+	let negated = parity == Parity::Odd;
+	if negated {
+		shares = shares.into_iter().map(scalar::neg).collect();
+		commitments = commitments.into_iter().map(|c| c.negate(&SECP)).collect();
+	}
+
+	let shares = shares.into_iter().enumerate().map(|(i, s)| {
+		let secret = scalar::to_secret(s).ok_or("derived a zero share, try another seed")?;
+		Ok(FrostShareInfo {
+			index: i as u32 + 1,
+			secret_share: secret,
+			public_share: secret.public_key(&SECP),
+		})
+	}).collect::<Result<Vec<_>, &'static str>>()?;
+
+	Ok(FrostKeygenInfo {
+		threshold,
+		participants: shares.len() as u32,
+		group_public_key,
+		negated,
+		vss_commitments: commitments,
+		shares,
+		dkg_transcript,
+	})
+}
+
+/// Generate key shares using a trusted dealer.
+///
+/// If no group secret is given, it is derived from the seed.
+pub fn keygen_dealer(
+	threshold: u32,
+	participants: u32,
+	secret: Option<SecretKey>,
+	seed: &[u8],
+) -> Result<FrostKeygenInfo, &'static str> {
+	check_params(threshold, participants)?;
+
+	let coefficients = (0..threshold).map(|k| match (k, secret) {
+		(0, Some(s)) => Ok(s.into()),
+		_ => derive_scalar(seed, "coefficient", &[&0u32.to_be_bytes(), &k.to_be_bytes()]),
+	}).collect::<Result<Vec<_>, _>>()?;
+	let commitments = coefficients.iter()
+		.map(|c| scalar::base_mul(*c).expect("non-zero"))
+		.collect::<Vec<_>>();
+	let shares = (1..=participants).map(|i| poly_eval(&coefficients, i)).collect();
+
+	finalize_keygen(threshold, shares, commitments, None)
+}
+
+/// Simulate a Pedersen DKG between all participants, with proofs of
+/// knowledge for the constant terms as in the FROST paper.
+pub fn keygen_dkg(
+	threshold: u32,
+	participants: u32,
+	seed: &[u8],
+) -> Result<FrostKeygenInfo, &'static str> {
+	check_params(threshold, participants)?;
+
+	let mut transcript = Vec::with_capacity(participants as usize);
+	let mut shares = vec![Scalar::ZERO; participants as usize];
+	let mut commitments = vec![Vec::with_capacity(participants as usize); threshold as usize];
+	for i in 1..=participants {
+		let coefficients = (0..threshold).map(|k| {
+			derive_scalar(seed, "coefficient", &[&i.to_be_bytes(), &k.to_be_bytes()])
+		}).collect::<Result<Vec<_>, _>>()?;
+		let own_commitments = coefficients.iter()
+			.map(|c| scalar::base_mul(*c).expect("non-zero"))
+			.collect::<Vec<_>>();
+
+		// Schnorr proof of knowledge of the constant term.
+		let k = derive_scalar(seed, "pok-nonce", &[&i.to_be_bytes()])?;
+		let r = scalar::base_mul(k).expect("non-zero");
+		let c = pok_challenge(i, &own_commitments[0], &r);
+		let mu = scalar::add(k, scalar::mul(coefficients[0], c));
+		let mut pok = r.serialize().to_vec();
+		pok.extend_from_slice(&mu.to_be_bytes());
+		debug_assert!(verify_pok(i, &own_commitments[0], &pok));
+
+		let mut dealt = Vec::with_capacity(participants as usize);
+		for j in 1..=participants {
+			let share = scalar::to_secret(poly_eval(&coefficients, j))
+				.ok_or("derived a zero share, try another seed")?;
+			if !vss_verify(&own_commitments, j, &share) {
+				return Err("share doesn't match commitments");
+			}
+			shares[j as usize - 1] = scalar::add(shares[j as usize - 1], share.into());
+			dealt.push(FrostDealtShareInfo { recipient: j, share });
+		}
+
+		for (k, c) in own_commitments.iter().enumerate() {
+			commitments[k].push(*c);
+		}
+		transcript.push(FrostDkgParticipantInfo {
+			index: i,
+			commitments: own_commitments,
+			proof_of_knowledge: pok.into(),
+			shares: dealt,
+		});
+	}
+
+	let commitments = commitments.iter()
+		.map(|cs| scalar::point_sum(cs).ok_or("commitments sum to infinity, try another seed"))
+		.collect::<Result<Vec<_>, _>>()?;
+	finalize_keygen(threshold, shares, commitments, Some(transcript))
+}
+
+fn pok_challenge(index: u32, commitment: &PublicKey, r: &PublicKey) -> Scalar {
+	let mut engine = FrostPokHash::engine();
+	engine.input(&index.to_be_bytes());
+	engine.input(&commitment.serialize());
+	engine.input(&r.serialize());
+	scalar::reduce(FrostPokHash::from_engine(engine).to_byte_array())
+}
+
+/// Verify a DKG proof of knowledge of the secret behind the commitment.
+pub fn verify_pok(index: u32, commitment: &PublicKey, pok: &[u8]) -> bool {
+	if pok.len() != 65 {
+		return false;
+	}
+	let (r, mu) = match (PublicKey::from_slice(&pok[0..33]), pok[33..].try_into()) {
+		(Ok(r), Ok(mu)) => (r, scalar::reduce(mu)),
+		_ => return false,
+	};
+	let c = pok_challenge(index, commitment, &r);
+	let rhs = match scalar::point_mul(commitment, c) {
+		Some(cp) => scalar::point_sum(&[r, cp]),
+		None => Some(r),
+	};
+	scalar::base_mul(mu) == rhs
+}
+
+/// Generate a nonce pair for the signer with the given share.
+pub fn nonce_gen(share: &SecretKey, seed: &[u8]) -> Result<SecretNonce, &'static str> {
+	let share = share.secret_bytes();
+	let hiding = derive_scalar(seed, "nonce", &[&share, &[0]])?;
+	let binding = derive_scalar(seed, "nonce", &[&share, &[1]])?;
+	Ok(SecretNonce {
+		hiding: scalar::to_secret(hiding).expect("non-zero"),
+		binding: scalar::to_secret(binding).expect("non-zero"),
+	})
+}
+
+/// The Lagrange coefficient of the given signer at zero.
+pub fn lagrange_coefficient(index: u32, signers: &[u32]) -> Result<Scalar, &'static str> {
+	let mut num = Scalar::ONE;
+	let mut den = Scalar::ONE;
+	for j in signers {
+		if *j == index {
+			continue;
+		}
+		num = scalar::mul(num, scalar::from_u32(*j));
+		den = scalar::mul(den, scalar::sub(scalar::from_u32(*j), scalar::from_u32(index)));
+	}
+	Ok(scalar::mul(num, scalar::inv(den).ok_or("duplicate signer index")?))
+}
+
+/// The signing session derived from the nonce commitments of all signers.
+struct Session {
+	/// The signer indices, in order.
+	signers: Vec<u32>,
+	/// The binding factors for each signer.
+	binding_factors: Vec<Scalar>,
+	/// The group commitment R, before parity correction.
+	group_commitment: PublicKey,
+	challenge: Scalar,
+}
+
+impl Session {
+	fn new(
+		group_key: &XOnlyPublicKey,
+		commitments: &[(u32, PublicNonce)],
+		msg: &[u8; 32],
+	) -> Result<Session, &'static str> {
+		let mut commitments = commitments.to_vec();
+		commitments.sort_by_key(|c| c.0);
+		let signers = commitments.iter().map(|c| c.0).collect::<Vec<_>>();
+		if signers.windows(2).any(|w| w[0] == w[1]) {
+			return Err("duplicate signer index");
+		}
+		if signers.contains(&0) {
+			return Err("signer indices start at 1");
+		}
+
+		let mut encoded = Vec::with_capacity(commitments.len() * 70);
+		for (i, nonce) in &commitments {
+			encoded.extend_from_slice(&i.to_be_bytes());
+			encoded.extend_from_slice(&nonce.serialize());
+		}
+
+		let mut binding_factors = Vec::with_capacity(commitments.len());
+		let mut terms = Vec::with_capacity(commitments.len() * 2);
+		for (i, nonce) in &commitments {
+			let mut engine = FrostBindingHash::engine();
+			engine.input(&group_key.serialize());
+			engine.input(msg);
+			engine.input(&encoded);
+			engine.input(&i.to_be_bytes());
+			let rho = scalar::reduce(FrostBindingHash::from_engine(engine).to_byte_array());
+			binding_factors.push(rho);
+
+			terms.push(nonce.hiding);
+			if let Some(p) = scalar::point_mul(&nonce.binding, rho) {
+				terms.push(p);
+			}
+		}
+		let group_commitment = scalar::point_sum(&terms)
+			.ok_or("group commitment is the point at infinity")?;
+
+		let mut engine = Bip340ChallengeHash::engine();
+		engine.input(&group_commitment.x_only_public_key().0.serialize());
+		engine.input(&group_key.serialize());
+		engine.input(msg);
+		let challenge = scalar::reduce(Bip340ChallengeHash::from_engine(engine).to_byte_array());
+
+		Ok(Session { signers, binding_factors, group_commitment, challenge })
+	}
+
+	fn position(&self, index: u32) -> Result<usize, &'static str> {
+		self.signers.iter().position(|i| *i == index).ok_or("signer not part of the commitments")
+	}
+
+	/// Whether the nonces have to be negated to get an even R.
+	fn negate_nonces(&self) -> bool {
+		self.group_commitment.x_only_public_key().1 == Parity::Odd
+	}
+}
+
+/// Create a signature share.
+pub fn sign(
+	index: u32,
+	share: &SecretKey,
+	group_key: &XOnlyPublicKey,
+	secnonce: &SecretNonce,
+	commitments: &[(u32, PublicNonce)],
+	msg: &[u8; 32],
+) -> Result<Scalar, &'static str> {
+	let session = Session::new(group_key, commitments, msg)?;
+	let pos = session.position(index)?;
+	if !commitments.contains(&(index, secnonce.public_nonce())) {
+		return Err("secret nonce doesn't match the signer's commitment");
+	}
+
+	let rho = session.binding_factors[pos];
+	let mut k = scalar::add(secnonce.hiding.into(), scalar::mul(secnonce.binding.into(), rho));
+	if session.negate_nonces() {
+		k = scalar::neg(k);
+	}
+	let lambda = lagrange_coefficient(index, &session.signers)?;
+	let cls = scalar::mul(session.challenge, scalar::mul(lambda, (*share).into()));
+	Ok(scalar::add(k, cls))
+}
+
+/// Verify a signature share against the signer's public share.
+pub fn verify_share(
+	index: u32,
+	public_share: &PublicKey,
+	group_key: &XOnlyPublicKey,
+	commitments: &[(u32, PublicNonce)],
+	msg: &[u8; 32],
+	sig_share: Scalar,
+) -> Result<bool, &'static str> {
+	let session = Session::new(group_key, commitments, msg)?;
+	let pos = session.position(index)?;
+	let nonce = commitments.iter().find(|c| c.0 == index).expect("in session").1;
+
+	let rho = session.binding_factors[pos];
+	let mut terms = vec![nonce.hiding];
+	terms.extend(scalar::point_mul(&nonce.binding, rho));
+	let mut r = scalar::point_sum(&terms);
+	if session.negate_nonces() {
+		r = r.map(|r| r.negate(&SECP));
+	}
+	let lambda = lagrange_coefficient(index, &session.signers)?;
+	let mut terms = r.into_iter().collect::<Vec<_>>();
+	terms.extend(scalar::point_mul(public_share, scalar::mul(session.challenge, lambda)));
+	Ok(scalar::base_mul(sig_share) == scalar::point_sum(&terms))
+}
+
+/// Aggregate the signature shares into a BIP-340 signature.
+///
+/// The signature is verified against the group key before it is returned.
+pub fn aggregate(
+	group_key: &XOnlyPublicKey,
+	threshold: u32,
+	commitments: &[(u32, PublicNonce)],
+	msg: &[u8; 32],
+	sig_shares: &[(u32, Scalar)],
+) -> Result<schnorr::Signature, &'static str> {
+	let session = Session::new(group_key, commitments, msg)?;
+	if session.signers.len() < threshold as usize {
+		return Err("the number of signers is smaller than the threshold");
+	}
+	let mut shared = sig_shares.iter().map(|s| s.0).collect::<Vec<_>>();
+	shared.sort();
+	if shared != session.signers {
+		return Err("need exactly one signature share for each signer");
+	}
+
+	let s = sig_shares.iter().fold(Scalar::ZERO, |acc, z| scalar::add(acc, z.1));
+	let mut sig = [0u8; 64];
+	sig[0..32].copy_from_slice(&session.group_commitment.x_only_public_key().0.serialize());
+	sig[32..64].copy_from_slice(&s.to_be_bytes());
+	let sig = schnorr::Signature::from_slice(&sig).expect("64 bytes");
+	SECP.verify_schnorr(&sig, &secp256k1::Message::from_digest(*msg), group_key)
+		.map_err(|_| "aggregated signature is invalid")?;
+	Ok(sig)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sign_with(
+		keygen: &FrostKeygenInfo,
+		signers: &[u32],
+		msg: &[u8; 32],
+	) -> Result<schnorr::Signature, &'static str> {
+		let nonces = signers.iter().map(|i| {
+			let share = &keygen.shares[*i as usize - 1];
+			(*i, nonce_gen(&share.secret_share, b"nonce seed").unwrap())
+		}).collect::<Vec<_>>();
+		let commitments = nonces.iter().map(|(i, n)| (*i, n.public_nonce())).collect::<Vec<_>>();
+
+		let sig_shares = nonces.iter().map(|(i, n)| {
+			let share = &keygen.shares[*i as usize - 1];
+			let z = sign(*i, &share.secret_share, &keygen.group_public_key, n, &commitments, msg)
+				.unwrap();
+			assert!(verify_share(
+				*i, &share.public_share, &keygen.group_public_key, &commitments, msg, z,
+			).unwrap());
+			(*i, z)
+		}).collect::<Vec<_>>();
+
+		aggregate(&keygen.group_public_key, keygen.threshold, &commitments, msg, &sig_shares)
+	}
+
+	#[test]
+	fn test_frost_sign() {
+		let msg = [42u8; 32];
+		let msg_obj = secp256k1::Message::from_digest(msg);
+		for seed in [&b"seed1"[..], &b"seed2"[..], &b"seed3"[..]] {
+			let dealer = keygen_dealer(2, 3, None, seed).unwrap();
+			let dkg = keygen_dkg(3, 5, seed).unwrap();
+			for (i, s) in dealer.shares.iter().enumerate() {
+				assert!(vss_verify(&dealer.vss_commitments, i as u32 + 1, &s.secret_share));
+			}
+			for p in dkg.dkg_transcript.as_ref().unwrap() {
+				assert!(verify_pok(p.index, &p.commitments[0], p.proof_of_knowledge.bytes()));
+			}
+
+			for signers in [&[1, 2][..], &[2, 3], &[1, 3], &[1, 2, 3]] {
+				let sig = sign_with(&dealer, signers, &msg).unwrap();
+				SECP.verify_schnorr(&sig, &msg_obj, &dealer.group_public_key).unwrap();
+			}
+			for signers in [&[1, 2, 3][..], &[5, 3, 1], &[2, 4, 5, 1]] {
+				let sig = sign_with(&dkg, signers, &msg).unwrap();
+				SECP.verify_schnorr(&sig, &msg_obj, &dkg.group_public_key).unwrap();
+			}
+			assert!(sign_with(&dealer, &[2], &msg).is_err());
+			assert!(sign_with(&dkg, &[1, 4], &msg).is_err());
+		}
+	}
+
+	#[test]
+	fn test_frost_dealer_secret() {
+		let secret = SecretKey::from_slice(&[1u8; 32]).unwrap();
+		let keygen = keygen_dealer(1, 1, Some(secret), b"seed").unwrap();
+		assert_eq!(keygen.group_public_key, secret.x_only_public_key(&SECP).0);
+	}
+}
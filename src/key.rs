@@ -1,5 +1,7 @@
 
-use bitcoin::{secp256k1, Network, PrivateKey, PublicKey, XOnlyPublicKey};
+use bitcoin::{secp256k1, Address, Network, PrivateKey, PublicKey, XOnlyPublicKey};
+use bitcoin::key::TapTweak;
+use bitcoin::taproot::{TapNodeHash, TapTweakHash};
 use serde::{Deserialize, Serialize};
 
 use crate::{SECP, address, GetInfo, HexBytes};
@@ -101,3 +103,151 @@ impl GetInfo<EcdsaSignatureInfo> for secp256k1::ecdsa::Signature {
 		}
 	}
 }
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct EcdhInfo {
+	/// The shared secret as computed by libsecp256k1: SHA-256 of the
+	/// compressed shared point.
+	pub shared_secret: HexBytes,
+	pub shared_point: secp256k1::PublicKey,
+	pub shared_x: HexBytes,
+}
+
+impl EcdhInfo {
+	pub fn new(secret_key: &secp256k1::SecretKey, public_key: &secp256k1::PublicKey) -> EcdhInfo {
+		let shared_secret = secp256k1::ecdh::SharedSecret::new(public_key, secret_key);
+		let point = secp256k1::ecdh::shared_secret_point(public_key, secret_key);
+		let shared_point = secp256k1::PublicKey::from_slice(&{
+			let mut uncompressed = [4u8; 65];
+			uncompressed[1..].copy_from_slice(&point);
+			uncompressed
+		}).expect("valid point");
+		EcdhInfo {
+			shared_secret: shared_secret.secret_bytes().to_vec().into(),
+			shared_point,
+			shared_x: point[0..32].to_vec().into(),
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct TaprootTweakInfo {
+	pub internal_key: XOnlyPublicKey,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub merkle_root: Option<TapNodeHash>,
+	pub tweak: TapTweakHash,
+	pub output_key: XOnlyPublicKey,
+	pub output_key_parity: u8,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tweaked_private_key: Option<secp256k1::SecretKey>,
+	pub address: Address<bitcoin::address::NetworkUnchecked>,
+}
+
+impl TaprootTweakInfo {
+	/// Calculate the BIP-341 tweak for the internal key and merkle root.
+	///
+	/// If the secret key for the internal key is given, the tweaked secret
+	/// key is calculated as well.
+	pub fn new(
+		internal_key: XOnlyPublicKey,
+		merkle_root: Option<TapNodeHash>,
+		secret_key: Option<&secp256k1::SecretKey>,
+		network: Network,
+	) -> TaprootTweakInfo {
+		let (output_key, parity) = internal_key.tap_tweak(&SECP, merkle_root);
+		TaprootTweakInfo {
+			internal_key,
+			merkle_root,
+			tweak: TapTweakHash::from_key_and_tweak(internal_key, merkle_root),
+			output_key: output_key.to_x_only_public_key(),
+			output_key_parity: parity.to_u8(),
+			tweaked_private_key: secret_key.map(|sk| {
+				let keypair = secp256k1::Keypair::from_secret_key(&SECP, sk);
+				keypair.tap_tweak(&SECP, merkle_root).to_keypair().secret_key()
+			}),
+			address: Address::p2tr_tweaked(output_key, network).as_unchecked().clone(),
+		}
+	}
+}
+
+/// Check that the output key commits to the internal key and merkle root.
+///
+/// The parity of the output key is only checked when it is given.
+pub fn verify_taproot_commitment(
+	internal_key: XOnlyPublicKey,
+	merkle_root: Option<TapNodeHash>,
+	output_key: XOnlyPublicKey,
+	parity: Option<secp256k1::Parity>,
+) -> bool {
+	let (tweaked, tweaked_parity) = internal_key.tap_tweak(&SECP, merkle_root);
+	tweaked.to_x_only_public_key() == output_key && parity.map_or(true, |p| p == tweaked_parity)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::str::FromStr;
+
+	#[test]
+	fn test_taproot_tweak() {
+		// The first two scriptPubKey vectors of the BIP-341 wallet test vectors.
+		let internal_key = XOnlyPublicKey::from_str(
+			"d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d").unwrap();
+		let info = TaprootTweakInfo::new(internal_key, None, None, Network::Bitcoin);
+		assert_eq!(info.tweak.to_string(),
+			"b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70");
+		assert_eq!(info.output_key.to_string(),
+			"53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343");
+		assert_eq!(info.address.assume_checked().to_string(),
+			"bc1p2wsldez5mud2yam29q22wgfh9439spgduvct83k3pm50fcxa5dps59h4z5");
+
+		let internal_key = XOnlyPublicKey::from_str(
+			"187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27").unwrap();
+		let merkle_root = TapNodeHash::from_str(
+			"5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21").unwrap();
+		let info = TaprootTweakInfo::new(internal_key, Some(merkle_root), None, Network::Bitcoin);
+		assert_eq!(info.tweak.to_string(),
+			"cbd8679ba636c1110ea247542cfbd964131a6be84f873f7f3b62a777528ed001");
+		assert_eq!(info.output_key.to_string(),
+			"147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3");
+		assert_eq!(info.output_key_parity, 1);
+		assert_eq!(info.address.assume_checked().to_string(),
+			"bc1pz37fc4cn9ah8anwm4xqqhvxygjf9rjf2resrw8h8w4tmvcs0863sa2e586");
+
+		let odd = Some(secp256k1::Parity::Odd);
+		assert!(verify_taproot_commitment(internal_key, Some(merkle_root), info.output_key, None));
+		assert!(verify_taproot_commitment(internal_key, Some(merkle_root), info.output_key, odd));
+		assert!(!verify_taproot_commitment(internal_key, Some(merkle_root), info.output_key,
+			Some(secp256k1::Parity::Even)));
+		assert!(!verify_taproot_commitment(internal_key, None, info.output_key, None));
+
+		// The tweaked private key of the first input of the key path spending vector.
+		let sk = secp256k1::SecretKey::from_str(
+			"6b973d88838f27366ed61c9ad6367663045cb456e28335c109e30717ae0c6baa").unwrap();
+		let internal_key = sk.x_only_public_key(&SECP).0;
+		let info = TaprootTweakInfo::new(internal_key, None, Some(&sk), Network::Bitcoin);
+		assert_eq!(info.tweaked_private_key.unwrap().display_secret().to_string(),
+			"2405b971772ad26915c8dcdf10f238753a9b837e5f8e6a86fd7c0cce5b7296d9");
+	}
+
+	#[test]
+	fn test_ecdh() {
+		// Computed independently with affine point arithmetic.
+		let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+		let pk = secp256k1::SecretKey::from_slice(&[2; 32]).unwrap().public_key(&SECP);
+		let info = EcdhInfo::new(&sk, &pk);
+		assert_eq!(
+			info.shared_point.to_string(),
+			"03d0158a38faf6118af133af12d9bfa388eab4a08d1a2088ea6e6ec1269e03567f",
+		);
+		assert_eq!(
+			info.shared_x.hex(),
+			"d0158a38faf6118af133af12d9bfa388eab4a08d1a2088ea6e6ec1269e03567f",
+		);
+		assert_eq!(
+			info.shared_secret.hex(),
+			"b7c99dee100e6844572a8d9ee91975af09e602491d4ba32f6781261cd9c99173",
+		);
+	}
+}
@@ -10,18 +10,30 @@ extern crate miniscript as miniscriptlib;
 extern crate secp256k1;
 extern crate serde;
 
+pub mod adaptor;
 pub mod address;
+pub mod aezeed;
+pub mod age;
 pub mod ark;
 pub mod bech32;
 pub mod bip32;
+pub mod bip38;
 pub mod bip39;
+pub mod bip85;
 pub mod block;
+pub mod electrum;
+pub mod entropy;
+pub mod frost;
 pub mod key;
 pub mod lightning;
 pub mod message;
 pub mod miniscript;
 pub mod psbt;
+pub mod silentpayment;
+pub mod slip39;
 pub mod tx;
+mod crypto;
+mod scalar;
 mod serde_utils;
 pub use serde_utils::HexBytes;
 
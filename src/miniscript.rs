@@ -1,3 +1,4 @@
+use bitcoin::bip32;
 use serde::{Deserialize, Serialize};
 
 use crate::HexBytes;
@@ -6,6 +7,7 @@ use crate::HexBytes;
 #[serde(rename_all = "snake_case")]
 pub enum MiniscriptKeyType {
 	PublicKey,
+	DescriptorPublicKey,
 	String,
 }
 
@@ -65,6 +67,7 @@ pub struct Miniscripts {
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 pub struct DescriptorInfo {
 	pub descriptor: String,
+	pub checksum: String,
 	pub key_type: MiniscriptKeyType,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub address: Option<String>,
@@ -78,6 +81,32 @@ pub struct DescriptorInfo {
 	pub max_satisfaction_weight: Option<u64>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub policy: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub derived: Option<Vec<DerivedDescriptorInfo>>,
+}
+
+/// A key of a descriptor derived at a specific index.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct DerivedKeyInfo {
+	pub public_key: bitcoin::PublicKey,
+	pub master_fingerprint: bip32::Fingerprint,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub derivation_path: Option<bip32::DerivationPath>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub private_key: Option<bitcoin::PrivateKey>,
+}
+
+/// A descriptor derived at a specific index.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct DerivedDescriptorInfo {
+	pub index: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub multipath_index: Option<usize>,
+	pub descriptor: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub address: Option<String>,
+	pub script_pubkey: HexBytes,
+	pub keys: Vec<DerivedKeyInfo>,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
@@ -0,0 +1,127 @@
+//! Scalar arithmetic modulo the secp256k1 curve order.
+//!
+//! libsecp256k1 only exposes tweak operations on non-zero secret keys, so
+//! these helpers take care of the zero cases to give us total operations.
+
+use secp256k1::{constants, PublicKey, Scalar, SecretKey};
+
+use crate::SECP;
+
+/// Interpret the 32 big-endian bytes as an integer and reduce it modulo the
+/// curve order.
+pub fn reduce(mut bytes: [u8; 32]) -> Scalar {
+	if bytes >= constants::CURVE_ORDER {
+		// Since 2^256 < 2n, a single subtraction is enough.
+		let mut borrow = 0i16;
+		for i in (0..32).rev() {
+			let d = bytes[i] as i16 - constants::CURVE_ORDER[i] as i16 - borrow;
+			borrow = if d < 0 { 1 } else { 0 };
+			bytes[i] = d.rem_euclid(256) as u8;
+		}
+	}
+	Scalar::from_be_bytes(bytes).expect("reduced")
+}
+
+/// Create a scalar from a small integer.
+pub fn from_u32(v: u32) -> Scalar {
+	let mut bytes = [0u8; 32];
+	bytes[28..].copy_from_slice(&v.to_be_bytes());
+	Scalar::from_be_bytes(bytes).expect("small integer")
+}
+
+/// Convert a non-zero scalar into a secret key.
+pub fn to_secret(s: Scalar) -> Option<SecretKey> {
+	SecretKey::from_slice(&s.to_be_bytes()).ok()
+}
+
+pub fn is_zero(s: Scalar) -> bool {
+	s == Scalar::ZERO
+}
+
+pub fn add(a: Scalar, b: Scalar) -> Scalar {
+	match to_secret(a) {
+		Some(a) => a.add_tweak(&b).map(Scalar::from).unwrap_or(Scalar::ZERO),
+		None => b,
+	}
+}
+
+pub fn neg(a: Scalar) -> Scalar {
+	match to_secret(a) {
+		Some(a) => a.negate().into(),
+		None => Scalar::ZERO,
+	}
+}
+
+pub fn sub(a: Scalar, b: Scalar) -> Scalar {
+	add(a, neg(b))
+}
+
+pub fn mul(a: Scalar, b: Scalar) -> Scalar {
+	match to_secret(a) {
+		// The curve order is prime so the product of non-zeros is non-zero.
+		Some(a) if !is_zero(b) => a.mul_tweak(&b).expect("non-zero").into(),
+		_ => Scalar::ZERO,
+	}
+}
+
+/// The multiplicative inverse, using Fermat's little theorem.
+pub fn inv(a: Scalar) -> Option<Scalar> {
+	if is_zero(a) {
+		return None;
+	}
+
+	let mut exp = constants::CURVE_ORDER;
+	// n is odd, so n - 2 doesn't need to borrow
+	exp[31] -= 2;
+
+	let mut ret = Scalar::ONE;
+	for byte in exp.iter() {
+		for bit in (0..8).rev() {
+			ret = mul(ret, ret);
+			if (byte >> bit) & 1 == 1 {
+				ret = mul(ret, a);
+			}
+		}
+	}
+	Some(ret)
+}
+
+/// Multiply the generator with the scalar, [None] for zero.
+pub fn base_mul(s: Scalar) -> Option<PublicKey> {
+	to_secret(s).map(|sk| PublicKey::from_secret_key(&SECP, &sk))
+}
+
+/// Multiply the point with the scalar, [None] for zero.
+pub fn point_mul(p: &PublicKey, s: Scalar) -> Option<PublicKey> {
+	p.mul_tweak(&SECP, &s).ok()
+}
+
+/// Add up the points, [None] if the result is the point at infinity.
+pub fn point_sum<'a>(points: impl IntoIterator<Item = &'a PublicKey>) -> Option<PublicKey> {
+	let points = points.into_iter().collect::<Vec<_>>();
+	PublicKey::combine_keys(&points).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_scalar_arith() {
+		let two = from_u32(2);
+		let three = from_u32(3);
+		assert_eq!(add(two, three), from_u32(5));
+		assert_eq!(sub(two, two), Scalar::ZERO);
+		assert_eq!(mul(two, three), from_u32(6));
+		assert_eq!(mul(inv(three).unwrap(), from_u32(6)), two);
+		assert_eq!(add(neg(two), three), Scalar::ONE);
+		assert_eq!(reduce(constants::CURVE_ORDER), Scalar::ZERO);
+		// 2^256 - 1 - n
+		let mut expected = [0u8; 32];
+		expected[15..].copy_from_slice(&[
+			0x01, 0x45, 0x51, 0x23, 0x19, 0x50, 0xb7, 0x5f, 0xc4,
+			0x40, 0x2d, 0xa1, 0x73, 0x2f, 0xc9, 0xbe, 0xbe,
+		]);
+		assert_eq!(reduce([0xff; 32]).to_be_bytes(), expected);
+	}
+}
@@ -0,0 +1,421 @@
+//! BIP-352 Silent Payments.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::bech32::primitives::decode::CheckedHrpstring;
+use bitcoin::bech32::{Bech32m, ByteIterExt, Fe32, Fe32IterExt, Hrp};
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hashes::{hash160, sha256t_hash_newtype, Hash, HashEngine};
+use bitcoin::script::Instruction;
+use bitcoin::{Script, Transaction, TxIn};
+use secp256k1::{Parity, PublicKey, Scalar, SecretKey, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+
+use crate::{scalar, HexBytes, SECP};
+
+sha256t_hash_newtype! {
+	pub struct InputsTag = hash_str("BIP0352/Inputs");
+
+	/// Tagged hash committing to the inputs of a transaction.
+	#[hash_newtype(forward)]
+	pub struct InputsHash(_);
+
+	pub struct SharedSecretTag = hash_str("BIP0352/SharedSecret");
+
+	/// Tagged hash to derive output tweaks from the shared secret.
+	#[hash_newtype(forward)]
+	pub struct SharedSecretHash(_);
+
+	pub struct LabelTag = hash_str("BIP0352/Label");
+
+	/// Tagged hash to derive label tweaks.
+	#[hash_newtype(forward)]
+	pub struct LabelHash(_);
+}
+
+const HRP_MAINNET: Hrp = Hrp::parse_unchecked("sp");
+const HRP_TESTNET: Hrp = Hrp::parse_unchecked("tsp");
+
+/// The x coordinate of the BIP-341 NUMS point H.
+const NUMS_H: [u8; 32] = [
+	0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+	0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// A silent payment address.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SilentPaymentAddress {
+	pub testnet: bool,
+	pub version: u8,
+	pub scan_key: PublicKey,
+	pub spend_key: PublicKey,
+}
+
+impl fmt::Display for SilentPaymentAddress {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let hrp = if self.testnet { HRP_TESTNET } else { HRP_MAINNET };
+		let version = Fe32::try_from(self.version).expect("version is at most 31");
+		let mut data = [0u8; 66];
+		data[0..33].copy_from_slice(&self.scan_key.serialize());
+		data[33..66].copy_from_slice(&self.spend_key.serialize());
+		let chars = data.iter()
+			.copied()
+			.bytes_to_fes()
+			.with_checksum::<Bech32m>(&hrp)
+			.with_witness_version(version)
+			.chars();
+		for c in chars {
+			fmt::Write::write_char(f, c)?;
+		}
+		Ok(())
+	}
+}
+
+impl FromStr for SilentPaymentAddress {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<SilentPaymentAddress, String> {
+		let mut checked = CheckedHrpstring::new::<Bech32m>(s)
+			.map_err(|e| format!("invalid bech32m encoding: {}", e))?;
+		let testnet = match checked.hrp() {
+			h if h == HRP_MAINNET => false,
+			h if h == HRP_TESTNET => true,
+			h => return Err(format!("invalid HRP for a silent payment address: {}", h)),
+		};
+		let version = checked.remove_witness_version().ok_or("missing version")?.to_u8();
+		if version == 31 {
+			return Err("invalid silent payment address version 31".into());
+		}
+		let data = checked.byte_iter().collect::<Vec<u8>>();
+		// Future versions can append data, but must keep the first 66 bytes.
+		if data.len() < 66 || (version == 0 && data.len() != 66) {
+			return Err(format!("invalid silent payment address payload length: {}", data.len()));
+		}
+		Ok(SilentPaymentAddress {
+			testnet,
+			version,
+			scan_key: PublicKey::from_slice(&data[0..33]).map_err(|_| "invalid scan key")?,
+			spend_key: PublicKey::from_slice(&data[33..66]).map_err(|_| "invalid spend key")?,
+		})
+	}
+}
+
+/// The tweak for label m.
+pub fn label_tweak(scan_key: &SecretKey, m: u32) -> Scalar {
+	let mut engine = LabelHash::engine();
+	engine.input(&scan_key.secret_bytes());
+	engine.input(&m.to_be_bytes());
+	scalar::reduce(LabelHash::from_engine(engine).to_byte_array())
+}
+
+/// The spend key with label m applied.
+pub fn labeled_spend_key(
+	spend_key: &PublicKey,
+	scan_key: &SecretKey,
+	m: u32,
+) -> Result<PublicKey, &'static str> {
+	spend_key.add_exp_tweak(&SECP, &label_tweak(scan_key, m)).map_err(|_| "invalid label tweak")
+}
+
+/// The tweak t_k for the k-th output.
+pub fn output_tweak(shared_secret: &PublicKey, k: u32) -> Scalar {
+	let mut engine = SharedSecretHash::engine();
+	engine.input(&shared_secret.serialize());
+	engine.input(&k.to_be_bytes());
+	scalar::reduce(SharedSecretHash::from_engine(engine).to_byte_array())
+}
+
+/// Parse a compressed public key, as only those are eligible.
+fn compressed_key(bytes: &[u8]) -> Option<PublicKey> {
+	if bytes.len() == 33 {
+		PublicKey::from_slice(bytes).ok()
+	} else {
+		None
+	}
+}
+
+/// Get the public key from an input that is eligible for silent payments.
+///
+/// Returns an error if the input makes the whole transaction ineligible.
+pub fn input_public_key(input: &TxIn, prevout: &Script) -> Result<Option<PublicKey>, &'static str> {
+	if prevout.is_p2pkh() {
+		// Take the last push that matches the pubkey hash, because the
+		// scriptSig is malleable.
+		let pkh = &prevout.as_bytes()[3..23];
+		let pushes = input.script_sig.instructions().filter_map(|i| match i {
+			Ok(Instruction::PushBytes(b)) => Some(b.as_bytes()),
+			_ => None,
+		}).collect::<Vec<_>>();
+		Ok(pushes.into_iter().rev()
+			.find(|b| b.len() == 33 && hash160::Hash::hash(b)[..] == pkh[..])
+			.and_then(compressed_key))
+	} else if prevout.is_p2sh() {
+		// Only p2sh-p2wpkh is eligible.
+		let redeem_script = match input.script_sig.instructions().next() {
+			Some(Ok(Instruction::PushBytes(b))) => Script::from_bytes(b.as_bytes()),
+			_ => return Ok(None),
+		};
+		if redeem_script.is_p2wpkh() && input.witness.len() == 2 {
+			Ok(input.witness.last().and_then(compressed_key))
+		} else {
+			Ok(None)
+		}
+	} else if prevout.is_p2wpkh() {
+		if input.witness.len() == 2 {
+			Ok(input.witness.last().and_then(compressed_key))
+		} else {
+			Ok(None)
+		}
+	} else if prevout.is_p2tr() {
+		let mut stack = input.witness.iter().collect::<Vec<_>>();
+		if stack.len() > 1 && stack.last().map(|a| a.first() == Some(&0x50)).unwrap_or(false) {
+			// remove the annex
+			stack.pop();
+		}
+		if stack.len() > 1 {
+			// A script path spend with the NUMS point as internal key is
+			// not eligible.
+			let control_block = stack.last().expect("not empty");
+			if control_block.len() >= 33 && control_block[1..33] == NUMS_H {
+				return Ok(None);
+			}
+		}
+		let output_key = XOnlyPublicKey::from_slice(&prevout.as_bytes()[2..34])
+			.map_err(|_| "invalid taproot output key")?;
+		Ok(Some(output_key.public_key(Parity::Even)))
+	} else if prevout.witness_version().map(|v| v.to_num() > 1).unwrap_or(false) {
+		Err("transaction spends a segwit output of version greater than 1")
+	} else {
+		Ok(None)
+	}
+}
+
+/// The input hash and the sum of the public keys of all eligible inputs.
+pub fn input_hash(
+	tx: &Transaction,
+	prevouts: &[&Script],
+) -> Result<(Scalar, PublicKey), &'static str> {
+	if tx.input.len() != prevouts.len() {
+		return Err("need exactly one prevout for each input");
+	}
+	let mut keys = Vec::new();
+	for (input, prevout) in tx.input.iter().zip(prevouts) {
+		if let Some(pk) = input_public_key(input, prevout)? {
+			keys.push(pk);
+		}
+	}
+	if keys.is_empty() {
+		return Err("transaction has no inputs eligible for silent payments");
+	}
+	let sum = scalar::point_sum(&keys).ok_or("input public keys sum to the point at infinity")?;
+
+	let smallest = tx.input.iter()
+		.map(|i| serialize(&i.previous_output))
+		.min()
+		.expect("at least one input");
+	let mut engine = InputsHash::engine();
+	engine.input(&smallest);
+	engine.input(&sum.serialize());
+	let hash = scalar::reduce(InputsHash::from_engine(engine).to_byte_array());
+	Ok((hash, sum))
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct SilentPaymentAddressInfo {
+	pub address: String,
+	pub testnet: bool,
+	pub version: u8,
+	pub scan_public_key: PublicKey,
+	pub spend_public_key: PublicKey,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub label: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub label_tweak: Option<HexBytes>,
+}
+
+impl SilentPaymentAddressInfo {
+	pub fn new(address: &SilentPaymentAddress) -> SilentPaymentAddressInfo {
+		SilentPaymentAddressInfo {
+			address: address.to_string(),
+			testnet: address.testnet,
+			version: address.version,
+			scan_public_key: address.scan_key,
+			spend_public_key: address.spend_key,
+			label: None,
+			label_tweak: None,
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct SilentPaymentOutputInfo {
+	pub vout: u32,
+	pub k: u32,
+	pub output_key: XOnlyPublicKey,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub label: Option<u32>,
+	/// The tweak to add to the spend private key, including the label tweak.
+	pub tweak: HexBytes,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub private_key: Option<SecretKey>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct SilentPaymentScanInfo {
+	pub input_public_key: PublicKey,
+	pub input_hash: HexBytes,
+	pub shared_secret: PublicKey,
+	/// The expected output key for k=0 without label.
+	pub first_output_key: XOnlyPublicKey,
+	pub outputs: Vec<SilentPaymentOutputInfo>,
+}
+
+/// Scan the transaction for outputs paying to the given keys and labels.
+///
+/// If the spend private key is given, the private keys for the found outputs
+/// are calculated.
+pub fn scan(
+	tx: &Transaction,
+	prevouts: &[&Script],
+	scan_key: &SecretKey,
+	spend_key: &PublicKey,
+	spend_private_key: Option<&SecretKey>,
+	labels: &[u32],
+) -> Result<SilentPaymentScanInfo, &'static str> {
+	let (input_hash, input_key) = input_hash(tx, prevouts)?;
+	let shared_secret = input_key
+		.mul_tweak(&SECP, &input_hash).and_then(|p| p.mul_tweak(&SECP, &Scalar::from(*scan_key)))
+		.map_err(|_| "invalid shared secret")?;
+
+	let labels = labels.iter().map(|m| (*m, label_tweak(scan_key, *m))).collect::<Vec<_>>();
+	let taproot_outputs = tx.output.iter().enumerate()
+		.filter(|(_, o)| o.script_pubkey.is_p2tr())
+		.map(|(i, o)| (i as u32, &o.script_pubkey.as_bytes()[2..34]))
+		.collect::<Vec<_>>();
+
+	let mut outputs = Vec::new();
+	let mut first_output_key = None;
+	for k in 0.. {
+		let t_k = output_tweak(&shared_secret, k);
+		let p_k = spend_key.add_exp_tweak(&SECP, &t_k).map_err(|_| "invalid output tweak")?;
+		first_output_key.get_or_insert(p_k.x_only_public_key().0);
+
+		// The unlabeled output, followed by all labeled ones.
+		let candidates = Some((None, t_k, p_k)).into_iter().chain(labels.iter().filter_map(|(m, l)| {
+			let tweak = scalar::add(t_k, *l);
+			p_k.add_exp_tweak(&SECP, l).ok().map(|p| (Some(*m), tweak, p))
+		})).collect::<Vec<_>>();
+
+		let found = candidates.iter().find_map(|(m, tweak, p)| {
+			let xonly = p.x_only_public_key().0;
+			taproot_outputs.iter().find(|(_, o)| *o == &xonly.serialize()[..]).map(|(vout, _)| {
+				SilentPaymentOutputInfo {
+					vout: *vout,
+					k,
+					output_key: xonly,
+					label: *m,
+					tweak: tweak.to_be_bytes().to_vec().into(),
+					private_key: spend_private_key.and_then(|sk| sk.add_tweak(tweak).ok()),
+				}
+			})
+		});
+		match found {
+			Some(output) => outputs.push(output),
+			None => break,
+		}
+	}
+
+	Ok(SilentPaymentScanInfo {
+		input_public_key: input_key,
+		input_hash: input_hash.to_be_bytes().to_vec().into(),
+		shared_secret,
+		first_output_key: first_output_key.expect("at least one iteration"),
+		outputs,
+	})
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bitcoin::consensus::encode::deserialize;
+	use bitcoin::ScriptBuf;
+
+	#[test]
+	fn test_bip352_simple_send() {
+		let addr = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+		let address = SilentPaymentAddress::from_str(addr).unwrap();
+		assert_eq!(address.to_string(), addr);
+
+		let scan_key = SecretKey::from_str(
+			"0f694e068028a717f8af6b9411f9a133dd3565258714cc226594b34db90c1f2c").unwrap();
+		let spend_key = SecretKey::from_str(
+			"9d6ad855ce3417ef84e836892e5a56392bfba05fa5d97ccea30e266f540e08b3").unwrap();
+		assert_eq!(address.scan_key, scan_key.public_key(&SECP));
+		assert_eq!(address.spend_key, spend_key.public_key(&SECP));
+
+		let tx = deserialize::<Transaction>(&hex::decode(
+			"0200000002169e1e83e930853391bc6f35f605c6754cfead57cf8387639d3b4096c54f18f40000000\
+			06b483046022100ad79e6801dd9a8727f342f31c71c4912866f59dc6e7981878e92c5844a0ce92902\
+			2100fb0d2393e813968648b9753b7e9871d90ab3d815ebf91820d704b19f4ed224d621025a1e61f89\
+			8173040e20616d43e9f496fba90338a39faa1ed98fcbaeee4dd9be5ffffffff8dd4f5fbd5e980fc02\
+			f35c6ce145935b11e284605bf599a13c6d415db55d07a1000000006b48304602210086783ded73e96\
+			1037e77d49d9deee4edc2b23136e9728d56e4491c80015c3a63022100fda4c0f21ea18de29edbce57\
+			f7134d613e044ee150a89e2e64700de2d4e83d4e2103bd85685d03d111699b15d046319febe77f8de\
+			5286e9e512703cdee1bf3be3792ffffffff01e8030000000000002251203e9fce73d4e77a4809908e\
+			3c3a2e54ee147b9312dc5044a193d1fc85de46e3c100000000",
+		).unwrap()).unwrap();
+		let prevouts = [
+			Script::from_bytes(&hex::decode("76a91419c2f3ae0ca3b642bd3e49598b8da89f50c1416188ac").unwrap()).to_owned(),
+			Script::from_bytes(&hex::decode("76a914d9317c66f54ff0a152ec50b1d19c25be50c8e15988ac").unwrap()).to_owned(),
+		];
+		let prevouts = prevouts.iter().map(|s| s.as_script()).collect::<Vec<_>>();
+
+		let info = scan(&tx, &prevouts, &scan_key, &address.spend_key, None, &[]).unwrap();
+		assert_eq!(info.outputs.len(), 1);
+		assert_eq!(info.outputs[0].output_key.to_string(),
+			"3e9fce73d4e77a4809908e3c3a2e54ee147b9312dc5044a193d1fc85de46e3c1");
+		assert_eq!(info.outputs[0].tweak.0, hex::decode(
+			"f438b40179a3c4262de12986c0e6cce0634007cdc79c1dcd3e20b9ebc2e7eef6").unwrap());
+
+		// The same payment to the change label.
+		let mut tx = tx;
+		tx.output[0].script_pubkey = ScriptBuf::from_hex(
+			"51204bca8dfeaf7c24df9f455119456e1cb3d754597bd9b50a98b7c400c81f483fdd").unwrap();
+		let info = scan(&tx, &prevouts, &scan_key, &address.spend_key, None, &[0]).unwrap();
+		assert_eq!(info.outputs.len(), 1);
+		assert_eq!(info.outputs[0].label, Some(0));
+		assert_eq!(info.outputs[0].tweak.0, hex::decode(
+			"338913e4ac2fed1b111a237829c60722b977978596f240c58668362f6bc289a9").unwrap());
+		assert!(scan(&tx, &prevouts, &scan_key, &address.spend_key, None, &[]).unwrap().outputs.is_empty());
+	}
+
+	#[test]
+	fn test_bip352_labels() {
+		let scan_key = SecretKey::from_str(
+			"0f694e068028a717f8af6b9411f9a133dd3565258714cc226594b34db90c1f2c").unwrap();
+		let spend_key = SecretKey::from_str(
+			"9d6ad855ce3417ef84e836892e5a56392bfba05fa5d97ccea30e266f540e08b3").unwrap();
+		let labeled_address = |m| SilentPaymentAddress {
+			testnet: false,
+			version: 0,
+			scan_key: scan_key.public_key(&SECP),
+			spend_key: labeled_spend_key(&spend_key.public_key(&SECP), &scan_key, m).unwrap(),
+		}.to_string();
+
+		// The labeled addresses of the BIP-352 receiving test vectors.
+		assert_eq!(labeled_address(2), "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjex54dmqmmv6rw353tsuqhs99ydvadxzrsy9nuvk74epvee55drs734pqq");
+		assert_eq!(labeled_address(3), "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqsg59z2rppn4qlkx0yz9sdltmjv3j8zgcqadjn4ug98m3t6plujsq9qvu5n");
+		assert_eq!(labeled_address(1001337), "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgq7c2zfthc6x3a5yecwc52nxa0kfd20xuz08zyrjpfw4l2j257yq6qgnkdh5");
+
+		// The change label m=0, of which the tweak is hash_BIP0352/Label(b_scan || 0).
+		assert_eq!(
+			label_tweak(&scan_key, 0).to_be_bytes(),
+			hex::decode("3f505fe3328c28f4e338f9f168df3a4110e66c9e7e9ec3340819dad07910dbf4").unwrap()[..],
+		);
+		assert_eq!(labeled_address(0), "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqma6khaxl7yel6xkzewummrwyze6k4grua2g6fvwtvja5eaf6apwgcl3l6s");
+	}
+}
@@ -0,0 +1,642 @@
+//! SLIP-39 Shamir's Secret-Sharing for Mnemonic Codes.
+
+use std::collections::BTreeMap;
+
+use bitcoin::bip32;
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use bitcoin::secp256k1::rand::RngCore;
+use bitcoin::Network;
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::pbkdf2_sha256;
+use crate::HexBytes;
+
+/// The number of bits encoded in a word.
+const RADIX_BITS: usize = 10;
+/// The number of bits of the share identifier.
+const ID_BITS: usize = 15;
+/// The number of words of the share metadata and the checksum.
+const METADATA_WORDS: usize = 7;
+/// The number of words of the checksum.
+const CHECKSUM_WORDS: usize = 3;
+/// The minimum length of the master secret in bytes.
+const MIN_SECRET_BYTES: usize = 16;
+/// The maximum number of shares in a group and of groups.
+const MAX_SHARE_COUNT: u8 = 16;
+/// The number of PBKDF2 iterations for each round of the encryption, before applying
+/// the iteration exponent.
+const BASE_ITERATION_COUNT: u32 = 10000;
+/// The number of rounds of the Feistel encryption.
+const ROUND_COUNT: u8 = 4;
+/// The x coordinate of the share that holds the digest.
+const DIGEST_INDEX: u8 = 254;
+/// The x coordinate of the share that holds the secret.
+const SECRET_INDEX: u8 = 255;
+const DIGEST_BYTES: usize = 4;
+
+/// The exponent and logarithm tables of GF(256) with the Rijndael polynomial.
+struct Gf256 {
+	exp: [u8; 255],
+	log: [u8; 256],
+}
+
+lazy_static! {
+	static ref GF256: Gf256 = {
+		let mut exp = [0u8; 255];
+		let mut log = [0u8; 256];
+		let mut poly: u16 = 1;
+		for (i, e) in exp.iter_mut().enumerate() {
+			*e = poly as u8;
+			log[poly as usize] = i as u8;
+			// Multiply by the generator 3.
+			poly = (poly << 1) ^ poly;
+			if poly & 0x100 != 0 {
+				poly ^= 0x11b;
+			}
+		}
+		Gf256 { exp, log }
+	};
+}
+
+/// Evaluate at x the polynomial that goes through the given shares.
+fn interpolate(shares: &[(u8, Vec<u8>)], x: u8) -> Result<Vec<u8>, &'static str> {
+	if let Some(share) = shares.iter().find(|s| s.0 == x) {
+		return Ok(share.1.clone());
+	}
+	let len = shares.first().ok_or("no shares")?.1.len();
+	if shares.iter().any(|s| s.1.len() != len) {
+		return Err("all shares must have the same length");
+	}
+
+	let gf = &*GF256;
+	let log = |v: u8| gf.log[v as usize] as usize;
+	let log_prod: usize = shares.iter().map(|s| log(s.0 ^ x)).sum();
+	let mut ret = vec![0u8; len];
+	for share in shares {
+		let others: usize = shares.iter().map(|o| log(share.0 ^ o.0)).sum();
+		let log_basis = (log_prod + 255 * shares.len() * 2 - log(share.0 ^ x) - others) % 255;
+		for (r, v) in ret.iter_mut().zip(share.1.iter()) {
+			if *v != 0 {
+				*r ^= gf.exp[(log(*v) + log_basis) % 255];
+			}
+		}
+	}
+	Ok(ret)
+}
+
+fn create_digest(random: &[u8], secret: &[u8]) -> [u8; DIGEST_BYTES] {
+	let mut engine = hmac::HmacEngine::<sha256::Hash>::new(random);
+	engine.input(secret);
+	let hmac = hmac::Hmac::from_engine(engine);
+	let mut ret = [0u8; DIGEST_BYTES];
+	ret.copy_from_slice(&hmac[..DIGEST_BYTES]);
+	ret
+}
+
+/// Split a secret into shares of which threshold are needed to recover it.
+fn split_secret<R: RngCore>(
+	threshold: u8,
+	count: u8,
+	secret: &[u8],
+	rng: &mut R,
+) -> Result<Vec<(u8, Vec<u8>)>, &'static str> {
+	if threshold == 0 || threshold > count {
+		return Err("the threshold must be between 1 and the number of shares");
+	}
+	if count > MAX_SHARE_COUNT {
+		return Err("the number of shares can be at most 16");
+	}
+	if threshold == 1 {
+		return Ok((0..count).map(|i| (i, secret.to_vec())).collect());
+	}
+
+	let random_bytes = |rng: &mut R, len: usize| {
+		let mut ret = vec![0u8; len];
+		rng.fill_bytes(&mut ret);
+		ret
+	};
+	let mut shares = (0..threshold - 2)
+		.map(|i| (i, random_bytes(rng, secret.len())))
+		.collect::<Vec<_>>();
+	let random_part = random_bytes(rng, secret.len() - DIGEST_BYTES);
+	let mut digest_share = create_digest(&random_part, secret).to_vec();
+	digest_share.extend(&random_part);
+
+	let mut base = shares.clone();
+	base.push((DIGEST_INDEX, digest_share));
+	base.push((SECRET_INDEX, secret.to_vec()));
+	for i in threshold - 2..count {
+		shares.push((i, interpolate(&base, i)?));
+	}
+	Ok(shares)
+}
+
+/// Recover a secret from threshold shares.
+fn recover_secret(threshold: u8, shares: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, &'static str> {
+	if threshold == 1 {
+		return Ok(shares.first().ok_or("no shares")?.1.clone());
+	}
+	let secret = interpolate(shares, SECRET_INDEX)?;
+	let digest_share = interpolate(shares, DIGEST_INDEX)?;
+	if digest_share[..DIGEST_BYTES] != create_digest(&digest_share[DIGEST_BYTES..], &secret) {
+		return Err("invalid digest of the shared secret");
+	}
+	Ok(secret)
+}
+
+/// Encrypt or decrypt the master secret with the passphrase using the Feistel network.
+fn feistel(
+	secret: &[u8],
+	passphrase: &[u8],
+	iteration_exponent: u8,
+	identifier: u16,
+	extendable: bool,
+	decrypt: bool,
+) -> Vec<u8> {
+	let half = secret.len() / 2;
+	let mut left = secret[..half].to_vec();
+	let mut right = secret[half..].to_vec();
+	let mut salt = Vec::new();
+	if !extendable {
+		salt.extend(b"shamir");
+		salt.extend(&identifier.to_be_bytes());
+	}
+	let iterations = (BASE_ITERATION_COUNT << iteration_exponent) / ROUND_COUNT as u32;
+
+	let mut rounds = (0..ROUND_COUNT).collect::<Vec<_>>();
+	if decrypt {
+		rounds.reverse();
+	}
+	for i in rounds {
+		let mut password = vec![i];
+		password.extend(passphrase);
+		let mut round_salt = salt.clone();
+		round_salt.extend(&right);
+		let f = pbkdf2_sha256(&password, &round_salt, iterations, right.len());
+		let new_right = left.iter().zip(f.iter()).map(|(l, f)| l ^ f).collect();
+		left = std::mem::replace(&mut right, new_right);
+	}
+	right.extend(left);
+	right
+}
+
+/// The RS1024 checksum over the customization string and the word indices.
+fn rs1024_polymod(values: impl IntoIterator<Item = u16>) -> u32 {
+	const GEN: [u32; 10] = [
+		0xe0e040, 0x1c1c080, 0x3838100, 0x7070200, 0xe0e0009,
+		0x1c0c2412, 0x38086c24, 0x3090fc48, 0x21b1f890, 0x3f3f120,
+	];
+	let mut chk = 1u32;
+	for v in values {
+		let b = chk >> 20;
+		chk = ((chk & 0xfffff) << 10) ^ v as u32;
+		for (i, g) in GEN.iter().enumerate() {
+			if (b >> i) & 1 != 0 {
+				chk ^= g;
+			}
+		}
+	}
+	chk
+}
+
+fn customization(extendable: bool) -> &'static [u8] {
+	if extendable { b"shamir_extendable" } else { b"shamir" }
+}
+
+/// A single SLIP-39 share.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Share {
+	pub identifier: u16,
+	pub extendable: bool,
+	pub iteration_exponent: u8,
+	pub group_index: u8,
+	pub group_threshold: u8,
+	pub group_count: u8,
+	pub member_index: u8,
+	pub member_threshold: u8,
+	pub value: Vec<u8>,
+}
+
+impl Share {
+	/// Parse a share from its mnemonic.
+	pub fn from_mnemonic(mnemonic: &str) -> Result<Share, &'static str> {
+		let indices = mnemonic.split_whitespace().map(|w| {
+			let w = w.to_lowercase();
+			WORDLIST.iter().position(|l| *l == w).map(|i| i as u16)
+		}).collect::<Option<Vec<_>>>().ok_or("invalid word in mnemonic")?;
+		if indices.len() < METADATA_WORDS + (MIN_SECRET_BYTES * 8).div_ceil(RADIX_BITS) {
+			return Err("mnemonic is too short");
+		}
+		let padding = (RADIX_BITS * (indices.len() - METADATA_WORDS)) % 16;
+		if padding > 8 {
+			return Err("invalid mnemonic length");
+		}
+
+		let header = indices[..4].iter().fold(0u64, |acc, i| (acc << RADIX_BITS) | *i as u64);
+		let field = |shift: usize, bits: usize| ((header >> shift) & ((1 << bits) - 1)) as u8;
+		let identifier = (header >> 25) as u16;
+		let extendable = field(24, 1) == 1;
+		let cs = customization(extendable).iter().map(|b| *b as u16);
+		if rs1024_polymod(cs.chain(indices.iter().cloned())) != 1 {
+			return Err("invalid mnemonic checksum");
+		}
+
+		let value_words = &indices[4..indices.len() - CHECKSUM_WORDS];
+		let mut bits = Vec::with_capacity(value_words.len() * RADIX_BITS);
+		for w in value_words {
+			bits.extend((0..RADIX_BITS).rev().map(|i| (w >> i) & 1 == 1));
+		}
+		if bits[..padding].iter().any(|b| *b) {
+			return Err("invalid mnemonic padding");
+		}
+		let value = bits[padding..].chunks(8)
+			.map(|c| c.iter().fold(0u8, |acc, b| (acc << 1) | *b as u8))
+			.collect::<Vec<_>>();
+
+		let share = Share {
+			identifier,
+			extendable,
+			iteration_exponent: field(20, 4),
+			group_index: field(16, 4),
+			group_threshold: field(12, 4) + 1,
+			group_count: field(8, 4) + 1,
+			member_index: field(4, 4),
+			member_threshold: field(0, 4) + 1,
+			value,
+		};
+		if share.group_threshold > share.group_count {
+			return Err("group threshold exceeds the number of groups");
+		}
+		Ok(share)
+	}
+
+	/// Encode the share as a mnemonic.
+	pub fn to_mnemonic(&self) -> String {
+		let header = (self.identifier as u64) << 25
+			| (self.extendable as u64) << 24
+			| (self.iteration_exponent as u64) << 20
+			| (self.group_index as u64) << 16
+			| (self.group_threshold as u64 - 1) << 12
+			| (self.group_count as u64 - 1) << 8
+			| (self.member_index as u64) << 4
+			| (self.member_threshold as u64 - 1);
+		let mut indices = (0..4).rev()
+			.map(|i| ((header >> (i * RADIX_BITS)) & 0x3ff) as u16)
+			.collect::<Vec<_>>();
+
+		let nb_words = (self.value.len() * 8).div_ceil(RADIX_BITS);
+		let mut bits = vec![false; nb_words * RADIX_BITS - self.value.len() * 8];
+		for b in &self.value {
+			bits.extend((0..8).rev().map(|i| (b >> i) & 1 == 1));
+		}
+		indices.extend(bits.chunks(RADIX_BITS)
+			.map(|c| c.iter().fold(0u16, |acc, b| (acc << 1) | *b as u16)));
+
+		let cs = customization(self.extendable).iter().map(|b| *b as u16);
+		let chk = rs1024_polymod(
+			cs.chain(indices.iter().cloned()).chain([0u16; CHECKSUM_WORDS].iter().cloned()),
+		) ^ 1;
+		indices.extend((0..CHECKSUM_WORDS).rev().map(|i| ((chk >> (i * RADIX_BITS)) & 0x3ff) as u16));
+
+		indices.iter().map(|i| WORDLIST[*i as usize]).collect::<Vec<_>>().join(" ")
+	}
+}
+
+/// Split a master secret into groups of shares.
+///
+/// The groups are given as pairs of member threshold and member count.
+pub fn split<R: RngCore>(
+	master_secret: &[u8],
+	passphrase: &str,
+	group_threshold: u8,
+	groups: &[(u8, u8)],
+	iteration_exponent: u8,
+	extendable: bool,
+	rng: &mut R,
+) -> Result<Vec<Vec<Share>>, &'static str> {
+	if master_secret.len() < MIN_SECRET_BYTES || master_secret.len() % 2 != 0 {
+		return Err("the master secret must be an even number of at least 16 bytes");
+	}
+	if !passphrase.bytes().all(|b| (32..=126).contains(&b)) {
+		return Err("the passphrase must contain only printable ASCII characters");
+	}
+	if iteration_exponent > 15 {
+		return Err("the iteration exponent can be at most 15");
+	}
+	if groups.len() > MAX_SHARE_COUNT as usize {
+		return Err("there can be at most 16 groups");
+	}
+	if groups.iter().any(|(t, n)| *t == 1 && *n > 1) {
+		return Err("a member threshold of 1 requires a single share in the group");
+	}
+
+	let mut id_bytes = [0u8; 2];
+	rng.fill_bytes(&mut id_bytes);
+	let identifier = u16::from_be_bytes(id_bytes) & ((1 << ID_BITS) - 1);
+	let encrypted = feistel(
+		master_secret, passphrase.as_bytes(), iteration_exponent, identifier, extendable, false,
+	);
+
+	let group_secrets = split_secret(group_threshold, groups.len() as u8, &encrypted, rng)?;
+	group_secrets.into_iter().zip(groups.iter()).map(|((group_index, secret), (t, n))| {
+		Ok(split_secret(*t, *n, &secret, rng)?.into_iter().map(|(member_index, value)| Share {
+			identifier,
+			extendable,
+			iteration_exponent,
+			group_index,
+			group_threshold,
+			group_count: groups.len() as u8,
+			member_index,
+			member_threshold: *t,
+			value,
+		}).collect())
+	}).collect()
+}
+
+/// Combine shares to recover the master secret.
+pub fn combine(shares: &[Share], passphrase: &str) -> Result<Vec<u8>, &'static str> {
+	let first = shares.first().ok_or("no shares given")?;
+	if shares.iter().any(|s| {
+		s.identifier != first.identifier || s.extendable != first.extendable
+			|| s.iteration_exponent != first.iteration_exponent
+			|| s.group_threshold != first.group_threshold || s.group_count != first.group_count
+	}) {
+		return Err("the shares don't belong to the same secret");
+	}
+
+	let mut groups = BTreeMap::<u8, Vec<&Share>>::new();
+	for share in shares {
+		groups.entry(share.group_index).or_default().push(share);
+	}
+	if groups.len() < first.group_threshold as usize {
+		return Err("not enough groups to reach the group threshold");
+	}
+
+	let mut group_secrets = Vec::new();
+	for (group_index, members) in groups {
+		let threshold = members[0].member_threshold;
+		if members.iter().any(|m| m.member_threshold != threshold) {
+			return Err("the shares of a group have different thresholds");
+		}
+		let mut values = members.iter().map(|m| (m.member_index, m.value.clone())).collect::<Vec<_>>();
+		values.sort();
+		values.dedup();
+		if values.len() < threshold as usize {
+			return Err("not enough shares to reach the member threshold of a group");
+		}
+		values.truncate(threshold as usize);
+		group_secrets.push((group_index, recover_secret(threshold, &values)?));
+	}
+	group_secrets.truncate(first.group_threshold as usize);
+	let encrypted = recover_secret(first.group_threshold, &group_secrets)?;
+
+	Ok(feistel(
+		&encrypted, passphrase.as_bytes(), first.iteration_exponent, first.identifier,
+		first.extendable, true,
+	))
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ShareInfo {
+	pub identifier: u16,
+	pub extendable: bool,
+	pub iteration_exponent: u8,
+	pub group_index: u8,
+	pub group_threshold: u8,
+	pub group_count: u8,
+	pub member_index: u8,
+	pub member_threshold: u8,
+	pub value: HexBytes,
+}
+
+impl<'a> From<&'a Share> for ShareInfo {
+	fn from(s: &'a Share) -> ShareInfo {
+		ShareInfo {
+			identifier: s.identifier,
+			extendable: s.extendable,
+			iteration_exponent: s.iteration_exponent,
+			group_index: s.group_index,
+			group_threshold: s.group_threshold,
+			group_count: s.group_count,
+			member_index: s.member_index,
+			member_threshold: s.member_threshold,
+			value: s.value.clone().into(),
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct MasterSecretInfo {
+	pub master_secret: HexBytes,
+	/// The BIP-39 mnemonic with the master secret as entropy.
+	///
+	/// Note that this is not the same wallet, because SLIP-39 uses the master
+	/// secret directly as the BIP-32 seed.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub bip39_mnemonic: Option<String>,
+	pub bip32_xpriv: bip32::Xpriv,
+	pub bip32_xpub: bip32::Xpub,
+}
+
+impl MasterSecretInfo {
+	pub fn new(master_secret: &[u8], network: Network) -> Result<MasterSecretInfo, &'static str> {
+		let xpriv = bip32::Xpriv::new_master(network, master_secret)
+			.map_err(|_| "invalid master secret")?;
+		Ok(MasterSecretInfo {
+			master_secret: master_secret.to_vec().into(),
+			bip39_mnemonic: bip39lib::Mnemonic::from_entropy(master_secret).ok()
+				.map(|m| m.to_string()),
+			bip32_xpriv: xpriv,
+			bip32_xpub: bip32::Xpub::from_priv(&crate::SECP, &xpriv),
+		})
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct GroupInfo {
+	pub member_threshold: u8,
+	pub shares: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct SplitInfo {
+	pub identifier: u16,
+	pub extendable: bool,
+	pub iteration_exponent: u8,
+	pub group_threshold: u8,
+	pub groups: Vec<GroupInfo>,
+	pub secret: MasterSecretInfo,
+}
+
+/// The SLIP-39 wordlist.
+#[rustfmt::skip]
+const WORDLIST: [&str; 1024] = [
+	"academic", "acid", "acne", "acquire", "acrobat", "activity", "actress", "adapt",
+	"adequate", "adjust", "admit", "adorn", "adult", "advance", "advocate", "afraid",
+	"again", "agency", "agree", "aide", "aircraft", "airline", "airport", "ajar",
+	"alarm", "album", "alcohol", "alien", "alive", "alpha", "already", "alto",
+	"aluminum", "always", "amazing", "ambition", "amount", "amuse", "analysis", "anatomy",
+	"ancestor", "ancient", "angel", "angry", "animal", "answer", "antenna", "anxiety",
+	"apart", "aquatic", "arcade", "arena", "argue", "armed", "artist", "artwork",
+	"aspect", "auction", "august", "aunt", "average", "aviation", "avoid", "award",
+	"away", "axis", "axle", "beam", "beard", "beaver", "become", "bedroom",
+	"behavior", "being", "believe", "belong", "benefit", "best", "beyond", "bike",
+	"biology", "birthday", "bishop", "black", "blanket", "blessing", "blimp", "blind",
+	"blue", "body", "bolt", "boring", "born", "both", "boundary", "bracelet",
+	"branch", "brave", "breathe", "briefing", "broken", "brother", "browser", "bucket",
+	"budget", "building", "bulb", "bulge", "bumpy", "bundle", "burden", "burning",
+	"busy", "buyer", "cage", "calcium", "camera", "campus", "canyon", "capacity",
+	"capital", "capture", "carbon", "cards", "careful", "cargo", "carpet", "carve",
+	"category", "cause", "ceiling", "center", "ceramic", "champion", "change", "charity",
+	"check", "chemical", "chest", "chew", "chubby", "cinema", "civil", "class",
+	"clay", "cleanup", "client", "climate", "clinic", "clock", "clogs", "closet",
+	"clothes", "club", "cluster", "coal", "coastal", "coding", "column", "company",
+	"corner", "costume", "counter", "course", "cover", "cowboy", "cradle", "craft",
+	"crazy", "credit", "cricket", "criminal", "crisis", "critical", "crowd", "crucial",
+	"crunch", "crush", "crystal", "cubic", "cultural", "curious", "curly", "custody",
+	"cylinder", "daisy", "damage", "dance", "darkness", "database", "daughter", "deadline",
+	"deal", "debris", "debut", "decent", "decision", "declare", "decorate", "decrease",
+	"deliver", "demand", "density", "deny", "depart", "depend", "depict", "deploy",
+	"describe", "desert", "desire", "desktop", "destroy", "detailed", "detect", "device",
+	"devote", "diagnose", "dictate", "diet", "dilemma", "diminish", "dining", "diploma",
+	"disaster", "discuss", "disease", "dish", "dismiss", "display", "distance", "dive",
+	"divorce", "document", "domain", "domestic", "dominant", "dough", "downtown", "dragon",
+	"dramatic", "dream", "dress", "drift", "drink", "drove", "drug", "dryer",
+	"duckling", "duke", "duration", "dwarf", "dynamic", "early", "earth", "easel",
+	"easy", "echo", "eclipse", "ecology", "edge", "editor", "educate", "either",
+	"elbow", "elder", "election", "elegant", "element", "elephant", "elevator", "elite",
+	"else", "email", "emerald", "emission", "emperor", "emphasis", "employer", "empty",
+	"ending", "endless", "endorse", "enemy", "energy", "enforce", "engage", "enjoy",
+	"enlarge", "entrance", "envelope", "envy", "epidemic", "episode", "equation", "equip",
+	"eraser", "erode", "escape", "estate", "estimate", "evaluate", "evening", "evidence",
+	"evil", "evoke", "exact", "example", "exceed", "exchange", "exclude", "excuse",
+	"execute", "exercise", "exhaust", "exotic", "expand", "expect", "explain", "express",
+	"extend", "extra", "eyebrow", "facility", "fact", "failure", "faint", "fake",
+	"false", "family", "famous", "fancy", "fangs", "fantasy", "fatal", "fatigue",
+	"favorite", "fawn", "fiber", "fiction", "filter", "finance", "findings", "finger",
+	"firefly", "firm", "fiscal", "fishing", "fitness", "flame", "flash", "flavor",
+	"flea", "flexible", "flip", "float", "floral", "fluff", "focus", "forbid",
+	"force", "forecast", "forget", "formal", "fortune", "forward", "founder", "fraction",
+	"fragment", "frequent", "freshman", "friar", "fridge", "friendly", "frost", "froth",
+	"frozen", "fumes", "funding", "furl", "fused", "galaxy", "game", "garbage",
+	"garden", "garlic", "gasoline", "gather", "general", "genius", "genre", "genuine",
+	"geology", "gesture", "glad", "glance", "glasses", "glen", "glimpse", "goat",
+	"golden", "graduate", "grant", "grasp", "gravity", "gray", "greatest", "grief",
+	"grill", "grin", "grocery", "gross", "group", "grownup", "grumpy", "guard",
+	"guest", "guilt", "guitar", "gums", "hairy", "hamster", "hand", "hanger",
+	"harvest", "have", "havoc", "hawk", "hazard", "headset", "health", "hearing",
+	"heat", "helpful", "herald", "herd", "hesitate", "hobo", "holiday", "holy",
+	"home", "hormone", "hospital", "hour", "huge", "human", "humidity", "hunting",
+	"husband", "hush", "husky", "hybrid", "idea", "identify", "idle", "image",
+	"impact", "imply", "improve", "impulse", "include", "income", "increase", "index",
+	"indicate", "industry", "infant", "inform", "inherit", "injury", "inmate", "insect",
+	"inside", "install", "intend", "intimate", "invasion", "involve", "iris", "island",
+	"isolate", "item", "ivory", "jacket", "jerky", "jewelry", "join", "judicial",
+	"juice", "jump", "junction", "junior", "junk", "jury", "justice", "kernel",
+	"keyboard", "kidney", "kind", "kitchen", "knife", "knit", "laden", "ladle",
+	"ladybug", "lair", "lamp", "language", "large", "laser", "laundry", "lawsuit",
+	"leader", "leaf", "learn", "leaves", "lecture", "legal", "legend", "legs",
+	"lend", "length", "level", "liberty", "library", "license", "lift", "likely",
+	"lilac", "lily", "lips", "liquid", "listen", "literary", "living", "lizard",
+	"loan", "lobe", "location", "losing", "loud", "loyalty", "luck", "lunar",
+	"lunch", "lungs", "luxury", "lying", "lyrics", "machine", "magazine", "maiden",
+	"mailman", "main", "makeup", "making", "mama", "manager", "mandate", "mansion",
+	"manual", "marathon", "march", "market", "marvel", "mason", "material", "math",
+	"maximum", "mayor", "meaning", "medal", "medical", "member", "memory", "mental",
+	"merchant", "merit", "method", "metric", "midst", "mild", "military", "mineral",
+	"minister", "miracle", "mixed", "mixture", "mobile", "modern", "modify", "moisture",
+	"moment", "morning", "mortgage", "mother", "mountain", "mouse", "move", "much",
+	"mule", "multiple", "muscle", "museum", "music", "mustang", "nail", "national",
+	"necklace", "negative", "nervous", "network", "news", "nuclear", "numb", "numerous",
+	"nylon", "oasis", "obesity", "object", "observe", "obtain", "ocean", "often",
+	"olympic", "omit", "oral", "orange", "orbit", "order", "ordinary", "organize",
+	"ounce", "oven", "overall", "owner", "paces", "pacific", "package", "paid",
+	"painting", "pajamas", "pancake", "pants", "papa", "paper", "parcel", "parking",
+	"party", "patent", "patrol", "payment", "payroll", "peaceful", "peanut", "peasant",
+	"pecan", "penalty", "pencil", "percent", "perfect", "permit", "petition", "phantom",
+	"pharmacy", "photo", "phrase", "physics", "pickup", "picture", "piece", "pile",
+	"pink", "pipeline", "pistol", "pitch", "plains", "plan", "plastic", "platform",
+	"playoff", "pleasure", "plot", "plunge", "practice", "prayer", "preach", "predator",
+	"pregnant", "premium", "prepare", "presence", "prevent", "priest", "primary", "priority",
+	"prisoner", "privacy", "prize", "problem", "process", "profile", "program", "promise",
+	"prospect", "provide", "prune", "public", "pulse", "pumps", "punish", "puny",
+	"pupal", "purchase", "purple", "python", "quantity", "quarter", "quick", "quiet",
+	"race", "racism", "radar", "railroad", "rainbow", "raisin", "random", "ranked",
+	"rapids", "raspy", "reaction", "realize", "rebound", "rebuild", "recall", "receiver",
+	"recover", "regret", "regular", "reject", "relate", "remember", "remind", "remove",
+	"render", "repair", "repeat", "replace", "require", "rescue", "research", "resident",
+	"response", "result", "retailer", "retreat", "reunion", "revenue", "review", "reward",
+	"rhyme", "rhythm", "rich", "rival", "river", "robin", "rocky", "romantic",
+	"romp", "roster", "round", "royal", "ruin", "ruler", "rumor", "sack",
+	"safari", "salary", "salon", "salt", "satisfy", "satoshi", "saver", "says",
+	"scandal", "scared", "scatter", "scene", "scholar", "science", "scout", "scramble",
+	"screw", "script", "scroll", "seafood", "season", "secret", "security", "segment",
+	"senior", "shadow", "shaft", "shame", "shaped", "sharp", "shelter", "sheriff",
+	"short", "should", "shrimp", "sidewalk", "silent", "silver", "similar", "simple",
+	"single", "sister", "skin", "skunk", "slap", "slavery", "sled", "slice",
+	"slim", "slow", "slush", "smart", "smear", "smell", "smirk", "smith",
+	"smoking", "smug", "snake", "snapshot", "sniff", "society", "software", "soldier",
+	"solution", "soul", "source", "space", "spark", "speak", "species", "spelling",
+	"spend", "spew", "spider", "spill", "spine", "spirit", "spit", "spray",
+	"sprinkle", "square", "squeeze", "stadium", "staff", "standard", "starting", "station",
+	"stay", "steady", "step", "stick", "stilt", "story", "strategy", "strike",
+	"style", "subject", "submit", "sugar", "suitable", "sunlight", "superior", "surface",
+	"surprise", "survive", "sweater", "swimming", "swing", "switch", "symbolic", "sympathy",
+	"syndrome", "system", "tackle", "tactics", "tadpole", "talent", "task", "taste",
+	"taught", "taxi", "teacher", "teammate", "teaspoon", "temple", "tenant", "tendency",
+	"tension", "terminal", "testify", "texture", "thank", "that", "theater", "theory",
+	"therapy", "thorn", "threaten", "thumb", "thunder", "ticket", "tidy", "timber",
+	"timely", "ting", "tofu", "together", "tolerate", "total", "toxic", "tracks",
+	"traffic", "training", "transfer", "trash", "traveler", "treat", "trend", "trial",
+	"tricycle", "trip", "triumph", "trouble", "true", "trust", "twice", "twin",
+	"type", "typical", "ugly", "ultimate", "umbrella", "uncover", "undergo", "unfair",
+	"unfold", "unhappy", "union", "universe", "unkind", "unknown", "unusual", "unwrap",
+	"upgrade", "upstairs", "username", "usher", "usual", "valid", "valuable", "vampire",
+	"vanish", "various", "vegan", "velvet", "venture", "verdict", "verify", "very",
+	"veteran", "vexed", "victim", "video", "view", "vintage", "violence", "viral",
+	"visitor", "visual", "vitamins", "vocal", "voice", "volume", "voter", "voting",
+	"walnut", "warmth", "warn", "watch", "wavy", "wealthy", "weapon", "webcam",
+	"welcome", "welfare", "western", "width", "wildlife", "window", "wine", "wireless",
+	"wisdom", "withdraw", "wits", "wolf", "woman", "work", "worthy", "wrap",
+	"wrist", "writing", "wrote", "year", "yelp", "yield", "yoga", "zero",
+];
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use bitcoin::secp256k1::rand;
+
+	#[test]
+	fn test_vectors() {
+		// The first vector of the SLIP-39 test vectors.
+		let share = Share::from_mnemonic("duckling enlarge academic academic agency result \
+			length solution fridge kidney coal piece deal husband erode duke ajar critical \
+			decision keyboard").unwrap();
+		assert_eq!(share.to_mnemonic().split(' ').next(), Some("duckling"));
+		let secret = combine(&[share], "TREZOR").unwrap();
+		assert_eq!(hex::encode(secret), "bb54aac4b89dc868ba37d9cc21b2cece");
+
+		// A 2-of-3 share set.
+		let shares = [
+			"shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang \
+			wrist rescue view short owner flip making coding armed",
+			"shadow pistol academic acid actress prayer class unknown daughter sweater depict \
+			flip twice unkind craft early superior advocate guest smoking",
+		].iter().map(|m| Share::from_mnemonic(m).unwrap()).collect::<Vec<_>>();
+		let secret = combine(&shares, "TREZOR").unwrap();
+		assert_eq!(hex::encode(secret), "b43ceb7e57a0ea8766221624d01b0864");
+		assert!(combine(&shares[..1], "TREZOR").is_err());
+	}
+
+	#[test]
+	fn test_split_combine() {
+		let secret = [0x42u8; 32];
+		let groups = split(&secret, "pass", 2, &[(1, 1), (2, 3), (3, 5)], 0, true,
+			&mut rand::thread_rng()).unwrap();
+		let shares = groups[1][1..].iter().chain(&groups[2][2..])
+			.map(|s| Share::from_mnemonic(&s.to_mnemonic()).unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(combine(&shares, "pass").unwrap(), secret);
+	}
+}
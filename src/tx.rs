@@ -60,31 +60,38 @@ pub struct OutputScriptInfo {
 
 pub struct OutputScript<'a>(pub &'a Script);
 
+/// All the output script types returned by [script_type].
+pub const SCRIPT_TYPES: &[&str] = &[
+	"p2pk", "p2pkh", "opreturn", "p2sh", "p2wpkh", "p2wsh", "p2tr", "unknown",
+];
+
+/// The type of an output script, like "p2wpkh", or "unknown".
+pub fn script_type(script: &Script) -> &'static str {
+	if script.is_p2pk() {
+		"p2pk"
+	} else if script.is_p2pkh() {
+		"p2pkh"
+	} else if script.is_op_return() {
+		"opreturn"
+	} else if script.is_p2sh() {
+		"p2sh"
+	} else if script.is_p2wpkh() {
+		"p2wpkh"
+	} else if script.is_p2wsh() {
+		"p2wsh"
+	} else if script.is_p2tr() {
+		"p2tr"
+	} else {
+		"unknown"
+	}
+}
+
 impl<'a> GetInfo<OutputScriptInfo> for OutputScript<'a> {
 	fn get_info(&self, network: Network) -> OutputScriptInfo {
 		OutputScriptInfo {
 			hex: Some(self.0.to_bytes().into()),
 			asm: Some(self.0.to_asm_string()),
-			type_: Some(
-				if self.0.is_p2pk() {
-					"p2pk"
-				} else if self.0.is_p2pkh() {
-					"p2pkh"
-				} else if self.0.is_op_return() {
-					"opreturn"
-				} else if self.0.is_p2sh() {
-					"p2sh"
-				} else if self.0.is_p2wpkh() {
-					"p2wpkh"
-				} else if self.0.is_p2wsh() {
-					"p2wsh"
-				} else if self.0.is_p2tr() {
-					"p2tr"
-				} else {
-					"unknown"
-				}
-				.to_owned(),
-			),
+			type_: Some(script_type(self.0).to_owned()),
 			address: Address::from_script(&self.0, network).ok().map(|a| a.as_unchecked().clone()),
 		}
 	}